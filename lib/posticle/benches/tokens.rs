@@ -0,0 +1,86 @@
+//! Compares reading and rendering a long status with owned and borrowed tokens, counting
+//! the allocations each makes. Run with `cargo bench`.
+
+extern crate posticle;
+
+use posticle::{borrowed, Reader};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Counts allocations on their way to the system allocator.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const ITERATIONS: usize = 200;
+
+/// Run `f` a few times, printing how long it took and how many allocations it made.
+fn bench<F>(name: &str, mut f: F)
+where
+    F: FnMut() -> usize,
+{
+    let mut total = 0;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        total += f();
+    }
+
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS;
+
+    println!(
+        "{:<24} {:>12?}/iter {:>8} allocations/iter ({} bytes out)",
+        name,
+        elapsed,
+        allocations,
+        total / ITERATIONS
+    );
+}
+
+fn main() {
+    let line = "Reading about #rust and #mastodon with @alice@example.com, see \
+                https://example.com/some/long/path?query=true for $RUST news :blobcat:\n";
+    let status = line.repeat(40);
+
+    bench("owned reader", || Reader::from(status.as_str()).count());
+    bench("borrowed reader", || {
+        borrowed::Reader::from(status.as_str()).count()
+    });
+
+    bench("owned render", || {
+        let mut output = String::new();
+        for token in Reader::from(status.as_str()) {
+            token.render(&mut output);
+        }
+        output.len()
+    });
+    bench("borrowed render", || {
+        let mut output = String::new();
+        for token in borrowed::Reader::from(status.as_str()) {
+            token.render(&mut output);
+        }
+        output.len()
+    });
+}
@@ -0,0 +1,645 @@
+//! Tokens which borrow their text from the input they were read from, rather than copying
+//! it, for reading lots of text without allocating a [`String`] for every token.
+//!
+//! Borrowed tokens are read without a transformer, and can be turned into owned
+//! [`tokens::Token`](crate::tokens::Token)s with [`Token::into_owned`].
+//!
+//! ```
+//! use posticle::borrowed::{Hashtag, Reader, Text, Token};
+//!
+//! let input = "hello #rust";
+//! let tokens: Vec<Token> = Reader::from(input).collect();
+//!
+//! assert_eq!(
+//!     tokens,
+//!     vec![
+//!         Token::Text(Text { text: "hello " }),
+//!         Token::Hashtag(Hashtag { name: "rust" }),
+//!     ]
+//! );
+//! ```
+
+use crate::grammar::{document, Rule};
+use crate::idn;
+use crate::tokens::{self, html_escape, LineBreak, Span, Spanned};
+use pest::iterators::Pair;
+use std::vec;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A textual emoticon.
+pub struct Emoticon<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A hashtag.
+pub struct Hashtag<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A cashtag, naming a stock symbol.
+pub struct Cashtag<'a> {
+    pub name: &'a str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A link to a resource.
+pub struct Link<'a> {
+    pub url: &'a str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A mention with an optional domain.
+pub struct Mention<'a> {
+    pub username: &'a str,
+    pub domain:   Option<&'a str>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Plain text that will have its entities encoded on render.
+pub struct Text<'a> {
+    pub text: &'a str,
+}
+
+#[cfg(feature = "markdown")]
+#[derive(Clone, Debug, PartialEq)]
+/// Strongly emphasised tokens, written `**like this**`.
+pub struct Strong<'a> {
+    pub children: Vec<Token<'a>>,
+}
+
+#[cfg(feature = "markdown")]
+#[derive(Clone, Debug, PartialEq)]
+/// Emphasised tokens, written `*like this*`.
+pub struct Emphasis<'a> {
+    pub children: Vec<Token<'a>>,
+}
+
+#[cfg(feature = "markdown")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Inline code, written `` `like this` ``.
+pub struct Code<'a> {
+    pub code: &'a str,
+}
+
+#[cfg(feature = "markdown")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Lines of code fenced by lines of three backticks.
+pub struct CodeBlock<'a> {
+    pub code: &'a str,
+}
+
+#[cfg(feature = "markdown")]
+#[derive(Clone, Debug, PartialEq)]
+/// Lines quoted with a `>` at the start of each, with a [`LineBreak`] between them.
+pub struct Quote<'a> {
+    pub children: Vec<Token<'a>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// An item in the abstract syntax tree, borrowing its text from the input.
+///
+/// There's no borrowed `Element`, since elements are only ever made by transformers.
+pub enum Token<'a> {
+    Emoticon(Emoticon<'a>),
+    Hashtag(Hashtag<'a>),
+    Cashtag(Cashtag<'a>),
+    LineBreak(LineBreak),
+    Link(Link<'a>),
+    Mention(Mention<'a>),
+    Text(Text<'a>),
+    #[cfg(feature = "markdown")]
+    Strong(Strong<'a>),
+    #[cfg(feature = "markdown")]
+    Emphasis(Emphasis<'a>),
+    #[cfg(feature = "markdown")]
+    Code(Code<'a>),
+    #[cfg(feature = "markdown")]
+    CodeBlock(CodeBlock<'a>),
+    #[cfg(feature = "markdown")]
+    Quote(Quote<'a>),
+}
+
+impl<'a> Token<'a> {
+    /// Copy the token's text into an owned [`tokens::Token`].
+    ///
+    /// ```
+    /// use posticle::borrowed;
+    /// use posticle::tokens;
+    ///
+    /// let token = borrowed::Token::Hashtag(borrowed::Hashtag { name: "rust" });
+    ///
+    /// assert_eq!(
+    ///     token.into_owned(),
+    ///     tokens::Token::Hashtag(tokens::Hashtag {
+    ///         name: "rust".to_string(),
+    ///     })
+    /// );
+    /// ```
+    pub fn into_owned(self) -> tokens::Token {
+        self.into_owned_with(&|token| token)
+    }
+
+    /// Copy the token into an owned [`tokens::Token`], passing it (and any tokens inside it,
+    /// first) through `transformer`.
+    pub(crate) fn into_owned_with(
+        self,
+        transformer: &dyn Fn(tokens::Token) -> tokens::Token,
+    ) -> tokens::Token {
+        #[cfg(feature = "markdown")]
+        let children = |children: Vec<Token>| -> Vec<tokens::Token> {
+            children
+                .into_iter()
+                .map(|child| child.into_owned_with(transformer))
+                .collect()
+        };
+
+        let token = match self {
+            Token::Emoticon(Emoticon { name }) => tokens::Token::Emoticon(tokens::Emoticon {
+                name: name.to_string(),
+            }),
+            Token::Hashtag(Hashtag { name }) => tokens::Token::Hashtag(tokens::Hashtag {
+                name: name.to_string(),
+            }),
+            Token::Cashtag(Cashtag { name }) => tokens::Token::Cashtag(tokens::Cashtag {
+                name: name.to_string(),
+            }),
+            Token::LineBreak(line_break) => tokens::Token::LineBreak(line_break),
+            Token::Link(Link { url }) => tokens::Token::Link(tokens::Link {
+                url: url.to_string(),
+            }),
+            Token::Mention(Mention { username, domain }) => {
+                tokens::Token::Mention(tokens::Mention {
+                    username: username.to_string(),
+                    domain:   domain.map(String::from),
+                })
+            },
+            Token::Text(Text { text }) => tokens::Token::Text(tokens::Text {
+                text: text.to_string(),
+            }),
+            #[cfg(feature = "markdown")]
+            Token::Strong(Strong { children: inner }) => tokens::Token::Strong(tokens::Strong {
+                children: children(inner),
+            }),
+            #[cfg(feature = "markdown")]
+            Token::Emphasis(Emphasis { children: inner }) => {
+                tokens::Token::Emphasis(tokens::Emphasis {
+                    children: children(inner),
+                })
+            },
+            #[cfg(feature = "markdown")]
+            Token::Code(Code { code }) => tokens::Token::Code(tokens::Code {
+                code: code.to_string(),
+            }),
+            #[cfg(feature = "markdown")]
+            Token::CodeBlock(CodeBlock { code }) => tokens::Token::CodeBlock(tokens::CodeBlock {
+                code: code.to_string(),
+            }),
+            #[cfg(feature = "markdown")]
+            Token::Quote(Quote { children: inner }) => tokens::Token::Quote(tokens::Quote {
+                children: children(inner),
+            }),
+        };
+
+        transformer(token)
+    }
+
+    /// Render the token as HTML, the same way as its owned [`tokens::Token`].
+    pub fn render(&self, output: &mut String) {
+        match self {
+            Token::Emoticon(Emoticon { name }) => {
+                output.push(':');
+                output.push_str(&html_escape(name));
+                output.push(':');
+            },
+            Token::Hashtag(Hashtag { name }) => {
+                output.push('#');
+                output.push_str(&html_escape(name));
+            },
+            Token::Cashtag(Cashtag { name }) => {
+                output.push('$');
+                output.push_str(&html_escape(name));
+            },
+            Token::LineBreak(line_break) => {
+                line_break.render(output);
+            },
+            Token::Link(Link { url }) => {
+                output.push_str(&html_escape(&idn::unicode_url(url)));
+            },
+            Token::Mention(Mention { username, domain }) => {
+                output.push('@');
+                output.push_str(&html_escape(username));
+
+                if let Some(domain) = domain {
+                    output.push('@');
+                    output.push_str(&html_escape(&idn::to_unicode(domain)));
+                }
+            },
+            Token::Text(Text { text }) => {
+                output.push_str(&html_escape(text));
+            },
+            #[cfg(feature = "markdown")]
+            Token::Strong(Strong { children }) => {
+                output.push_str("<strong>");
+                for child in children {
+                    child.render(output);
+                }
+                output.push_str("</strong>");
+            },
+            #[cfg(feature = "markdown")]
+            Token::Emphasis(Emphasis { children }) => {
+                output.push_str("<em>");
+                for child in children {
+                    child.render(output);
+                }
+                output.push_str("</em>");
+            },
+            #[cfg(feature = "markdown")]
+            Token::Code(Code { code }) => {
+                output.push_str("<code>");
+                output.push_str(&html_escape(code));
+                output.push_str("</code>");
+            },
+            #[cfg(feature = "markdown")]
+            Token::CodeBlock(CodeBlock { code }) => {
+                output.push_str("<pre><code>");
+                output.push_str(&html_escape(code));
+                output.push_str("</code></pre>");
+            },
+            #[cfg(feature = "markdown")]
+            Token::Quote(Quote { children }) => {
+                output.push_str("<blockquote>");
+                for child in children {
+                    child.render(output);
+                }
+                output.push_str("</blockquote>");
+            },
+        }
+    }
+}
+
+impl<'a> From<Token<'a>> for tokens::Token {
+    fn from(token: Token<'a>) -> Self {
+        token.into_owned()
+    }
+}
+
+/// Read borrowed [`Token`]s from a string, the same way as a [`crate::Reader`] reads owned
+/// ones, but without a transformer.
+///
+/// ```
+/// use posticle::borrowed::{Reader, Text, Token};
+/// use posticle::tokens::{LineBreak, Span, Spanned};
+///
+/// let tokens = Reader::from("a\nb").into_spanned_vec();
+///
+/// assert_eq!(
+///     tokens[1],
+///     Spanned {
+///         token: Token::LineBreak(LineBreak),
+///         span:  Span {
+///             start:      1,
+///             end:        2,
+///             char_start: 1,
+///             char_end:   2,
+///         },
+///     }
+/// );
+/// ```
+pub struct Reader<'a> {
+    tokens: vec::IntoIter<Spanned<Token<'a>>>,
+}
+
+impl<'a> From<&'a str> for Reader<'a> {
+    /// Read `input`, or read it as plain text if it can't be parsed.
+    fn from(input: &'a str) -> Self {
+        let mut tokens = match document(input) {
+            Ok(pairs) => {
+                let tokens = pairs
+                    .filter(|pair| pair.as_rule() != Rule::EOI)
+                    .flat_map(Spanned::<Token>::from_parse_pair)
+                    .collect();
+
+                normalize_text_tokens(input, 0, tokens)
+            },
+            Err(_) => read_plain_text(input),
+        };
+
+        Span::fill_char_offsets(input, tokens.iter_mut().map(|spanned| &mut spanned.span));
+
+        Reader {
+            tokens: tokens.into_iter(),
+        }
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokens.next().map(|spanned| spanned.token)
+    }
+}
+
+impl<'a> Reader<'a> {
+    /// Convert the [`Reader`] to a [`Vec`] of the [`Token`]s it has left.
+    pub fn into_vec(self) -> Vec<Token<'a>> {
+        self.collect()
+    }
+
+    /// Convert the [`Reader`] to a [`Vec`] of the [`Token`]s it has left, along with where in
+    /// the input each one was read from.
+    pub fn into_spanned_vec(self) -> Vec<Spanned<Token<'a>>> {
+        self.tokens.collect()
+    }
+}
+
+/// Read `input` as lines of text, with byte spans.
+pub(crate) fn read_plain_text<'a>(input: &'a str) -> Vec<Spanned<Token<'a>>> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    let mut break_start = 0;
+
+    for (i, line) in input.split('\n').enumerate() {
+        if i > 0 {
+            // the line break takes in any carriage returns trimmed off the line before it.
+            tokens.push(Spanned::new(
+                Token::LineBreak(LineBreak),
+                Span::bytes(break_start, offset),
+            ));
+        }
+
+        let text = line.trim_end_matches('\r');
+        if !text.is_empty() {
+            tokens.push(Spanned::new(
+                Token::Text(Text { text }),
+                Span::bytes(offset, offset + text.len()),
+            ));
+        }
+
+        break_start = offset + text.len();
+        offset += line.len() + 1;
+    }
+
+    tokens
+}
+
+/// Combine rows of text tokens read from `input`, which starts `offset` bytes into the text
+/// the spans count from. Text tokens which follow each other are always next to each other
+/// in the input, so they're combined by borrowing all of it at once.
+fn normalize_text_tokens<'a>(
+    input: &'a str,
+    offset: usize,
+    tokens: Vec<Spanned<Token<'a>>>,
+) -> Vec<Spanned<Token<'a>>> {
+    let mut output: Vec<Spanned<Token<'a>>> = Vec::with_capacity(tokens.len());
+
+    for spanned in tokens {
+        if let Some(previous) = output.last_mut() {
+            if let (Token::Text(_), Token::Text(_)) = (&previous.token, &spanned.token) {
+                let span = previous.span.to(spanned.span);
+
+                previous.token = Token::Text(Text {
+                    text: &input[span.start - offset..span.end - offset],
+                });
+                previous.span = span;
+                continue;
+            }
+        }
+
+        output.push(spanned);
+    }
+
+    output
+}
+
+impl<'a> Spanned<Token<'a>> {
+    /// Read the tokens in `pair`, with byte spans.
+    pub fn from_parse_pair(pair: Pair<'a, Rule>) -> Vec<Self> {
+        let span = Span::bytes(pair.as_span().start(), pair.as_span().end());
+
+        match pair.as_rule() {
+            Rule::emoticon => {
+                Self::from_symbol_rule(pair, |name| Token::Emoticon(Emoticon { name }))
+            },
+            Rule::hashtag | Rule::opening_hashtag => {
+                Self::from_symbol_rule(pair, |name| Token::Hashtag(Hashtag { name }))
+            },
+            Rule::cashtag | Rule::opening_cashtag => {
+                Self::from_symbol_rule(pair, |name| Token::Cashtag(Cashtag { name }))
+            },
+            Rule::line_break => vec![Self::new(Token::LineBreak(LineBreak), span)],
+            Rule::link | Rule::markdown_link | Rule::opening_link => Self::from_link_rule(pair),
+            Rule::mention | Rule::opening_mention => Self::from_mention_rule(pair),
+            #[cfg(feature = "markdown")]
+            Rule::strong => {
+                Self::from_container_rule(pair, |children| Token::Strong(Strong { children }))
+            },
+            #[cfg(feature = "markdown")]
+            Rule::emphasis => {
+                Self::from_container_rule(pair, |children| Token::Emphasis(Emphasis { children }))
+            },
+            #[cfg(feature = "markdown")]
+            Rule::quote => {
+                Self::from_container_rule(pair, |children| Token::Quote(Quote { children }))
+            },
+            #[cfg(feature = "markdown")]
+            Rule::code => Self::from_code_rule(pair, |code| Token::Code(Code { code })),
+            #[cfg(feature = "markdown")]
+            Rule::code_block => {
+                Self::from_code_rule(pair, |code| Token::CodeBlock(CodeBlock { code }))
+            },
+            _ => vec![Self::new(
+                Token::Text(Text {
+                    text: pair.as_str(),
+                }),
+                span,
+            )],
+        }
+    }
+
+    /// The span of a symbol (a hashtag, mention...) read from `pair`, leaving out the
+    /// prefix which was read before it, if any.
+    fn symbol_span(pair: &Pair<Rule>, prefix_end: Option<usize>) -> Span {
+        let start = prefix_end.unwrap_or_else(|| pair.as_span().start());
+
+        Span::bytes(start, pair.as_span().end())
+    }
+
+    /// Read a symbol with just a name, like an emoticon or a hashtag, handing its name to
+    /// `wrap`.
+    fn from_symbol_rule(pair: Pair<'a, Rule>, wrap: fn(&'a str) -> Token<'a>) -> Vec<Self> {
+        let mut tokens = Vec::new();
+        let mut name: Option<&'a str> = None;
+        let mut prefix_end: Option<usize> = None;
+        let outer = pair.clone();
+
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::emoticon_name | Rule::hashtag_name | Rule::cashtag_name => {
+                    name = Some(pair.as_str());
+                },
+                _ => {
+                    prefix_end = Some(pair.as_span().end());
+                    tokens.append(&mut Self::from_symbol_prefix(pair));
+                },
+            }
+        }
+
+        if let Some(name) = name {
+            let span = Self::symbol_span(&outer, prefix_end);
+            tokens.push(Self::new(wrap(name), span));
+        }
+
+        tokens
+    }
+
+    fn from_link_rule(pair: Pair<'a, Rule>) -> Vec<Self> {
+        let mut tokens = Vec::new();
+        let mut url_start: Option<usize> = None;
+        let mut has_tail = false;
+        let mut prefix_end: Option<usize> = None;
+        let outer = pair.clone();
+
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::link_schema => {
+                    url_start = Some(pair.as_span().start());
+                },
+                Rule::link_tail | Rule::markdown_link_tail => {
+                    has_tail = true;
+                },
+                _ => {
+                    prefix_end = Some(pair.as_span().end());
+                    tokens.append(&mut Self::from_symbol_prefix(pair));
+                },
+            }
+        }
+
+        if let (Some(url_start), true) = (url_start, has_tail) {
+            // the schema and tail are next to each other, so the URL is the rest of the pair.
+            let url = &outer.as_str()[url_start - outer.as_span().start()..];
+            let span = Self::symbol_span(&outer, prefix_end);
+
+            tokens.push(Self::new(Token::Link(Link { url }), span));
+        }
+
+        tokens
+    }
+
+    fn from_mention_rule(pair: Pair<'a, Rule>) -> Vec<Self> {
+        let mut tokens = Vec::new();
+        let mut username: Option<&'a str> = None;
+        let mut domain: Option<&'a str> = None;
+        let mut prefix_end: Option<usize> = None;
+        let outer = pair.clone();
+
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::mention_username => {
+                    username = Some(pair.as_str());
+                },
+                Rule::mention_domain => {
+                    domain = Some(pair.as_str());
+                },
+                _ => {
+                    prefix_end = Some(pair.as_span().end());
+                    tokens.append(&mut Self::from_symbol_prefix(pair));
+                },
+            }
+        }
+
+        if let Some(username) = username {
+            let span = Self::symbol_span(&outer, prefix_end);
+            tokens.push(Self::new(
+                Token::Mention(Mention { username, domain }),
+                span,
+            ));
+        }
+
+        tokens
+    }
+
+    /// Read Markdown which wraps other tokens, like `**strong**` text, handing the tokens
+    /// inside it to `wrap`. Those tokens don't keep their spans.
+    #[cfg(feature = "markdown")]
+    fn from_container_rule(
+        pair: Pair<'a, Rule>,
+        wrap: fn(Vec<Token<'a>>) -> Token<'a>,
+    ) -> Vec<Self> {
+        let mut tokens = Vec::new();
+        let mut children = Vec::new();
+        let mut prefix_end: Option<usize> = None;
+        let outer = pair.clone();
+
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::block_prefix => {
+                    prefix_end = Some(pair.as_span().end());
+                    tokens.append(&mut Self::from_symbol_prefix(pair));
+                },
+                _ => {
+                    children.append(&mut Self::from_parse_pair(pair));
+                },
+            }
+        }
+
+        let children = normalize_text_tokens(outer.as_str(), outer.as_span().start(), children)
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .collect();
+        let span = Self::symbol_span(&outer, prefix_end);
+        tokens.push(Self::new(wrap(children), span));
+
+        tokens
+    }
+
+    /// Read inline code or a code block, handing the code to `wrap`.
+    #[cfg(feature = "markdown")]
+    fn from_code_rule(pair: Pair<'a, Rule>, wrap: fn(&'a str) -> Token<'a>) -> Vec<Self> {
+        let mut tokens = Vec::new();
+        let mut code = "";
+        let mut prefix_end: Option<usize> = None;
+        let outer = pair.clone();
+
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::code_text | Rule::code_block_text => {
+                    code = pair.as_str();
+                },
+                Rule::block_prefix => {
+                    prefix_end = Some(pair.as_span().end());
+                    tokens.append(&mut Self::from_symbol_prefix(pair));
+                },
+                _ => {},
+            }
+        }
+
+        let span = Self::symbol_span(&outer, prefix_end);
+        tokens.push(Self::new(wrap(code), span));
+
+        tokens
+    }
+
+    fn from_symbol_prefix(pair: Pair<'a, Rule>) -> Vec<Self> {
+        let mut tokens = Vec::new();
+
+        for pair in pair.into_inner() {
+            let span = Span::bytes(pair.as_span().start(), pair.as_span().end());
+
+            match pair.as_rule() {
+                Rule::line_break => {
+                    tokens.push(Self::new(Token::LineBreak(LineBreak), span));
+                },
+                _ => {
+                    let text = pair.as_str();
+
+                    tokens.push(Self::new(Token::Text(Text { text }), span));
+                },
+            }
+        }
+
+        tokens
+    }
+}
@@ -0,0 +1,114 @@
+use crate::tokens::*;
+use crate::Reader;
+use std::iter;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How many characters every link counts as, however long it really is.
+pub const URL_LENGTH: usize = 23;
+
+/// Count the characters in `input` the way Mastodon does when checking a status isn't too
+/// long.
+///
+/// Characters are grapheme clusters, every link counts as [`URL_LENGTH`] characters, and
+/// mentions of remote accounts only count their username.
+///
+/// ```
+/// use posticle::count;
+///
+/// assert_eq!(count("Hi @alice@example.com!"), 10);
+/// assert_eq!(count("See https://example.com/a/very/long/path/indeed"), 27);
+/// assert_eq!(count("🏳️‍🌈"), 1);
+/// ```
+pub fn count(input: &str) -> usize {
+    count_tokens(Reader::from(input))
+}
+
+/// Count the characters in `tokens`, with the same rules as [`count`].
+pub fn count_tokens<I>(tokens: I) -> usize
+where
+    I: IntoIterator<Item = Token>,
+{
+    let mut text = String::new();
+
+    for token in tokens {
+        push_counted_text(&token, &mut text);
+    }
+
+    text.graphemes(true).count()
+}
+
+/// Write out the text `token` counts as, so graphemes spanning tokens are only counted once.
+fn push_counted_text(token: &Token, text: &mut String) {
+    match token {
+        Token::Emoticon(Emoticon { name }) => {
+            text.push(':');
+            text.push_str(name);
+            text.push(':');
+        },
+        Token::Hashtag(Hashtag { name }) => {
+            text.push('#');
+            text.push_str(name);
+        },
+        Token::Cashtag(Cashtag { name }) => {
+            text.push('$');
+            text.push_str(name);
+        },
+        Token::LineBreak(_) => {
+            text.push('\n');
+        },
+        Token::Link(_) => {
+            text.extend(iter::repeat('x').take(URL_LENGTH));
+        },
+        Token::Mention(Mention { username, .. }) => {
+            text.push('@');
+            text.push_str(username);
+        },
+        Token::Text(Text { text: token_text }) => {
+            text.push_str(token_text);
+        },
+        Token::Element(Element { children, .. }) => {
+            for child in children {
+                push_counted_text(child, text);
+            }
+        },
+        // Markdown counts as it was written, markup and all.
+        #[cfg(feature = "markdown")]
+        Token::Strong(Strong { children }) => {
+            text.push_str("**");
+            for child in children {
+                push_counted_text(child, text);
+            }
+            text.push_str("**");
+        },
+        #[cfg(feature = "markdown")]
+        Token::Emphasis(Emphasis { children }) => {
+            text.push('*');
+            for child in children {
+                push_counted_text(child, text);
+            }
+            text.push('*');
+        },
+        #[cfg(feature = "markdown")]
+        Token::Code(Code { code }) => {
+            text.push('`');
+            text.push_str(code);
+            text.push('`');
+        },
+        #[cfg(feature = "markdown")]
+        Token::CodeBlock(CodeBlock { code }) => {
+            text.push_str("```\n");
+            text.push_str(code);
+            text.push_str("\n```");
+        },
+        #[cfg(feature = "markdown")]
+        Token::Quote(Quote { children }) => {
+            text.push('>');
+            for child in children {
+                push_counted_text(child, text);
+                if let Token::LineBreak(_) = child {
+                    text.push('>');
+                }
+            }
+        },
+    }
+}
@@ -0,0 +1,54 @@
+use crate::grammar::Rule;
+use pest::error::{ErrorVariant, InputLocation, LineColLocation};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+/// An error reading posticle style markup, with where in the input it happened.
+pub struct Error {
+    /// The byte offset into the input.
+    pub position: usize,
+    /// The line, counting from 1.
+    pub line: usize,
+    /// The column within the line, counting from 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl From<pest::error::Error<Rule>> for Error {
+    fn from(error: pest::error::Error<Rule>) -> Self {
+        let position = match error.location {
+            InputLocation::Pos(position) => position,
+            InputLocation::Span((start, _)) => start,
+        };
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(line_col) => line_col,
+            LineColLocation::Span(start, _) => start,
+        };
+        let message = match error.variant {
+            ErrorVariant::ParsingError { positives, .. } if !positives.is_empty() => {
+                format!("expected {:?}", positives)
+            },
+            ErrorVariant::ParsingError { negatives, .. } => format!("unexpected {:?}", negatives),
+            ErrorVariant::CustomError { message } => message,
+        };
+
+        Error {
+            position,
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for Error {}
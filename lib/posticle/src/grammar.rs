@@ -7,9 +7,15 @@ use pest::Parser;
 #[grammar = "grammar.pest"]
 struct Grammar;
 
-/// Parse an entire document of posticle style markup.
+/// Parse an entire document of posticle style markup, along with the subset of Markdown
+/// when the `markdown` feature is enabled.
 pub fn document(input: &str) -> Result<Pairs<Rule>, Error<Rule>> {
-    Grammar::parse(Rule::document, input)
+    #[cfg(feature = "markdown")]
+    let rule = Rule::markdown_document;
+    #[cfg(not(feature = "markdown"))]
+    let rule = Rule::document;
+
+    Grammar::parse(rule, input)
 }
 
 /// Parse a single emoticon.
@@ -22,6 +28,11 @@ pub fn hashtag(input: &str) -> Result<Pairs<Rule>, Error<Rule>> {
     Grammar::parse(Rule::hashtag, input)
 }
 
+/// Parse a single cashtag.
+pub fn cashtag(input: &str) -> Result<Pairs<Rule>, Error<Rule>> {
+    Grammar::parse(Rule::cashtag, input)
+}
+
 /// Parse a single link.
 pub fn link(input: &str) -> Result<Pairs<Rule>, Error<Rule>> {
     Grammar::parse(Rule::link, input)
@@ -0,0 +1,170 @@
+use crate::tokens::*;
+use ammonia::Url;
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::{
+    BufferQueue, Tag, TagKind, Token as HtmlToken, TokenSink, TokenSinkResult, Tokenizer,
+};
+
+/// Read the tokens out of a fragment of HTML, like the content of a Mastodon status.
+///
+/// Line breaks and paragraphs become [`LineBreak`]s, links Mastodon marks as mentions or
+/// hashtags become [`Mention`]s and [`Hashtag`]s, and links showing their own address
+/// become [`Link`]s. Any other link is kept as an `<a>` [`Element`], and every other tag is
+/// dropped, leaving its text.
+pub(crate) fn tokens_from_html(input: &str) -> Vec<Token> {
+    let mut queue = BufferQueue::new();
+    queue.push_back(StrTendril::from_slice(input));
+
+    let mut tokenizer = Tokenizer::new(HtmlSink::default(), Default::default());
+    let _ = tokenizer.feed(&mut queue);
+    tokenizer.end();
+
+    tokenizer.sink.tokens
+}
+
+/// An `<a>` tag being read.
+#[derive(Default)]
+struct Anchor {
+    href:    Option<String>,
+    classes: Vec<String>,
+    rels:    Vec<String>,
+    text:    String,
+}
+
+impl Anchor {
+    fn from_tag(tag: &Tag) -> Self {
+        let mut anchor = Anchor::default();
+
+        for attribute in &tag.attrs {
+            let value = attribute.value.to_string();
+
+            match &*attribute.name.local {
+                "href" => anchor.href = Some(value),
+                "class" => anchor.classes = value.split_whitespace().map(String::from).collect(),
+                "rel" => anchor.rels = value.split_whitespace().map(String::from).collect(),
+                _ => {},
+            }
+        }
+
+        anchor
+    }
+
+    fn has_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+
+    fn into_token(self) -> Token {
+        let is_hashtag = self.has_class("hashtag") || self.rels.iter().any(|rel| rel == "tag");
+        if is_hashtag && self.text.starts_with('#') {
+            return Token::Hashtag(Hashtag {
+                name: self.text[1..].to_string(),
+            });
+        }
+
+        if self.has_class("mention") && self.text.starts_with('@') {
+            let mut parts = self.text[1..].splitn(2, '@');
+            let username = parts.next().unwrap_or_default().to_string();
+
+            // Mastodon only writes out the username, so the domain comes from the link.
+            let domain = parts.next().map(String::from).or_else(|| {
+                let url = Url::parse(self.href.as_ref()?).ok()?;
+                url.host_str().map(String::from)
+            });
+
+            return Token::Mention(Mention { username, domain });
+        }
+
+        match self.href {
+            Some(href) if href == self.text => Token::Link(Link { url: href }),
+            Some(href) => Token::Element(Element {
+                name: "a".to_string(),
+                attributes: vec![("href".to_string(), href)],
+                children: vec![Token::Text(Text { text: self.text })],
+            }),
+            None => Token::Text(Text { text: self.text }),
+        }
+    }
+}
+
+/// Turns the tags and text coming out of the tokenizer into [`Token`]s.
+#[derive(Default)]
+struct HtmlSink {
+    tokens: Vec<Token>,
+    text:   String,
+    anchor: Option<Anchor>,
+}
+
+impl HtmlSink {
+    fn flush_text(&mut self) {
+        if !self.text.is_empty() {
+            let text = self.text.split_off(0);
+            self.tokens.push(Token::Text(Text { text }));
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.flush_text();
+        self.tokens.push(Token::LineBreak(LineBreak));
+    }
+
+    /// Paragraphs are separated by a line break, and whitespace between them is ignored.
+    fn paragraph(&mut self, kind: TagKind) {
+        if self.text.trim().is_empty() {
+            self.text.clear();
+        }
+
+        match kind {
+            TagKind::StartTag if !self.tokens.is_empty() || !self.text.is_empty() => {
+                self.line_break();
+            },
+            _ => self.flush_text(),
+        }
+    }
+
+    fn tag(&mut self, tag: Tag) {
+        match (tag.kind, &*tag.name) {
+            (TagKind::StartTag, "br") => self.line_break(),
+            (kind, "p") => self.paragraph(kind),
+            (TagKind::StartTag, "a") if self.anchor.is_none() => {
+                self.flush_text();
+                self.anchor = Some(Anchor::from_tag(&tag));
+            },
+            (TagKind::EndTag, "a") => {
+                if let Some(anchor) = self.anchor.take() {
+                    self.tokens.push(anchor.into_token());
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn characters(&mut self, characters: &str) {
+        // newlines in HTML are just whitespace.
+        let characters = characters.replace("\r\n", " ").replace('\n', " ");
+
+        match &mut self.anchor {
+            Some(anchor) => anchor.text.push_str(&characters),
+            None => self.text.push_str(&characters),
+        }
+    }
+}
+
+impl TokenSink for HtmlSink {
+    type Handle = ();
+
+    fn process_token(&mut self, token: HtmlToken, _line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            HtmlToken::TagToken(tag) => self.tag(tag),
+            HtmlToken::CharacterTokens(characters) => self.characters(&characters),
+            HtmlToken::EOFToken => {
+                if let Some(anchor) = self.anchor.take() {
+                    self.tokens.push(anchor.into_token());
+                }
+                self.flush_text();
+            },
+            _ => {},
+        }
+
+        TokenSinkResult::Continue
+    }
+}
@@ -0,0 +1,276 @@
+//! Internationalized domain names, which are looked up as punycode (`xn--…`) and shown in
+//! Unicode, unless they could pass for another domain.
+
+/// Convert `domain` to its ASCII form, punycode encoding any labels with other characters in
+/// them, or return `None` if it isn't a valid domain.
+///
+/// ```
+/// use posticle::idn::to_ascii;
+///
+/// assert_eq!(to_ascii("Bücher.example"), Some("xn--bcher-kva.example".to_string()));
+/// assert_eq!(to_ascii("example.com"), Some("example.com".to_string()));
+/// ```
+pub fn to_ascii(domain: &str) -> Option<String> {
+    idna::domain_to_ascii(domain).ok()
+}
+
+/// Convert `domain` to its Unicode form, decoding any punycode labels. If it hasn't got any,
+/// or it isn't a valid domain, it's returned as it is.
+///
+/// Like browsers, labels which mix scripts, or which are written in Cyrillic letters that
+/// all look like Latin ones, are left in punycode so they can't pass for other domains.
+///
+/// ```
+/// use posticle::idn::to_unicode;
+///
+/// assert_eq!(to_unicode("xn--bcher-kva.example"), "bücher.example");
+/// assert_eq!(to_unicode("Example.com"), "Example.com");
+/// assert_eq!(to_unicode("xn--pple-43d.com"), "xn--pple-43d.com");
+/// ```
+pub fn to_unicode(domain: &str) -> String {
+    if !has_punycode(domain) {
+        return domain.to_string();
+    }
+
+    let unicode = match idna::domain_to_unicode(domain) {
+        (unicode, Ok(())) => unicode,
+        (_, Err(_)) => return domain.to_string(),
+    };
+    let labels = domain.split('.').collect::<Vec<_>>();
+    let unicode_labels = unicode.split('.').collect::<Vec<_>>();
+
+    if labels.len() != unicode_labels.len() {
+        return domain.to_string();
+    }
+
+    let last = labels.len() - 1;
+
+    labels
+        .iter()
+        .zip(unicode_labels)
+        .enumerate()
+        .map(|(index, (label, unicode_label))| {
+            // Top level domains are picked by IANA, so only need to be in one script.
+            if is_punycode(label) && !is_safe_label(unicode_label, index == last) {
+                *label
+            } else {
+                unicode_label
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Whether any label of `domain` is punycode encoded.
+fn has_punycode(domain: &str) -> bool {
+    domain.split('.').any(is_punycode)
+}
+
+/// Whether `label` is punycode encoded.
+fn is_punycode(label: &str) -> bool {
+    label
+        .get(..4)
+        .map_or(false, |prefix| prefix.eq_ignore_ascii_case("xn--"))
+}
+
+/// The scripts told apart when deciding whether a label can be shown in Unicode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Han,
+    Hiragana,
+    Katakana,
+    Bopomofo,
+    Hangul,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Gurmukhi,
+    Gujarati,
+    Oriya,
+    Tamil,
+    Telugu,
+    Kannada,
+    Malayalam,
+    Sinhala,
+    Thai,
+    Lao,
+    Tibetan,
+    Myanmar,
+    Georgian,
+    Ethiopic,
+    Khmer,
+    Unknown,
+}
+
+/// The code points of each script, other than ASCII.
+const SCRIPT_RANGES: &[(char, char, Script)] = &[
+    ('\u{00C0}', '\u{00D6}', Script::Latin),
+    ('\u{00D8}', '\u{00F6}', Script::Latin),
+    ('\u{00F8}', '\u{024F}', Script::Latin),
+    ('\u{0370}', '\u{03FF}', Script::Greek),
+    ('\u{0400}', '\u{052F}', Script::Cyrillic),
+    ('\u{0530}', '\u{058F}', Script::Armenian),
+    ('\u{0590}', '\u{05FF}', Script::Hebrew),
+    ('\u{0600}', '\u{06FF}', Script::Arabic),
+    ('\u{0750}', '\u{077F}', Script::Arabic),
+    ('\u{08A0}', '\u{08FF}', Script::Arabic),
+    ('\u{0900}', '\u{097F}', Script::Devanagari),
+    ('\u{0980}', '\u{09FF}', Script::Bengali),
+    ('\u{0A00}', '\u{0A7F}', Script::Gurmukhi),
+    ('\u{0A80}', '\u{0AFF}', Script::Gujarati),
+    ('\u{0B00}', '\u{0B7F}', Script::Oriya),
+    ('\u{0B80}', '\u{0BFF}', Script::Tamil),
+    ('\u{0C00}', '\u{0C7F}', Script::Telugu),
+    ('\u{0C80}', '\u{0CFF}', Script::Kannada),
+    ('\u{0D00}', '\u{0D7F}', Script::Malayalam),
+    ('\u{0D80}', '\u{0DFF}', Script::Sinhala),
+    ('\u{0E00}', '\u{0E7F}', Script::Thai),
+    ('\u{0E80}', '\u{0EFF}', Script::Lao),
+    ('\u{0F00}', '\u{0FFF}', Script::Tibetan),
+    ('\u{1000}', '\u{109F}', Script::Myanmar),
+    ('\u{10A0}', '\u{10FF}', Script::Georgian),
+    ('\u{1100}', '\u{11FF}', Script::Hangul),
+    ('\u{1200}', '\u{139F}', Script::Ethiopic),
+    ('\u{1780}', '\u{17FF}', Script::Khmer),
+    ('\u{1C80}', '\u{1C8F}', Script::Cyrillic),
+    ('\u{1E00}', '\u{1EFF}', Script::Latin),
+    ('\u{1F00}', '\u{1FFF}', Script::Greek),
+    ('\u{2C60}', '\u{2C7F}', Script::Latin),
+    ('\u{2D00}', '\u{2D2F}', Script::Georgian),
+    ('\u{2DE0}', '\u{2DFF}', Script::Cyrillic),
+    ('\u{2E80}', '\u{2FDF}', Script::Han),
+    ('\u{3005}', '\u{3007}', Script::Han),
+    ('\u{3021}', '\u{3029}', Script::Han),
+    ('\u{3038}', '\u{303B}', Script::Han),
+    ('\u{3040}', '\u{309F}', Script::Hiragana),
+    ('\u{30A0}', '\u{30FF}', Script::Katakana),
+    ('\u{3100}', '\u{312F}', Script::Bopomofo),
+    ('\u{3130}', '\u{318F}', Script::Hangul),
+    ('\u{31A0}', '\u{31BF}', Script::Bopomofo),
+    ('\u{31F0}', '\u{31FF}', Script::Katakana),
+    ('\u{3400}', '\u{4DBF}', Script::Han),
+    ('\u{4E00}', '\u{9FFF}', Script::Han),
+    ('\u{A640}', '\u{A69F}', Script::Cyrillic),
+    ('\u{A720}', '\u{A7FF}', Script::Latin),
+    ('\u{A960}', '\u{A97F}', Script::Hangul),
+    ('\u{AB30}', '\u{AB6F}', Script::Latin),
+    ('\u{AC00}', '\u{D7FF}', Script::Hangul),
+    ('\u{F900}', '\u{FAFF}', Script::Han),
+    ('\u{FB1D}', '\u{FB4F}', Script::Hebrew),
+    ('\u{FB50}', '\u{FDFF}', Script::Arabic),
+    ('\u{FE70}', '\u{FEFF}', Script::Arabic),
+    ('\u{20000}', '\u{2FFFF}', Script::Han),
+];
+
+/// Cyrillic letters which look like Latin ones.
+const LATIN_LOOKING_CYRILLIC: &str = "асԁеһіјӏорԛѕԝхуъьҽпгѵѡ";
+
+/// The script `c` is written in, or `None` for digits, hyphens, joiners and combining marks,
+/// which go with any script.
+fn script(c: char) -> Option<Script> {
+    match c {
+        '0'..='9' | '-' | '\u{200C}' | '\u{200D}' => None,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}' => None,
+        'a'..='z' | 'A'..='Z' => Some(Script::Latin),
+        _ => Some(
+            SCRIPT_RANGES
+                .iter()
+                .find(|(first, last, _)| (*first..=*last).contains(&c))
+                .map_or(Script::Unknown, |(_, _, script)| *script),
+        ),
+    }
+}
+
+/// Whether `label` can be shown in Unicode without passing for another label. Like the
+/// "highly restrictive" profile of Unicode's security mechanisms, it must be in one script,
+/// or in Latin with the scripts Chinese, Japanese or Korean are written in.
+fn is_safe_label(label: &str, is_top_level: bool) -> bool {
+    let mut scripts = label.chars().filter_map(script).collect::<Vec<_>>();
+
+    scripts.sort_by_key(|script| *script as u8);
+    scripts.dedup();
+
+    let is_one_of = |allowed: &[Script]| scripts.iter().all(|script| allowed.contains(script));
+    let is_mixed = scripts.len() > 1
+        && !is_one_of(&[
+            Script::Latin,
+            Script::Han,
+            Script::Hiragana,
+            Script::Katakana,
+        ])
+        && !is_one_of(&[Script::Latin, Script::Han, Script::Bopomofo])
+        && !is_one_of(&[Script::Latin, Script::Han, Script::Hangul]);
+
+    if is_mixed {
+        return false;
+    }
+
+    let is_latin_looking = scripts == [Script::Cyrillic]
+        && label
+            .chars()
+            .all(|c| script(c).is_none() || LATIN_LOOKING_CYRILLIC.contains(c));
+
+    is_top_level || !is_latin_looking
+}
+
+/// Replace the host of `url` with whatever `map` turns it into, leaving the rest of it as
+/// it's written. URLs without a host, and hosts `map` gives up on, are left alone.
+fn map_host<F>(url: &str, map: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let authority_start = match url.find("://") {
+        Some(index) => index + 3,
+        None => return url.to_string(),
+    };
+    let authority_end = url[authority_start..]
+        .find(|c| c == '/' || c == '?' || c == '#')
+        .map_or(url.len(), |index| authority_start + index);
+    let authority = &url[authority_start..authority_end];
+
+    let host_start = authority.rfind('@').map_or(0, |index| index + 1);
+    let host = &authority[host_start..];
+
+    // IPv6 addresses aren't domains.
+    if host.starts_with('[') {
+        return url.to_string();
+    }
+
+    let host = match host.rfind(':') {
+        Some(index) => &host[..index],
+        None => host,
+    };
+
+    match map(host) {
+        Some(mapped) => {
+            let host_start = authority_start + host_start;
+
+            format!(
+                "{}{}{}",
+                &url[..host_start],
+                mapped,
+                &url[host_start + host.len()..]
+            )
+        },
+        None => url.to_string(),
+    }
+}
+
+/// The `url` with its host in ASCII, for following it.
+pub(crate) fn ascii_url(url: &str) -> String {
+    map_host(url, to_ascii)
+}
+
+/// The `url` with any punycode in its host shown in Unicode.
+pub(crate) fn unicode_url(url: &str) -> String {
+    map_host(url, |host| Some(to_unicode(host)))
+}
@@ -3,13 +3,24 @@
 #[macro_use]
 extern crate pest_derive;
 
+pub mod borrowed;
+mod count;
+mod error;
 pub mod grammar;
+mod html;
+pub mod idn;
+pub mod render;
 pub mod tokens;
 
-use crate::grammar::document;
+pub use crate::count::{count, count_tokens, URL_LENGTH};
+pub use crate::error::Error;
+use crate::grammar::{document, Rule};
+use crate::render::{Html, PlainText, Render};
 use crate::tokens::*;
 use ammonia::Builder as Ammonia;
-use maplit::hashset;
+use pest::iterators::Pairs;
+use std::borrow::Cow;
+use std::vec;
 
 /// Build a new [`Reader`].
 pub struct ReaderBuilder<'t>(Reader<'t>);
@@ -27,6 +38,9 @@ impl<'t> ReaderBuilder<'t> {
 
     /// Finish building a [`Reader`] and tokenize its input.
     ///
+    /// If the input can't be parsed, it's read as plain text instead (see
+    /// [`ReaderBuilder::plain_text`]), so none of it is ever lost.
+    ///
     /// ```
     /// use posticle::ReaderBuilder;
     ///
@@ -38,6 +52,86 @@ impl<'t> ReaderBuilder<'t> {
         self.0.finish()
     }
 
+    /// Finish building a [`Reader`] and tokenize its input, returning an [`Error`] saying
+    /// where if the input can't be parsed.
+    ///
+    /// ```
+    /// use posticle::ReaderBuilder;
+    ///
+    /// let reader = ReaderBuilder::new().with_str("Nice!").try_finish();
+    ///
+    /// assert!(reader.is_ok());
+    /// ```
+    pub fn try_finish(self) -> Result<Reader<'t>, Error> {
+        self.0.try_finish()
+    }
+
+    /// Read the input as plain text, only breaking it into [`Text`] and [`LineBreak`]
+    /// tokens, without looking for mentions, hashtags and so on.
+    ///
+    /// ```
+    /// use posticle::tokens::*;
+    /// use posticle::ReaderBuilder;
+    ///
+    /// let reader = ReaderBuilder::new()
+    ///     .plain_text()
+    ///     .with_str("#not_a_hashtag")
+    ///     .finish();
+    ///
+    /// assert_eq!(
+    ///     reader.into_vec(),
+    ///     vec![Token::Text(Text {
+    ///         text: "#not_a_hashtag".to_string(),
+    ///     })]
+    /// );
+    /// ```
+    pub fn plain_text(self) -> Self {
+        ReaderBuilder(Reader {
+            syntax: Syntax::PlainText,
+            ..self.0
+        })
+    }
+
+    /// Read the input as HTML, like the content of a status from another server.
+    ///
+    /// Line breaks and paragraphs are read as [`LineBreak`]s, and links marked as mentions
+    /// or hashtags (the way Mastodon marks them) as [`Mention`]s and [`Hashtag`]s. Links
+    /// which show their own address become [`Link`]s, while other links are kept as `<a>`
+    /// [`Element`]s. Every other tag is dropped, leaving its text.
+    ///
+    /// HTML isn't read with spans, so every token has an empty one.
+    ///
+    /// ```
+    /// use posticle::tokens::*;
+    /// use posticle::ReaderBuilder;
+    ///
+    /// let reader = ReaderBuilder::new()
+    ///     .html()
+    ///     .with_str(
+    ///         r#"<p>hi <span class="h-card"><a href="https://example.com/@alice" class="u-url mention">@<span>alice</span></a></span></p>"#,
+    ///     )
+    ///     .finish();
+    ///
+    /// assert_eq!(
+    ///     reader.into_vec(),
+    ///     vec![
+    ///         Token::Text(Text {
+    ///             text: "hi ".to_string(),
+    ///         }),
+    ///         Token::Mention(Mention {
+    ///             username: "alice".to_string(),
+    ///             domain:   Some("example.com".to_string()),
+    ///         }),
+    ///     ]
+    /// );
+    /// ```
+    pub fn html(self) -> Self {
+        ReaderBuilder(Reader {
+            syntax: Syntax::Html,
+            ..self.0
+        })
+    }
+
     /// Add a [`str`] as input to the [`Reader`] being built.
     ///
     /// ```
@@ -48,8 +142,11 @@ impl<'t> ReaderBuilder<'t> {
     ///
     /// assert_eq!(reader_a.into_vec(), reader_b.into_vec());
     /// ```
-    pub fn with_str(self, input: &str) -> Self {
-        self.with_string(input.to_string())
+    pub fn with_str(self, input: &'t str) -> Self {
+        ReaderBuilder(Reader {
+            input: Cow::Borrowed(input),
+            ..self.0
+        })
     }
 
     /// Add a [`String`] as input to the [`Reader`] being built.
@@ -65,7 +162,10 @@ impl<'t> ReaderBuilder<'t> {
     /// assert_eq!(reader_a.into_vec(), reader_b.into_vec());
     /// ```
     pub fn with_string(self, input: String) -> Self {
-        ReaderBuilder(Reader { input, ..self.0 })
+        ReaderBuilder(Reader {
+            input: Cow::Owned(input),
+            ..self.0
+        })
     }
 
     /// Add a transformer closure to the [`Reader`] being built.
@@ -92,23 +192,188 @@ impl<'t> ReaderBuilder<'t> {
             ..self.0
         })
     }
+
+    /// Choose whether the [`Reader`] being built reads mentions, or leaves them as text.
+    /// They're read unless told otherwise.
+    ///
+    /// ```
+    /// use posticle::tokens::*;
+    /// use posticle::ReaderBuilder;
+    ///
+    /// let reader = ReaderBuilder::new()
+    ///     .with_mentions(false)
+    ///     .with_str("@alice")
+    ///     .finish();
+    ///
+    /// assert_eq!(
+    ///     reader.into_vec(),
+    ///     vec![Token::Text(Text {
+    ///         text: "@alice".to_string(),
+    ///     })]
+    /// );
+    /// ```
+    pub fn with_mentions(self, enabled: bool) -> Self {
+        let symbols = Symbols {
+            mentions: enabled,
+            ..self.0.symbols
+        };
+
+        ReaderBuilder(Reader { symbols, ..self.0 })
+    }
+
+    /// Choose whether the [`Reader`] being built reads hashtags, or leaves them as text.
+    /// They're read unless told otherwise.
+    ///
+    /// ```
+    /// use posticle::tokens::*;
+    /// use posticle::ReaderBuilder;
+    ///
+    /// let reader = ReaderBuilder::new()
+    ///     .with_hashtags(false)
+    ///     .with_str("#rust")
+    ///     .finish();
+    ///
+    /// assert_eq!(
+    ///     reader.into_vec(),
+    ///     vec![Token::Text(Text {
+    ///         text: "#rust".to_string(),
+    ///     })]
+    /// );
+    /// ```
+    pub fn with_hashtags(self, enabled: bool) -> Self {
+        let symbols = Symbols {
+            hashtags: enabled,
+            ..self.0.symbols
+        };
+
+        ReaderBuilder(Reader { symbols, ..self.0 })
+    }
+
+    /// Choose whether the [`Reader`] being built reads cashtags, or leaves them as text.
+    /// They're read unless told otherwise.
+    ///
+    /// ```
+    /// use posticle::tokens::*;
+    /// use posticle::ReaderBuilder;
+    ///
+    /// let reader = ReaderBuilder::new()
+    ///     .with_cashtags(false)
+    ///     .with_str("$RUST")
+    ///     .finish();
+    ///
+    /// assert_eq!(
+    ///     reader.into_vec(),
+    ///     vec![Token::Text(Text {
+    ///         text: "$RUST".to_string(),
+    ///     })]
+    /// );
+    /// ```
+    pub fn with_cashtags(self, enabled: bool) -> Self {
+        let symbols = Symbols {
+            cashtags: enabled,
+            ..self.0.symbols
+        };
+
+        ReaderBuilder(Reader { symbols, ..self.0 })
+    }
+
+    /// Choose whether the [`Reader`] being built reads emoticons, or leaves them as text.
+    /// They're read unless told otherwise.
+    ///
+    /// ```
+    /// use posticle::tokens::*;
+    /// use posticle::ReaderBuilder;
+    ///
+    /// let reader = ReaderBuilder::new()
+    ///     .with_emoticons(false)
+    ///     .with_str(":blobcat:")
+    ///     .finish();
+    ///
+    /// assert_eq!(
+    ///     reader.into_vec(),
+    ///     vec![Token::Text(Text {
+    ///         text: ":blobcat:".to_string(),
+    ///     })]
+    /// );
+    /// ```
+    pub fn with_emoticons(self, enabled: bool) -> Self {
+        let symbols = Symbols {
+            emoticons: enabled,
+            ..self.0.symbols
+        };
+
+        ReaderBuilder(Reader { symbols, ..self.0 })
+    }
+}
+
+/// What a [`Reader`] reads its input as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Syntax {
+    Posticle,
+    PlainText,
+    Html,
+}
+
+/// The kinds of symbol a [`Reader`] reads, rather than leaving them as text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Symbols {
+    mentions:  bool,
+    hashtags:  bool,
+    cashtags:  bool,
+    emoticons: bool,
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        Symbols {
+            mentions:  true,
+            hashtags:  true,
+            cashtags:  true,
+            emoticons: true,
+        }
+    }
+}
+
+impl Symbols {
+    /// Turn `token` back into the text it was read from, if it's a kind of symbol which
+    /// isn't being read.
+    fn filter(self, token: Token) -> Token {
+        let enabled = match &token {
+            Token::Mention(_) => self.mentions,
+            Token::Hashtag(_) => self.hashtags,
+            Token::Cashtag(_) => self.cashtags,
+            Token::Emoticon(_) => self.emoticons,
+            _ => true,
+        };
+
+        if enabled {
+            return token;
+        }
+
+        let mut text = String::new();
+        PlainText.render(&token, &mut text);
+
+        Token::Text(Text { text })
+    }
 }
 
 /// Read [`Token`]s from a string.
 pub struct Reader<'t> {
-    input: String,
-    tokens: Vec<Token>,
-    current_token: usize,
+    input: Cow<'t, str>,
+    tokens: vec::IntoIter<Spanned<Token>>,
     transformer: Box<dyn 't + Fn(Token) -> Token>,
+    syntax: Syntax,
+    symbols: Symbols,
 }
 
 impl Default for Reader<'_> {
     fn default() -> Self {
         Reader {
-            input: String::new(),
-            tokens: Vec::new(),
-            current_token: 0,
+            input: Cow::Borrowed(""),
+            tokens: Vec::new().into_iter(),
             transformer: Box::new(|token| token),
+            syntax: Syntax::Posticle,
+            symbols: Symbols::default(),
         }
     }
 }
@@ -117,21 +382,11 @@ impl Iterator for Reader<'_> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let current_token = self.current_token.to_owned();
-
-        if current_token < self.tokens.len() {
-            self.current_token += 1;
-
-            if let Some(token) = self.tokens.get(current_token) {
-                return Some(token.to_owned());
-            }
-        }
-
-        None
+        self.tokens.next().map(|spanned| spanned.token)
     }
 }
 
-impl<'a, 't> From<&'a str> for Reader<'t> {
+impl<'t> From<&'t str> for Reader<'t> {
     /// ```
     /// use posticle::tokens::*;
     /// use posticle::Reader;
@@ -145,7 +400,7 @@ impl<'a, 't> From<&'a str> for Reader<'t> {
     ///     })]
     /// );
     /// ```
-    fn from(input: &str) -> Self {
+    fn from(input: &'t str) -> Self {
         ReaderBuilder::new().with_str(input).finish()
     }
 }
@@ -186,8 +441,16 @@ impl From<Vec<Token>> for Reader<'_> {
     /// );
     /// ```
     fn from(tokens: Vec<Token>) -> Self {
+        let tokens = tokens
+            .into_iter()
+            .map(|token| Spanned {
+                token,
+                span: Span::default(),
+            })
+            .collect::<Vec<_>>();
+
         Reader {
-            tokens,
+            tokens: tokens.into_iter(),
             ..Self::default()
         }
     }
@@ -195,57 +458,184 @@ impl From<Vec<Token>> for Reader<'_> {
 
 impl<'ta, 'tb> PartialEq<Reader<'ta>> for Reader<'tb> {
     fn eq(&self, other: &Reader) -> bool {
-        self.input == other.input && self.tokens == other.tokens
+        self.input == other.input && self.tokens.as_slice() == other.tokens.as_slice()
     }
 }
 
 impl Reader<'_> {
     fn finish(self) -> Self {
-        let mut tokens: Vec<Token> = Vec::new();
+        match self.syntax {
+            Syntax::PlainText => return self.finish_plain_text(),
+            Syntax::Html => return self.finish_html(),
+            Syntax::Posticle => {},
+        }
 
-        if let Ok(pairs) = document(&self.input) {
-            let transformer = &self.transformer;
+        match document(&self.input) {
+            Ok(pairs) => {
+                let tokens = self.tokens_from_pairs(pairs).into_iter();
+                Self { tokens, ..self }
+            },
+            Err(_) => self.finish_plain_text(),
+        }
+    }
+
+    fn try_finish(self) -> Result<Self, Error> {
+        match self.syntax {
+            Syntax::PlainText => return Ok(self.finish_plain_text()),
+            Syntax::Html => return Ok(self.finish_html()),
+            Syntax::Posticle => {},
+        }
+
+        let pairs = document(&self.input)?;
+        let tokens = self.tokens_from_pairs(pairs).into_iter();
+
+        Ok(Self { tokens, ..self })
+    }
+
+    fn tokens_from_pairs(&self, pairs: Pairs<Rule>) -> Vec<Spanned<Token>> {
+        let mut tokens = Vec::new();
+        let transformer = &self.symbol_transformer();
 
-            for pair in pairs {
-                tokens.append(&mut Token::from_parse_pair(pair, transformer));
+        for pair in pairs {
+            if pair.as_rule() != Rule::EOI {
+                tokens.append(&mut Spanned::<Token>::from_parse_pair(pair, transformer));
             }
         }
 
-        let tokens = normalize_text_tokens(tokens);
+        self.normalize(tokens)
+    }
+
+    /// The transformer, after leaving any symbols which aren't being read as text.
+    fn symbol_transformer(&self) -> Box<dyn '_ + Fn(Token) -> Token> {
+        let symbols = self.symbols;
+        let transformer = &self.transformer;
+
+        Box::new(move |token| transformer(symbols.filter(token)))
+    }
+
+    /// Tokenize the input as lines of text.
+    fn finish_plain_text(self) -> Self {
+        let tokens = self.tokens_from_plain_text().into_iter();
+
+        Self { tokens, ..self }
+    }
+
+    fn tokens_from_plain_text(&self) -> Vec<Spanned<Token>> {
+        let transformer = &self.transformer;
+        let tokens = borrowed::read_plain_text(&self.input)
+            .into_iter()
+            .map(|spanned| Spanned {
+                token: spanned.token.into_owned_with(transformer),
+                span:  spanned.span,
+            })
+            .collect();
+
+        self.normalize(tokens)
+    }
+
+    /// Tokenize the input as HTML.
+    fn finish_html(self) -> Self {
+        let tokens = self.tokens_from_html().into_iter();
 
         Self { tokens, ..self }
     }
 
+    fn tokens_from_html(&self) -> Vec<Spanned<Token>> {
+        let transformer = self.symbol_transformer();
+        let tokens = html::tokens_from_html(&self.input)
+            .into_iter()
+            .map(|token| Spanned {
+                token: transformer(token),
+                span:  Span::default(),
+            })
+            .collect();
+
+        self.normalize(tokens)
+    }
+
+    /// Combine rows of text tokens, and work out the char offsets of every token.
+    fn normalize(&self, tokens: Vec<Spanned<Token>>) -> Vec<Spanned<Token>> {
+        let mut tokens = normalize_text_tokens(tokens);
+        Span::fill_char_offsets(
+            &self.input,
+            tokens.iter_mut().map(|spanned| &mut spanned.span),
+        );
+
+        tokens
+    }
+
     /// Convert a [`Reader`] to a [`Vec`] of [`Token`].
     pub fn into_vec(self) -> Vec<Token> {
-        self.tokens
+        self.tokens.map(|spanned| spanned.token).collect()
+    }
+
+    /// Convert a [`Reader`] to a [`Vec`] of [`Token`]s along with where in the input each
+    /// one was read from.
+    ///
+    /// ```
+    /// use posticle::tokens::*;
+    /// use posticle::Reader;
+    ///
+    /// let tokens = Reader::from("¡hola #rust").into_spanned_vec();
+    ///
+    /// assert_eq!(
+    ///     tokens[1],
+    ///     Spanned {
+    ///         token: Token::Hashtag(Hashtag {
+    ///             name: "rust".to_string(),
+    ///         }),
+    ///         span:  Span {
+    ///             start:      7,
+    ///             end:        12,
+    ///             char_start: 6,
+    ///             char_end:   11,
+    ///         },
+    ///     }
+    /// );
+    /// ```
+    pub fn into_spanned_vec(self) -> Vec<Spanned<Token>> {
+        self.tokens.collect()
     }
 }
 
 /// The parser has a tendency to produce rows of text tokens, combine any
 /// text token that follows another text token into a new text token.
-fn normalize_text_tokens(input: Vec<Token>) -> Vec<Token> {
+fn normalize_text_tokens(input: Vec<Spanned<Token>>) -> Vec<Spanned<Token>> {
     let mut output = Vec::new();
-    let mut replacement = String::new();
+    let mut replacement: Option<(String, Span)> = None;
 
-    for token in input {
-        match token {
+    for spanned in input {
+        match spanned.token {
             Token::Text(Text { text }) => {
-                replacement.push_str(&text);
+                replacement = match replacement {
+                    Some((mut replacement, span)) => {
+                        replacement.push_str(&text);
+                        Some((replacement, span.to(spanned.span)))
+                    },
+                    None => Some((text, spanned.span)),
+                };
             },
-            _ => {
-                if !replacement.is_empty() {
-                    output.push(Token::Text(Text { text: replacement }));
-                    replacement = String::new();
+            token => {
+                if let Some((text, span)) = replacement.take() {
+                    output.push(Spanned {
+                        token: Token::Text(Text { text }),
+                        span,
+                    });
                 }
 
-                output.push(token);
+                output.push(Spanned {
+                    token,
+                    span: spanned.span,
+                });
             },
         }
     }
 
-    if !replacement.is_empty() {
-        output.push(Token::Text(Text { text: replacement }));
+    if let Some((text, span)) = replacement {
+        output.push(Spanned {
+            token: Token::Text(Text { text }),
+            span,
+        });
     }
 
     output
@@ -340,30 +730,47 @@ impl<'w> WriterBuilder<'w> {
     /// assert_eq!(writer.to_string(), "Nice!".to_string());
     /// ```
     pub fn with_html_sanitizer(self, html_sanitizer: Ammonia<'w>) -> Self {
+        self.with_renderer(Html::new(html_sanitizer))
+    }
+
+    /// Choose how the [`Writer`] being built renders its tokens. They're written as
+    /// sanitized HTML (see [`render::Html`]) unless told otherwise.
+    ///
+    /// ```
+    /// use posticle::render::PlainText;
+    /// use posticle::{Reader, WriterBuilder};
+    ///
+    /// let writer = WriterBuilder::new()
+    ///     .with_reader(Reader::from("<b>Nice!</b>"))
+    ///     .with_renderer(PlainText)
+    ///     .finish();
+    ///
+    /// assert_eq!(writer.to_string(), "<b>Nice!</b>".to_string());
+    /// ```
+    pub fn with_renderer<R>(self, renderer: R) -> Self
+    where
+        R: 'w + Render,
+    {
         WriterBuilder(Writer {
-            html_sanitizer,
+            renderer: Box::new(renderer),
             ..self.0
         })
     }
 }
 
-/// Write [`Token`]s as HTML to a string.
+/// Write [`Token`]s to a string, as HTML unless another [`Render`] is chosen.
 pub struct Writer<'w> {
-    output: String,
-    html_sanitizer: Ammonia<'w>,
-    tokens: Vec<Token>,
+    output:   String,
+    renderer: Box<dyn 'w + Render>,
+    tokens:   Vec<Token>,
 }
 
 impl Default for Writer<'_> {
     fn default() -> Self {
-        let mut html_sanitizer = Ammonia::default();
-
-        html_sanitizer.tags(hashset!["br"]);
-
         Self {
-            output: String::new(),
-            html_sanitizer,
-            tokens: Vec::new(),
+            output:   String::new(),
+            renderer: Box::new(Html::default()),
+            tokens:   Vec::new(),
         }
     }
 }
@@ -412,11 +819,11 @@ impl Writer<'_> {
 
     /// Convert the [`Writer`] to a [`String`].
     pub fn to_string(&self) -> String {
-        self.html_sanitizer.clean(&self.output).to_string()
+        self.renderer.finish(&self.output)
     }
 
     /// Push a [`Token`] onto the [`Writer`].
     pub fn push(&mut self, token: &Token) {
-        token.render(&mut self.output);
+        self.renderer.render(token, &mut self.output);
     }
 }
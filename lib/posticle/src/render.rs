@@ -0,0 +1,324 @@
+//! Ways of writing [`Token`]s out as text, for use with a [`Writer`](crate::Writer).
+
+use crate::tokens::*;
+use ammonia::Builder as Ammonia;
+use maplit::hashset;
+
+/// Returns the value of the attribute `name` of `element`, if it has one.
+fn attribute<'e>(element: &'e Element, name: &str) -> Option<&'e str> {
+    element
+        .attributes
+        .iter()
+        .find(|(attribute, _)| attribute == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Writes out a token read from the Markdown subset the way it would have been written, with
+/// `renderer` writing the tokens inside it.
+#[cfg(feature = "markdown")]
+fn render_markdown<R: Render>(renderer: &R, token: &Token, output: &mut String) {
+    let render_children = |children: &[Token], output: &mut String| {
+        for child in children {
+            renderer.render(child, output);
+        }
+    };
+
+    match token {
+        Token::Strong(Strong { children }) => {
+            output.push_str("**");
+            render_children(children, output);
+            output.push_str("**");
+        },
+        Token::Emphasis(Emphasis { children }) => {
+            output.push('*');
+            render_children(children, output);
+            output.push('*');
+        },
+        Token::Code(Code { code }) => {
+            output.push_str(&format!("`{}`", code));
+        },
+        Token::CodeBlock(CodeBlock { code }) => {
+            output.push_str(&format!("```\n{}\n```", code));
+        },
+        Token::Quote(Quote { children }) => {
+            let mut quoted = String::new();
+            render_children(children, &mut quoted);
+
+            output.push('>');
+            output.push_str(&quoted.replace('\n', "\n>"));
+        },
+        _ => {},
+    }
+}
+
+/// Writes [`Token`]s out in some format.
+pub trait Render {
+    /// Render `token` onto the end of `output`.
+    fn render(&self, token: &Token, output: &mut String);
+
+    /// Turn everything rendered into `output` into the finished text.
+    fn finish(&self, output: &str) -> String {
+        output.to_string()
+    }
+}
+
+/// Renders tokens as HTML, sanitized with an [`ammonia::Builder`].
+///
+/// By default only `<br>` tags make it through the sanitizer, along with the tags the
+/// Markdown subset is written with when the `markdown` feature is enabled.
+///
+/// ```
+/// use posticle::render::Html;
+/// use posticle::{Reader, WriterBuilder};
+///
+/// let writer = WriterBuilder::new()
+///     .with_reader(Reader::from("a & b\nc"))
+///     .with_renderer(Html::default())
+///     .finish();
+///
+/// assert_eq!(writer.to_string(), "a &amp; b\n<br>c");
+/// ```
+pub struct Html<'a> {
+    html_sanitizer: Ammonia<'a>,
+}
+
+impl Default for Html<'_> {
+    fn default() -> Self {
+        let mut html_sanitizer = Ammonia::default();
+
+        html_sanitizer.tags(hashset!["br"]);
+
+        #[cfg(feature = "markdown")]
+        html_sanitizer.add_tags(&["strong", "em", "code", "pre", "blockquote"]);
+
+        Html { html_sanitizer }
+    }
+}
+
+impl<'a> Html<'a> {
+    /// Render HTML which is cleaned by `html_sanitizer`.
+    pub fn new(html_sanitizer: Ammonia<'a>) -> Self {
+        Html { html_sanitizer }
+    }
+}
+
+impl Render for Html<'_> {
+    fn render(&self, token: &Token, output: &mut String) {
+        token.render(output);
+    }
+
+    fn finish(&self, output: &str) -> String {
+        self.html_sanitizer.clean(output).to_string()
+    }
+}
+
+/// Renders tokens as plain text, the way they would have been written. Elements are
+/// replaced by their contents, or by their `alt` text if they have none (like images).
+///
+/// ```
+/// use posticle::render::PlainText;
+/// use posticle::{Reader, WriterBuilder};
+///
+/// let writer = WriterBuilder::new()
+///     .with_reader(Reader::from("a & b\n@c@example.com"))
+///     .with_renderer(PlainText)
+///     .finish();
+///
+/// assert_eq!(writer.to_string(), "a & b\n@c@example.com");
+/// ```
+pub struct PlainText;
+
+impl Render for PlainText {
+    fn render(&self, token: &Token, output: &mut String) {
+        match token {
+            Token::Emoticon(Emoticon { name }) => {
+                output.push_str(&format!(":{}:", name));
+            },
+            Token::Hashtag(Hashtag { name }) => {
+                output.push_str(&format!("#{}", name));
+            },
+            Token::Cashtag(Cashtag { name }) => {
+                output.push_str(&format!("${}", name));
+            },
+            Token::LineBreak(_) => {
+                output.push('\n');
+            },
+            Token::Link(Link { url }) => {
+                output.push_str(url);
+            },
+            Token::Mention(Mention { username, domain }) => {
+                output.push_str(&format!("@{}", username));
+
+                if let Some(domain) = domain {
+                    output.push_str(&format!("@{}", domain));
+                }
+            },
+            Token::Text(Text { text }) => {
+                output.push_str(text);
+            },
+            Token::Element(element) => {
+                if element.children.is_empty() {
+                    output.push_str(attribute(element, "alt").unwrap_or_default());
+                }
+
+                for child in &element.children {
+                    self.render(child, output);
+                }
+            },
+            #[cfg(feature = "markdown")]
+            _ => render_markdown(self, token, output),
+        }
+    }
+}
+
+/// Renders tokens as Markdown. Text is escaped so it reads the same once the Markdown is
+/// rendered, `<a>` elements become Markdown links and `<img>` elements Markdown images.
+///
+/// ```
+/// use posticle::render::Markdown;
+/// use posticle::tokens::*;
+/// use posticle::WriterBuilder;
+///
+/// let writer = WriterBuilder::new()
+///     .with_tokens(vec![
+///         Token::Text(Text {
+///             text: "*not* emphasis ".to_string(),
+///         }),
+///         Token::Element(Element {
+///             name: "a".to_string(),
+///             attributes: vec![("href".to_string(), "https://example.com".to_string())],
+///             children: vec![Token::Text(Text {
+///                 text: "a link".to_string(),
+///             })],
+///         }),
+///     ])
+///     .with_renderer(Markdown)
+///     .finish();
+///
+/// assert_eq!(
+///     writer.to_string(),
+///     "\\*not\\* emphasis [a link](<https://example.com>)"
+/// );
+/// ```
+pub struct Markdown;
+
+impl Markdown {
+    fn escape(text: &str, output: &mut String) {
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if Self::at_line_start(output) {
+                match c {
+                    // list markers.
+                    '-' | '+' => output.push('\\'),
+                    // ordered list markers are digits and then `.` or `)`, which is always
+                    // escaped anyway.
+                    '0'..='9' => {
+                        output.push(c);
+                        while let Some(&digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                            output.push(digit);
+                            chars.next();
+                        }
+                        if chars.peek() == Some(&'.') {
+                            output.push('\\');
+                        }
+                        continue;
+                    },
+                    _ => {},
+                }
+            }
+
+            if "\\`*_~[]<>#&!()|".contains(c) {
+                output.push('\\');
+            }
+
+            output.push(c);
+        }
+    }
+
+    /// Returns whether the next character written to `output` starts a line, as far as block
+    /// syntax cares: up to three spaces may come before it.
+    fn at_line_start(output: &str) -> bool {
+        let mut spaces = 0;
+
+        for byte in output.bytes().rev() {
+            match byte {
+                b'\n' => return true,
+                b' ' if spaces < 3 => spaces += 1,
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Write `url` as a link destination, which can't have angle brackets in it.
+    fn destination(url: &str) -> String {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    }
+}
+
+impl Render for Markdown {
+    fn render(&self, token: &Token, output: &mut String) {
+        match token {
+            Token::Emoticon(Emoticon { name }) => {
+                output.push(':');
+                Self::escape(name, output);
+                output.push(':');
+            },
+            Token::Hashtag(Hashtag { name }) => {
+                output.push('#');
+                Self::escape(name, output);
+            },
+            Token::Cashtag(Cashtag { name }) => {
+                output.push('$');
+                Self::escape(name, output);
+            },
+            Token::LineBreak(_) => {
+                output.push_str("  \n");
+            },
+            Token::Link(Link { url }) => {
+                output.push_str(&Self::destination(url));
+            },
+            Token::Mention(Mention { username, domain }) => {
+                output.push('@');
+                Self::escape(username, output);
+
+                if let Some(domain) = domain {
+                    output.push('@');
+                    Self::escape(domain, output);
+                }
+            },
+            Token::Text(Text { text }) => {
+                Self::escape(text, output);
+            },
+            Token::Element(element) => match element.name.as_str() {
+                "a" if attribute(element, "href").is_some() => {
+                    output.push('[');
+                    for child in &element.children {
+                        self.render(child, output);
+                    }
+                    output.push_str(&format!(
+                        "]({})",
+                        Self::destination(attribute(element, "href").unwrap_or_default())
+                    ));
+                },
+                "img" if attribute(element, "src").is_some() => {
+                    output.push_str("![");
+                    Self::escape(attribute(element, "alt").unwrap_or_default(), output);
+                    output.push_str(&format!(
+                        "]({})",
+                        Self::destination(attribute(element, "src").unwrap_or_default())
+                    ));
+                },
+                _ => {
+                    for child in &element.children {
+                        self.render(child, output);
+                    }
+                },
+            },
+            #[cfg(feature = "markdown")]
+            _ => render_markdown(self, token, output),
+        }
+    }
+}
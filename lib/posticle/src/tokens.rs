@@ -1,7 +1,9 @@
+use crate::borrowed;
 use crate::grammar::Rule;
+use crate::idn;
 use pest::iterators::Pair;
 
-fn html_escape(text: &str) -> String {
+pub(crate) fn html_escape(text: &str) -> String {
     text.replace('&', "&amp;")
         .replace('"', "&quot")
         .replace('<', "&lt;")
@@ -32,6 +34,18 @@ impl Hashtag {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+/// A cashtag, naming a stock symbol.
+pub struct Cashtag {
+    pub name: String,
+}
+
+impl Cashtag {
+    pub fn render(&self, output: &mut String) {
+        output.push_str(&format!("${}", html_escape(&self.name)));
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 /// A line break.
 pub struct LineBreak;
@@ -50,7 +64,37 @@ pub struct Link {
 
 impl Link {
     pub fn render(&self, output: &mut String) {
-        output.push_str(&html_escape(&self.url));
+        output.push_str(&html_escape(&self.unicode_url()));
+    }
+
+    /// The URL with its domain in ASCII, punycode encoded if need be, for following it.
+    ///
+    /// ```
+    /// use posticle::tokens::Link;
+    ///
+    /// let link = Link {
+    ///     url: "https://Bücher.example/a?b".to_string(),
+    /// };
+    ///
+    /// assert_eq!(link.ascii_url(), "https://xn--bcher-kva.example/a?b");
+    /// ```
+    pub fn ascii_url(&self) -> String {
+        idn::ascii_url(&self.url)
+    }
+
+    /// The URL with any punycode in its domain shown in Unicode, for reading it.
+    ///
+    /// ```
+    /// use posticle::tokens::Link;
+    ///
+    /// let link = Link {
+    ///     url: "https://xn--bcher-kva.example/a?b".to_string(),
+    /// };
+    ///
+    /// assert_eq!(link.unicode_url(), "https://bücher.example/a?b");
+    /// ```
+    pub fn unicode_url(&self) -> String {
+        idn::unicode_url(&self.url)
     }
 }
 
@@ -62,11 +106,37 @@ pub struct Mention {
 }
 
 impl Mention {
+    /// The domain in ASCII, punycode encoded if need be, as domains are looked up.
+    ///
+    /// ```
+    /// use posticle::tokens::Mention;
+    ///
+    /// let mention = Mention {
+    ///     username: "alice".to_string(),
+    ///     domain:   Some("Bücher.example".to_string()),
+    /// };
+    ///
+    /// assert_eq!(
+    ///     mention.ascii_domain(),
+    ///     Some("xn--bcher-kva.example".to_string())
+    /// );
+    /// ```
+    pub fn ascii_domain(&self) -> Option<String> {
+        self.domain
+            .as_ref()
+            .map(|domain| idn::to_ascii(domain).unwrap_or_else(|| domain.clone()))
+    }
+
+    /// The domain with any punycode in it shown in Unicode, for reading it.
+    pub fn unicode_domain(&self) -> Option<String> {
+        self.domain.as_ref().map(|domain| idn::to_unicode(domain))
+    }
+
     pub fn render(&self, output: &mut String) {
         output.push_str(&format!("@{}", html_escape(&self.username)));
 
-        if let Some(domain) = &self.domain {
-            output.push_str(&format!("@{}", html_escape(domain)));
+        if let Some(domain) = self.unicode_domain() {
+            output.push_str(&format!("@{}", html_escape(&domain)));
         }
     }
 }
@@ -120,165 +190,130 @@ impl Element {
     }
 }
 
+#[cfg(feature = "markdown")]
 #[derive(Clone, Debug, PartialEq)]
-/// An item in the abstract syntax tree.
-pub enum Token {
-    Emoticon(Emoticon),
-    Hashtag(Hashtag),
-    LineBreak(LineBreak),
-    Link(Link),
-    Mention(Mention),
-    Text(Text),
-    Element(Element),
+/// Strongly emphasised tokens, written `**like this**`.
+pub struct Strong {
+    pub children: Vec<Token>,
 }
 
-impl Token {
-    pub fn from_parse_pair<'t>(
-        pair: Pair<Rule>,
-        transformer: &Box<dyn 't + Fn(Token) -> Token>,
-    ) -> Vec<Self> {
-        match pair.as_rule() {
-            Rule::emoticon => Self::from_emoticon_rule(pair, transformer),
-            Rule::hashtag => Self::from_hashtag_rule(pair, transformer),
-            Rule::line_break => vec![transformer(Token::LineBreak(LineBreak))],
-            Rule::link => Self::from_link_rule(pair, transformer),
-            Rule::mention => Self::from_mention_rule(pair, transformer),
-            _ => vec![transformer(Token::Text(Text {
-                text: pair.as_str().to_string(),
-            }))],
-        }
-    }
-
-    fn from_emoticon_rule<'t>(
-        pair: Pair<Rule>,
-        transformer: &Box<dyn 't + Fn(Token) -> Token>,
-    ) -> Vec<Self> {
-        let mut tokens = Vec::new();
-        let mut name: Option<String> = None;
-
-        for pair in pair.into_inner() {
-            match pair.as_rule() {
-                Rule::emoticon_name => {
-                    name = Some(pair.as_str().to_string());
-                },
-                _ => {
-                    tokens.append(&mut Self::from_symbol_prefix(pair, transformer));
-                },
-            }
-        }
+#[cfg(feature = "markdown")]
+impl Strong {
+    pub fn render(&self, output: &mut String) {
+        output.push_str("<strong>");
 
-        if let Some(name) = name {
-            tokens.push(transformer(Token::Emoticon(Emoticon { name })));
+        for child in &self.children {
+            child.render(output);
         }
 
-        tokens
+        output.push_str("</strong>");
     }
+}
 
-    fn from_hashtag_rule<'t>(
-        pair: Pair<Rule>,
-        transformer: &Box<dyn 't + Fn(Token) -> Token>,
-    ) -> Vec<Self> {
-        let mut tokens = Vec::new();
-        let mut name: Option<String> = None;
-
-        for pair in pair.into_inner() {
-            match pair.as_rule() {
-                Rule::hashtag_name => {
-                    name = Some(pair.as_str().to_string());
-                },
-                _ => {
-                    tokens.append(&mut Self::from_symbol_prefix(pair, transformer));
-                },
-            }
-        }
+#[cfg(feature = "markdown")]
+#[derive(Clone, Debug, PartialEq)]
+/// Emphasised tokens, written `*like this*`.
+pub struct Emphasis {
+    pub children: Vec<Token>,
+}
+
+#[cfg(feature = "markdown")]
+impl Emphasis {
+    pub fn render(&self, output: &mut String) {
+        output.push_str("<em>");
 
-        if let Some(name) = name {
-            tokens.push(transformer(Token::Hashtag(Hashtag { name })));
+        for child in &self.children {
+            child.render(output);
         }
 
-        tokens
+        output.push_str("</em>");
     }
+}
 
-    fn from_link_rule<'t>(
-        pair: Pair<Rule>,
-        transformer: &Box<dyn 't + Fn(Token) -> Token>,
-    ) -> Vec<Self> {
-        let mut tokens = Vec::new();
-        let mut schema: Option<String> = None;
-        let mut tail: Option<String> = None;
-
-        for pair in pair.into_inner() {
-            match pair.as_rule() {
-                Rule::link_schema => {
-                    schema = Some(pair.as_str().to_string());
-                },
-                Rule::link_tail => {
-                    tail = Some(pair.as_str().to_string());
-                },
-                _ => {
-                    tokens.append(&mut Self::from_symbol_prefix(pair, transformer));
-                },
-            }
-        }
+#[cfg(feature = "markdown")]
+#[derive(Clone, Debug, PartialEq)]
+/// Inline code, written `` `like this` ``, which is never read for mentions and so on.
+pub struct Code {
+    pub code: String,
+}
 
-        if let (Some(schema), Some(tail)) = (schema, tail) {
-            let url = format!("{}{}", schema, tail);
+#[cfg(feature = "markdown")]
+impl Code {
+    pub fn render(&self, output: &mut String) {
+        output.push_str(&format!("<code>{}</code>", html_escape(&self.code)));
+    }
+}
 
-            tokens.push(transformer(Token::Link(Link { url })));
-        }
+#[cfg(feature = "markdown")]
+#[derive(Clone, Debug, PartialEq)]
+/// Lines of code fenced by lines of three backticks. Any info string after the opening
+/// backticks, like the language of the code, is left out.
+pub struct CodeBlock {
+    pub code: String,
+}
 
-        tokens
+#[cfg(feature = "markdown")]
+impl CodeBlock {
+    pub fn render(&self, output: &mut String) {
+        output.push_str(&format!(
+            "<pre><code>{}</code></pre>",
+            html_escape(&self.code)
+        ));
     }
+}
 
-    fn from_mention_rule<'t>(
-        pair: Pair<Rule>,
-        transformer: &Box<dyn 't + Fn(Token) -> Token>,
-    ) -> Vec<Self> {
-        let mut tokens = Vec::new();
-        let mut username: Option<String> = None;
-        let mut domain: Option<String> = None;
-
-        for pair in pair.into_inner() {
-            match pair.as_rule() {
-                Rule::mention_username => {
-                    username = Some(pair.as_str().to_string());
-                },
-                Rule::mention_domain => {
-                    domain = Some(pair.as_str().to_string());
-                },
-                _ => {
-                    tokens.append(&mut Self::from_symbol_prefix(pair, transformer));
-                },
-            }
-        }
+#[cfg(feature = "markdown")]
+#[derive(Clone, Debug, PartialEq)]
+/// Lines quoted with a `>` at the start of each, with a [`LineBreak`] between them.
+pub struct Quote {
+    pub children: Vec<Token>,
+}
 
-        if let Some(username) = username {
-            tokens.push(transformer(Token::Mention(Mention { username, domain })));
+#[cfg(feature = "markdown")]
+impl Quote {
+    pub fn render(&self, output: &mut String) {
+        output.push_str("<blockquote>");
+
+        for child in &self.children {
+            child.render(output);
         }
 
-        tokens
+        output.push_str("</blockquote>");
     }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// An item in the abstract syntax tree.
+pub enum Token {
+    Emoticon(Emoticon),
+    Hashtag(Hashtag),
+    Cashtag(Cashtag),
+    LineBreak(LineBreak),
+    Link(Link),
+    Mention(Mention),
+    Text(Text),
+    Element(Element),
+    #[cfg(feature = "markdown")]
+    Strong(Strong),
+    #[cfg(feature = "markdown")]
+    Emphasis(Emphasis),
+    #[cfg(feature = "markdown")]
+    Code(Code),
+    #[cfg(feature = "markdown")]
+    CodeBlock(CodeBlock),
+    #[cfg(feature = "markdown")]
+    Quote(Quote),
+}
 
-    fn from_symbol_prefix<'t>(
+impl Token {
+    pub fn from_parse_pair<'t>(
         pair: Pair<Rule>,
         transformer: &Box<dyn 't + Fn(Token) -> Token>,
     ) -> Vec<Self> {
-        let mut tokens = Vec::new();
-
-        for pair in pair.into_inner() {
-            match pair.as_rule() {
-                Rule::line_break => {
-                    tokens.push(transformer(Token::LineBreak(LineBreak)));
-                },
-                _ => {
-                    let text = pair.as_str().to_string();
-
-                    tokens.push(transformer(Token::Text(Text { text })));
-                },
-            }
-        }
-
-        tokens
+        Spanned::<Token>::from_parse_pair(pair, transformer)
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .collect()
     }
 
     pub fn render(&self, output: &mut String) {
@@ -289,6 +324,9 @@ impl Token {
             Token::Hashtag(token) => {
                 token.render(output);
             },
+            Token::Cashtag(token) => {
+                token.render(output);
+            },
             Token::LineBreak(token) => {
                 token.render(output);
             },
@@ -304,6 +342,109 @@ impl Token {
             Token::Element(token) => {
                 token.render(output);
             },
+            #[cfg(feature = "markdown")]
+            Token::Strong(token) => {
+                token.render(output);
+            },
+            #[cfg(feature = "markdown")]
+            Token::Emphasis(token) => {
+                token.render(output);
+            },
+            #[cfg(feature = "markdown")]
+            Token::Code(token) => {
+                token.render(output);
+            },
+            #[cfg(feature = "markdown")]
+            Token::CodeBlock(token) => {
+                token.render(output);
+            },
+            #[cfg(feature = "markdown")]
+            Token::Quote(token) => {
+                token.render(output);
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Where in the input a token was read from, as byte and as char offsets.
+///
+/// Tokens which weren't read from any input have an empty span at the start.
+pub struct Span {
+    /// The byte offset of the start of the token.
+    pub start: usize,
+    /// The byte offset just past the end of the token.
+    pub end: usize,
+    /// The char offset of the start of the token.
+    pub char_start: usize,
+    /// The char offset just past the end of the token.
+    pub char_end: usize,
+}
+
+impl Span {
+    /// A span of the bytes `start..end`, whose char offsets are filled in later by
+    /// [`Span::fill_char_offsets`].
+    pub(crate) fn bytes(start: usize, end: usize) -> Self {
+        Span {
+            start,
+            end,
+            ..Span::default()
+        }
+    }
+
+    /// Fill in the char offsets of `spans`, which must be in order, from the `input` they
+    /// were read from.
+    pub(crate) fn fill_char_offsets<'s>(input: &str, spans: impl Iterator<Item = &'s mut Span>) {
+        let mut byte = 0;
+        let mut chars = 0;
+        let mut advance = |to: usize| {
+            if to > byte {
+                chars += input[byte..to].chars().count();
+                byte = to;
+            }
+            chars
+        };
+
+        for span in spans {
+            span.char_start = advance(span.start);
+            span.char_end = advance(span.end);
         }
     }
+
+    /// The smallest span covering both this span and `other`, which comes after it.
+    pub(crate) fn to(self, other: Span) -> Self {
+        Span {
+            start: self.start,
+            end: other.end,
+            char_start: self.char_start,
+            char_end: other.char_end,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A token, along with where in the input it was read from.
+///
+/// Transformed tokens keep the span of the token they replaced.
+pub struct Spanned<T> {
+    pub token: T,
+    pub span:  Span,
+}
+
+impl<T> Spanned<T> {
+    pub(crate) fn new(token: T, span: Span) -> Self {
+        Spanned { token, span }
+    }
+}
+
+impl Spanned<Token> {
+    pub fn from_parse_pair<'t>(
+        pair: Pair<Rule>,
+        transformer: &Box<dyn 't + Fn(Token) -> Token>,
+    ) -> Vec<Self> {
+        Spanned::<borrowed::Token>::from_parse_pair(pair)
+            .into_iter()
+            .map(|spanned| Self::new(spanned.token.into_owned_with(transformer), spanned.span))
+            .collect()
+    }
 }
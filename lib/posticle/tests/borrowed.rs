@@ -0,0 +1,94 @@
+#[macro_use]
+extern crate pretty_assertions;
+extern crate posticle;
+
+use posticle::borrowed;
+use posticle::tokens::*;
+use posticle::Reader;
+
+const INPUTS: &[&str] = &[
+    "a string without at signs",
+    "text #hashtag https://example.com @mention text",
+    "(@mention@domain.place) $RUST :blobcat:",
+    "¡hola #rust\r\nhttps://xn--bcher-kva.example/ and more\n\n@alice",
+    "#not#not #文字化け",
+    "",
+];
+
+#[test]
+fn reads_the_same_tokens_as_owned_readers() {
+    for input in INPUTS {
+        let borrowed = borrowed::Reader::from(*input)
+            .into_spanned_vec()
+            .into_iter()
+            .map(|spanned| Spanned {
+                token: spanned.token.into_owned(),
+                span:  spanned.span,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            borrowed,
+            Reader::from(*input).into_spanned_vec(),
+            "reads_the_same_tokens_as_owned_readers failed on {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn borrows_text_from_the_input() {
+    let input = "hi @alice@example.com, see https://example.com #rust";
+    let range = input.as_ptr() as usize..input.as_ptr() as usize + input.len();
+    let borrowed = |text: &str| range.contains(&(text.as_ptr() as usize));
+
+    for token in borrowed::Reader::from(input) {
+        let texts = match token {
+            borrowed::Token::Text(borrowed::Text { text }) => vec![text],
+            borrowed::Token::Mention(borrowed::Mention { username, domain }) => {
+                vec![username, domain.unwrap()]
+            },
+            borrowed::Token::Link(borrowed::Link { url }) => vec![url],
+            borrowed::Token::Hashtag(borrowed::Hashtag { name }) => vec![name],
+            token => panic!("unexpected token {:?}", token),
+        };
+
+        for text in texts {
+            assert!(borrowed(text), "{} wasn't borrowed from the input", text);
+        }
+    }
+}
+
+#[test]
+fn renders_like_owned_tokens() {
+    for input in INPUTS {
+        let mut borrowed = String::new();
+        let mut owned = String::new();
+
+        for token in borrowed::Reader::from(*input) {
+            token.render(&mut borrowed);
+        }
+        for token in Reader::from(*input) {
+            token.render(&mut owned);
+        }
+
+        assert_eq!(
+            borrowed, owned,
+            "renders_like_owned_tokens failed on {}",
+            input
+        );
+    }
+}
+
+#[cfg(feature = "markdown")]
+#[test]
+fn reads_markdown() {
+    let input = "> **#rust** and `code`\n> *more*";
+
+    assert_eq!(
+        borrowed::Reader::from(input)
+            .map(borrowed::Token::into_owned)
+            .collect::<Vec<_>>(),
+        Reader::from(input).into_vec()
+    );
+}
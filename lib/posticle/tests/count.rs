@@ -0,0 +1,54 @@
+extern crate posticle;
+
+use posticle::tokens::*;
+use posticle::{count, count_tokens, ReaderBuilder, URL_LENGTH};
+
+#[test]
+fn counts_characters() {
+    assert_eq!(count(""), 0);
+    assert_eq!(count("Hello, world!"), 13);
+    assert_eq!(count("#hashtag :emoticon:"), 19);
+    assert_eq!(count("line\nbreak"), 10);
+}
+
+#[test]
+fn counts_graphemes() {
+    assert_eq!(count("文字"), 2);
+    assert_eq!(count("e\u{301}"), 1);
+    assert_eq!(count("👩‍👩‍👧‍👦 family"), 8);
+}
+
+#[test]
+fn counts_links_as_a_fixed_length() {
+    assert_eq!(count("https://a.co"), URL_LENGTH);
+    assert_eq!(
+        count("look: https://example.com/a/path/much/longer/than/twenty/three"),
+        6 + URL_LENGTH
+    );
+}
+
+#[test]
+fn counts_only_usernames_of_mentions() {
+    assert_eq!(count("@alice"), 6);
+    assert_eq!(count("@alice@example.com"), 6);
+    assert_eq!(count("@alice@example.com hi"), 9);
+}
+
+#[test]
+fn counts_transformed_tokens() {
+    let reader = ReaderBuilder::new()
+        .with_str("#hashtag")
+        .with_transformer(Box::new(|token| match token {
+            Token::Hashtag(Hashtag { name }) => Token::Element(Element {
+                name: "a".to_string(),
+                attributes: vec![],
+                children: vec![Token::Text(Text {
+                    text: format!("#{}", name),
+                })],
+            }),
+            _ => token,
+        }))
+        .finish();
+
+    assert_eq!(count_tokens(reader), 8);
+}
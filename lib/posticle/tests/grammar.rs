@@ -9,9 +9,11 @@ use posticle::grammar::*;
 fn parses_emoticons() {
     let english = ":rustodon:";
     let japanese = ":文字化け:";
+    let mastodon = ":blob_cat:";
 
     assert_eq!(english, emoticon(english).unwrap().as_str());
     assert_eq!(japanese, emoticon(japanese).unwrap().as_str());
+    assert_eq!(mastodon, emoticon(mastodon).unwrap().as_str());
 }
 
 #[test]
@@ -23,6 +25,16 @@ fn parses_hashtags() {
     assert_eq!(japanese, hashtag(japanese).unwrap().as_str());
 }
 
+#[test]
+fn parses_cashtags() {
+    let ticker = "$RUST";
+    let class = "$BRK.A";
+
+    assert_eq!(ticker, cashtag(ticker).unwrap().as_str());
+    assert_eq!(class, cashtag(class).unwrap().as_str());
+    assert!(cashtag("$100").is_err());
+}
+
 #[test]
 fn parses_links() {
     let valid_links = vec![
@@ -0,0 +1,112 @@
+#[macro_use]
+extern crate pretty_assertions;
+extern crate posticle;
+
+use posticle::tokens::*;
+use posticle::ReaderBuilder;
+
+fn read_html(html: &str) -> Vec<Token> {
+    ReaderBuilder::new()
+        .html()
+        .with_str(html)
+        .finish()
+        .into_vec()
+}
+
+#[test]
+fn reads_text_and_line_breaks() {
+    assert_eq!(
+        read_html("<p>a &amp; <b>b</b><br>c</p>\n<p>d</p>"),
+        vec![
+            Token::Text(Text {
+                text: "a & b".to_string(),
+            }),
+            Token::LineBreak(LineBreak),
+            Token::Text(Text {
+                text: "c".to_string(),
+            }),
+            Token::LineBreak(LineBreak),
+            Token::Text(Text {
+                text: "d".to_string(),
+            }),
+        ]
+    );
+}
+
+#[test]
+fn reads_mastodon_mentions() {
+    assert_eq!(
+        read_html(
+            r#"<span class="h-card"><a href="https://example.com/@alice" class="u-url mention">@<span>alice</span></a></span> <a href="https://example.com/@bob" class="mention">@bob@other.example</a>"#
+        ),
+        vec![
+            Token::Mention(Mention {
+                username: "alice".to_string(),
+                domain:   Some("example.com".to_string()),
+            }),
+            Token::Text(Text {
+                text: " ".to_string(),
+            }),
+            Token::Mention(Mention {
+                username: "bob".to_string(),
+                domain:   Some("other.example".to_string()),
+            }),
+        ]
+    );
+}
+
+#[test]
+fn reads_mastodon_hashtags() {
+    assert_eq!(
+        read_html(
+            r#"<a href="https://example.com/tags/rust" class="mention hashtag" rel="tag">#<span>rust</span></a>"#
+        ),
+        vec![Token::Hashtag(Hashtag {
+            name: "rust".to_string(),
+        })]
+    );
+}
+
+#[test]
+fn reads_links() {
+    assert_eq!(
+        read_html(
+            r#"<a href="https://example.com/a/long/path" rel="nofollow noopener"><span class="invisible">https://</span><span class="ellipsis">example.com/a/long</span><span class="invisible">/path</span></a> <a href="https://example.com">a site</a>"#
+        ),
+        vec![
+            Token::Link(Link {
+                url: "https://example.com/a/long/path".to_string(),
+            }),
+            Token::Text(Text {
+                text: " ".to_string(),
+            }),
+            Token::Element(Element {
+                name: "a".to_string(),
+                attributes: vec![("href".to_string(), "https://example.com".to_string())],
+                children: vec![Token::Text(Text {
+                    text: "a site".to_string(),
+                })],
+            }),
+        ]
+    );
+}
+
+#[test]
+fn transforms_html_tokens() {
+    let tokens = ReaderBuilder::new()
+        .html()
+        .with_str(r#"<p><a class="hashtag" href="https://example.com/tags/a">#a</a></p>"#)
+        .with_transformer(Box::new(|token| match token {
+            Token::Hashtag(Hashtag { name }) => Token::Text(Text { text: name }),
+            _ => token,
+        }))
+        .finish()
+        .into_vec();
+
+    assert_eq!(
+        tokens,
+        vec![Token::Text(Text {
+            text: "a".to_string(),
+        })]
+    );
+}
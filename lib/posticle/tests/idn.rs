@@ -0,0 +1,134 @@
+#[macro_use]
+extern crate pretty_assertions;
+extern crate posticle;
+extern crate yaml_rust;
+
+use posticle::tokens::*;
+use posticle::{Reader, Writer};
+
+#[test]
+fn normalizes_all_tlds() {
+    let tests = include_str!("data/tlds.yml");
+    let tests = yaml_rust::YamlLoader::load_from_str(tests).unwrap();
+    let tests = tests.first().unwrap();
+    let tests = &tests["tests"];
+
+    for (suite, test_cases) in tests.as_hash().expect("could not load tests document") {
+        let suite = suite.as_str().expect("suite could not be loaded");
+
+        for test in test_cases.as_vec().expect("suite could not be loaded") {
+            let text = test["text"].as_str().expect("test was missing 'text'");
+            let link = match Reader::from(text).into_vec().pop() {
+                Some(Token::Link(link)) => link,
+                token => panic!("test {} read {:?} from \"{}\"", suite, token, text),
+            };
+            let ascii = link.ascii_url();
+            let ascii_link = Link { url: ascii.clone() };
+
+            assert!(
+                ascii.is_ascii(),
+                "test {} made \"{}\" of \"{}\"",
+                suite,
+                ascii,
+                text
+            );
+            assert_eq!(
+                ascii_link.unicode_url(),
+                text,
+                "test {} failed to show \"{}\" in unicode",
+                suite,
+                ascii
+            );
+        }
+    }
+}
+
+#[test]
+fn normalizes_mention_domains() {
+    let mention = |domain: &str| Mention {
+        username: "alice".to_string(),
+        domain:   Some(domain.to_string()),
+    };
+
+    assert_eq!(
+        mention("Bücher.Example").ascii_domain(),
+        Some("xn--bcher-kva.example".to_string())
+    );
+    assert_eq!(
+        mention("xn--bcher-kva.example").ascii_domain(),
+        Some("xn--bcher-kva.example".to_string())
+    );
+    assert_eq!(
+        mention("xn--bcher-kva.example").unicode_domain(),
+        Some("bücher.example".to_string())
+    );
+    assert_eq!(
+        mention("Example.com").unicode_domain(),
+        Some("Example.com".to_string())
+    );
+}
+
+#[test]
+fn leaves_other_urls_alone() {
+    let urls = vec![
+        "https://example.com:8080/xn--path",
+        "https://user@xn--bcher-kva.example:443/?q#f",
+        "http://[::1]/",
+        "mailto:alice@example.com",
+    ];
+    let unicode = vec![
+        "https://example.com:8080/xn--path",
+        "https://user@bücher.example:443/?q#f",
+        "http://[::1]/",
+        "mailto:alice@example.com",
+    ];
+
+    for (url, expected) in urls.into_iter().zip(unicode) {
+        let link = Link {
+            url: url.to_string(),
+        };
+
+        assert_eq!(link.unicode_url(), expected);
+        assert_eq!(link.ascii_url(), url);
+    }
+}
+
+#[test]
+fn renders_unicode_domains() {
+    let writer = Writer::from(Reader::from(
+        "@alice@xn--bcher-kva.example https://xn--bcher-kva.example/",
+    ));
+
+    assert_eq!(
+        writer.to_string(),
+        "@alice@bücher.example https://bücher.example/"
+    );
+}
+
+#[test]
+fn keeps_deceptive_domains_in_punycode() {
+    let domains = vec![
+        // "аpple", with a Cyrillic "а".
+        ("xn--pple-43d.com", "xn--pple-43d.com"),
+        // "аррӏе", all in Cyrillic letters which look like Latin ones.
+        ("xn--80ak6aa92e.com", "xn--80ak6aa92e.com"),
+        // "aβc", mixing Latin and Greek.
+        ("xn--ac-f9b.example", "xn--ac-f9b.example"),
+        (
+            "xn--pple-43d.xn--bcher-kva.example",
+            "xn--pple-43d.bücher.example",
+        ),
+        ("xn--abc-mj4ba3j6cz996b6htb.jp", "abc漢字カタカナ.jp"),
+        ("twitter.xn--p1acf", "twitter.рус"),
+    ];
+
+    for (domain, expected) in domains {
+        assert_eq!(posticle::idn::to_unicode(domain), expected);
+    }
+
+    let link = Link {
+        url: "https://xn--pple-43d.com/".to_string(),
+    };
+
+    assert_eq!(link.unicode_url(), "https://xn--pple-43d.com/");
+}
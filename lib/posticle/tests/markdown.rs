@@ -0,0 +1,200 @@
+#![cfg(feature = "markdown")]
+
+#[macro_use]
+extern crate pretty_assertions;
+extern crate posticle;
+
+use posticle::render::*;
+use posticle::tokens::*;
+use posticle::{count, Reader, Writer, WriterBuilder};
+
+fn text(text: &str) -> Token {
+    Token::Text(Text {
+        text: text.to_string(),
+    })
+}
+
+fn hashtag(name: &str) -> Token {
+    Token::Hashtag(Hashtag {
+        name: name.to_string(),
+    })
+}
+
+#[test]
+fn extracts_strong_and_emphasis() {
+    assert_eq!(
+        Reader::from("very **strong** and *emphasised*").into_vec(),
+        vec![
+            text("very "),
+            Token::Strong(Strong {
+                children: vec![text("strong")],
+            }),
+            text(" and "),
+            Token::Emphasis(Emphasis {
+                children: vec![text("emphasised")],
+            }),
+        ]
+    );
+    assert_eq!(
+        Reader::from("**a *b* c**").into_vec(),
+        vec![Token::Strong(Strong {
+            children: vec![
+                text("a "),
+                Token::Emphasis(Emphasis {
+                    children: vec![text("b")],
+                }),
+                text(" c"),
+            ],
+        })]
+    );
+}
+
+#[test]
+fn ignores_stray_asterisks() {
+    let inputs = vec![
+        "2 * 3 * 4",
+        "** not strong **",
+        "*not closed",
+        "snake_case_name",
+    ];
+
+    for input in inputs {
+        assert_eq!(
+            Reader::from(input).into_vec(),
+            vec![text(input)],
+            "ignores_stray_asterisks failed on {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn extracts_symbols_inside_markdown() {
+    assert_eq!(
+        Reader::from("**#rust** *hi @alice@example.com*").into_vec(),
+        vec![
+            Token::Strong(Strong {
+                children: vec![hashtag("rust")],
+            }),
+            text(" "),
+            Token::Emphasis(Emphasis {
+                children: vec![
+                    text("hi "),
+                    Token::Mention(Mention {
+                        username: "alice".to_string(),
+                        domain:   Some("example.com".to_string()),
+                    }),
+                ],
+            }),
+        ]
+    );
+    assert_eq!(
+        Reader::from("**https://example.com/**").into_vec(),
+        vec![Token::Strong(Strong {
+            children: vec![Token::Link(Link {
+                url: "https://example.com/".to_string(),
+            })],
+        })]
+    );
+    assert_eq!(
+        Reader::from("https://example.com/a** b").into_vec(),
+        vec![
+            Token::Link(Link {
+                url: "https://example.com/a".to_string(),
+            }),
+            text("** b"),
+        ]
+    );
+}
+
+#[test]
+fn extracts_code() {
+    assert_eq!(
+        Reader::from("run `cargo test #tag`").into_vec(),
+        vec![
+            text("run "),
+            Token::Code(Code {
+                code: "cargo test #tag".to_string(),
+            }),
+        ]
+    );
+    assert_eq!(
+        Reader::from("look:\n```rust\nlet a = *b*;\n\n@c\n```\nnice").into_vec(),
+        vec![
+            text("look:"),
+            Token::LineBreak(LineBreak),
+            Token::CodeBlock(CodeBlock {
+                code: "let a = *b*;\n\n@c".to_string(),
+            }),
+            Token::LineBreak(LineBreak),
+            text("nice"),
+        ]
+    );
+}
+
+#[test]
+fn extracts_quotes() {
+    assert_eq!(
+        Reader::from("> #quoted\n> **twice**\nnot quoted >").into_vec(),
+        vec![
+            Token::Quote(Quote {
+                children: vec![
+                    text(" "),
+                    hashtag("quoted"),
+                    Token::LineBreak(LineBreak),
+                    text(" "),
+                    Token::Strong(Strong {
+                        children: vec![text("twice")],
+                    }),
+                ],
+            }),
+            Token::LineBreak(LineBreak),
+            text("not quoted >"),
+        ]
+    );
+    assert_eq!(Reader::from(">_<").into_vec(), vec![text(">_<")]);
+}
+
+#[test]
+fn reads_markdown_spans() {
+    let tokens = Reader::from("a\n> b").into_spanned_vec();
+
+    assert_eq!(tokens[1].token, Token::LineBreak(LineBreak));
+    assert_eq!((tokens[1].span.start, tokens[1].span.end), (1, 2));
+    assert_eq!((tokens[2].span.start, tokens[2].span.end), (2, 5));
+}
+
+#[test]
+fn renders_markdown_as_html() {
+    let writer = Writer::from(Reader::from(
+        "> **<b>** `a < b`\n```\n<script>\n```\n*#tag*",
+    ));
+
+    assert_eq!(
+        writer.to_string(),
+        "<blockquote> <strong>&lt;b&gt;</strong> <code>a &lt; b</code></blockquote>\n<br>\
+         <pre><code>&lt;script&gt;</code></pre>\n<br><em>#tag</em>"
+    );
+}
+
+#[test]
+fn renders_markdown_as_written() {
+    let input = "> **bold** and *#tag*\n> `code`\n```\nx\n```";
+    let writer = WriterBuilder::new()
+        .with_reader(Reader::from(input))
+        .with_renderer(PlainText)
+        .finish();
+
+    assert_eq!(writer.to_string(), input);
+    assert_eq!(count(input), input.chars().count());
+}
+
+#[test]
+fn renders_markdown_as_markdown() {
+    let writer = WriterBuilder::new()
+        .with_reader(Reader::from("**a_b** > c"))
+        .with_renderer(Markdown)
+        .finish();
+
+    assert_eq!(writer.to_string(), "**a\\_b** \\> c");
+}
@@ -0,0 +1,110 @@
+#[macro_use]
+extern crate pretty_assertions;
+extern crate posticle;
+
+use posticle::render::*;
+use posticle::tokens::*;
+use posticle::{Reader, WriterBuilder};
+
+fn linked_tokens() -> Vec<Token> {
+    vec![
+        Token::Text(Text {
+            text: "<hi> & ".to_string(),
+        }),
+        Token::Element(Element {
+            name: "a".to_string(),
+            attributes: vec![("href".to_string(), "https://example.com/>".to_string())],
+            children: vec![Token::Text(Text {
+                text: "#tag".to_string(),
+            })],
+        }),
+        Token::LineBreak(LineBreak),
+        Token::Element(Element {
+            name: "img".to_string(),
+            attributes: vec![
+                (
+                    "src".to_string(),
+                    "https://example.com/blobcat.png".to_string(),
+                ),
+                ("alt".to_string(), ":blob_cat:".to_string()),
+            ],
+            children: vec![],
+        }),
+        Token::Mention(Mention {
+            username: "user_name".to_string(),
+            domain:   Some("example.com".to_string()),
+        }),
+    ]
+}
+
+fn render<R: Render>(tokens: Vec<Token>, renderer: R) -> String {
+    WriterBuilder::new()
+        .with_tokens(tokens)
+        .with_renderer(renderer)
+        .finish()
+        .to_string()
+}
+
+#[test]
+fn renders_html_by_default() {
+    let tokens = Reader::from("<b>#tag</b>\nhttps://example.com").into_vec();
+
+    assert_eq!(
+        render(tokens.clone(), Html::default()),
+        WriterBuilder::new()
+            .with_tokens(tokens)
+            .finish()
+            .to_string()
+    );
+}
+
+#[test]
+fn renders_plain_text() {
+    assert_eq!(
+        render(linked_tokens(), PlainText),
+        "<hi> & #tag\n:blob_cat:@user_name@example.com"
+    );
+}
+
+#[test]
+fn renders_markdown() {
+    assert_eq!(
+        render(linked_tokens(), Markdown),
+        "\\<hi\\> \\& [\\#tag](<https://example.com/%3E>)  \n![:blob\\_cat:](<https://example.com/blobcat.png>)@user\\_name@example.com"
+    );
+}
+
+#[test]
+fn escapes_markdown_syntax() {
+    let markdown = |text: &str| render(Reader::from(text).into_vec(), Markdown);
+
+    assert_eq!(markdown("a &lt; b"), "a \\&lt; b");
+    assert_eq!(markdown("1. not a list"), "1\\. not a list");
+    assert_eq!(markdown("10) not a list"), "10\\) not a list");
+    assert_eq!(markdown("- item"), "\\- item");
+    assert_eq!(markdown("+ item"), "\\+ item");
+    assert_eq!(markdown("   - item"), "   \\- item");
+    assert_eq!(markdown("a - b + 1. c"), "a - b + 1. c");
+    assert_eq!(markdown("x\n- item"), "x  \n\\- item");
+    assert_eq!(markdown("![alt](x) | y"), "\\!\\[alt\\]\\(x\\) \\| y");
+}
+
+#[test]
+fn renders_with_custom_renderers() {
+    struct Shouting;
+
+    impl Render for Shouting {
+        fn render(&self, token: &Token, output: &mut String) {
+            PlainText.render(token, output);
+        }
+
+        fn finish(&self, output: &str) -> String {
+            output.to_uppercase()
+        }
+    }
+
+    assert_eq!(
+        render(Reader::from("hi @you").into_vec(), Shouting),
+        "HI @YOU"
+    );
+}
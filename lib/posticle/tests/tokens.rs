@@ -3,7 +3,7 @@ extern crate pretty_assertions;
 extern crate posticle;
 
 use posticle::tokens::*;
-use posticle::Reader;
+use posticle::{Reader, ReaderBuilder};
 
 #[test]
 fn extracts_nothing() {
@@ -145,6 +145,79 @@ fn ignores_invalid_hashtags() {
     }
 }
 
+#[test]
+fn extracts_cashtags() {
+    let cashtags = vec!["$RUST", "$rust", "$BRK.A", "$RDS_A"];
+
+    for cashtag in cashtags {
+        assert_eq!(
+            Reader::from(format!("({})", cashtag)).into_vec(),
+            vec![
+                Token::Text(Text {
+                    text: "(".to_string(),
+                }),
+                Token::Cashtag(Cashtag {
+                    name: cashtag[1..].to_string(),
+                }),
+                Token::Text(Text {
+                    text: ")".to_string(),
+                })
+            ],
+            "extracts_cashtags failed on {}",
+            cashtag
+        );
+    }
+}
+
+#[test]
+fn ignores_invalid_cashtags() {
+    let cashtags = vec!["$5", "$TOOLONG", "$$RUST", "$RUST$", "a$RUST", "$BRK.ABC"];
+
+    for cashtag in cashtags {
+        assert_eq!(
+            Reader::from(cashtag).into_vec(),
+            vec![Token::Text(Text {
+                text: cashtag.to_string(),
+            })],
+            "ignores_invalid_cashtags failed on {}",
+            cashtag
+        );
+    }
+}
+
+#[test]
+fn keeps_dollar_signs_in_hashtags_and_mentions() {
+    assert_eq!(
+        Reader::from("#rust$ ok").into_vec(),
+        vec![
+            Token::Hashtag(Hashtag {
+                name: "rust$".to_string(),
+            }),
+            Token::Text(Text {
+                text: " ok".to_string(),
+            }),
+        ]
+    );
+    assert_eq!(
+        Reader::from("#a$b").into_vec(),
+        vec![Token::Hashtag(Hashtag {
+            name: "a$b".to_string(),
+        })]
+    );
+    assert_eq!(
+        Reader::from("@bob$ hi").into_vec(),
+        vec![
+            Token::Mention(Mention {
+                username: "bob$".to_string(),
+                domain:   None,
+            }),
+            Token::Text(Text {
+                text: " hi".to_string(),
+            }),
+        ]
+    );
+}
+
 #[test]
 fn extracts_links() {
     let links = vec![
@@ -213,6 +286,22 @@ fn extracts_links_in_punctuation() {
     }
 }
 
+#[test]
+#[cfg(not(feature = "markdown"))]
+fn only_ends_links_at_emphasis_with_markdown() {
+    assert_eq!(
+        Reader::from("https://example.com/a** b").into_vec(),
+        vec![
+            Token::Link(Link {
+                url: "https://example.com/a*".to_string(),
+            }),
+            Token::Text(Text {
+                text: "* b".to_string(),
+            }),
+        ]
+    );
+}
+
 #[test]
 fn ignores_invalid_links() {
     let links = vec!["x- text http:// yo", "_=_:thing", "nö://thing/else yo"];
@@ -259,3 +348,149 @@ fn extracts_all() {
         ]
     );
 }
+
+#[test]
+fn reads_only_enabled_symbols() {
+    let input = "@alice #rust $RUST :blobcat: https://example.com";
+    let reader = ReaderBuilder::new()
+        .with_mentions(false)
+        .with_cashtags(false)
+        .with_emoticons(false)
+        .with_str(input)
+        .finish();
+
+    assert_eq!(
+        reader.into_vec(),
+        vec![
+            Token::Text(Text {
+                text: "@alice ".to_string(),
+            }),
+            Token::Hashtag(Hashtag {
+                name: "rust".to_string(),
+            }),
+            Token::Text(Text {
+                text: " $RUST :blobcat: ".to_string(),
+            }),
+            Token::Link(Link {
+                url: "https://example.com".to_string(),
+            }),
+        ]
+    );
+
+    let reader = ReaderBuilder::new()
+        .with_hashtags(false)
+        .with_transformer(Box::new(|token| match token {
+            Token::Hashtag(_) => panic!("disabled symbols shouldn't be transformed"),
+            token => token,
+        }))
+        .with_str(input)
+        .finish();
+
+    assert_eq!(
+        reader.into_vec()[1],
+        Token::Text(Text {
+            text: " #rust ".to_string(),
+        })
+    );
+}
+
+#[test]
+fn reads_plain_text() {
+    assert_eq!(
+        ReaderBuilder::new()
+            .plain_text()
+            .with_str("@mention #hashtag\r\nhttps://example.com")
+            .finish()
+            .into_vec(),
+        vec![
+            Token::Text(Text {
+                text: "@mention #hashtag".to_string(),
+            }),
+            Token::LineBreak(LineBreak),
+            Token::Text(Text {
+                text: "https://example.com".to_string(),
+            })
+        ]
+    );
+}
+
+#[test]
+fn tries_to_read_tokens() {
+    assert_eq!(
+        ReaderBuilder::new()
+            .with_str("#hashtag")
+            .try_finish()
+            .unwrap()
+            .into_vec(),
+        Reader::from("#hashtag").into_vec()
+    );
+}
+
+#[test]
+fn reports_error_positions() {
+    let error =
+        posticle::Error::from(posticle::grammar::hashtag("line\nnot a hashtag").unwrap_err());
+
+    assert_eq!(error.position, 0);
+    assert_eq!((error.line, error.column), (1, 1));
+    assert!(error.to_string().ends_with("at line 1, column 1"));
+}
+
+#[test]
+fn reads_token_spans() {
+    let spans = Reader::from("文字 #tag @user")
+        .into_spanned_vec()
+        .into_iter()
+        .map(|spanned| spanned.span)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        spans,
+        vec![
+            Span {
+                start: 0,
+                end: 7,
+                char_start: 0,
+                char_end: 3,
+            },
+            Span {
+                start: 7,
+                end: 11,
+                char_start: 3,
+                char_end: 7,
+            },
+            Span {
+                start: 11,
+                end: 12,
+                char_start: 7,
+                char_end: 8,
+            },
+            Span {
+                start: 12,
+                end: 17,
+                char_start: 8,
+                char_end: 13,
+            },
+        ]
+    );
+}
+
+#[test]
+fn reads_plain_text_spans() {
+    let spans = ReaderBuilder::new()
+        .plain_text()
+        .with_str("ä\r\nb")
+        .finish()
+        .into_spanned_vec()
+        .into_iter()
+        .map(|spanned| {
+            (
+                spanned.span.start,
+                spanned.span.end,
+                spanned.span.char_start,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(spans, vec![(0, 2, 0), (2, 4, 1), (4, 5, 3)]);
+}
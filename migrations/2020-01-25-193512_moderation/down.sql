@@ -0,0 +1,7 @@
+ALTER TABLE accounts
+    DROP COLUMN force_sensitive,
+    DROP COLUMN suspended,
+    DROP COLUMN silenced;
+
+ALTER TABLE users
+    DROP COLUMN role;
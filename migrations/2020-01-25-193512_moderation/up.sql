@@ -0,0 +1,7 @@
+ALTER TABLE users
+    ADD COLUMN role VARCHAR NOT NULL DEFAULT 'user';
+
+ALTER TABLE accounts
+    ADD COLUMN silenced BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN suspended BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN force_sensitive BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+DROP TABLE follow_requests;
+
+ALTER TABLE accounts
+    DROP COLUMN inbox_uri,
+    DROP COLUMN locked;
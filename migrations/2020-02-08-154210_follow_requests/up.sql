@@ -0,0 +1,13 @@
+ALTER TABLE accounts
+    ADD COLUMN locked BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN inbox_uri VARCHAR;
+
+CREATE TABLE follow_requests (
+    id BIGINT PRIMARY KEY,
+    source_id BIGINT REFERENCES accounts(id) ON DELETE CASCADE NOT NULL,
+    target_id BIGINT REFERENCES accounts(id) ON DELETE CASCADE NOT NULL,
+    uri VARCHAR,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+    UNIQUE(source_id, target_id)
+);
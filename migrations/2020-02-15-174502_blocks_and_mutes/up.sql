@@ -0,0 +1,15 @@
+CREATE TABLE blocks (
+    id BIGINT PRIMARY KEY,
+    account_id BIGINT REFERENCES accounts(id) ON DELETE CASCADE NOT NULL,
+    target_id BIGINT REFERENCES accounts(id) ON DELETE CASCADE NOT NULL,
+
+    UNIQUE(account_id, target_id)
+);
+
+CREATE TABLE mutes (
+    id BIGINT PRIMARY KEY,
+    account_id BIGINT REFERENCES accounts(id) ON DELETE CASCADE NOT NULL,
+    target_id BIGINT REFERENCES accounts(id) ON DELETE CASCADE NOT NULL,
+
+    UNIQUE(account_id, target_id)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE notifications (
+    id BIGINT PRIMARY KEY,
+    account_id BIGINT REFERENCES accounts(id) ON DELETE CASCADE NOT NULL,
+    from_account_id BIGINT REFERENCES accounts(id) ON DELETE CASCADE NOT NULL,
+    kind VARCHAR NOT NULL,
+    status_id BIGINT REFERENCES statuses(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    read BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX notifications_account_id_id_idx ON notifications (account_id, id);
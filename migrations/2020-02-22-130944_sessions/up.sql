@@ -0,0 +1,11 @@
+CREATE TABLE sessions (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    token_hash VARCHAR NOT NULL UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    user_agent VARCHAR,
+    ip VARCHAR
+);
+
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
@@ -0,0 +1,5 @@
+ALTER TABLE users
+    DROP COLUMN reset_password_sent_at,
+    DROP COLUMN reset_password_token_hash,
+    DROP COLUMN confirmation_token_hash,
+    DROP COLUMN confirmed_at;
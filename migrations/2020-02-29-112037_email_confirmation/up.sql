@@ -0,0 +1,9 @@
+-- tokens are only stored hashed; see `crypto::hash_token`.
+ALTER TABLE users
+    ADD COLUMN confirmed_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN confirmation_token_hash VARCHAR UNIQUE,
+    ADD COLUMN reset_password_token_hash VARCHAR UNIQUE,
+    ADD COLUMN reset_password_sent_at TIMESTAMP WITH TIME ZONE;
+
+-- nobody could confirm their email before now, so don't lock existing users out.
+UPDATE users SET confirmed_at = now();
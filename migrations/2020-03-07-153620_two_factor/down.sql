@@ -0,0 +1,6 @@
+DROP TABLE recovery_codes;
+
+ALTER TABLE users
+    DROP COLUMN otp_last_step,
+    DROP COLUMN otp_enabled,
+    DROP COLUMN otp_secret;
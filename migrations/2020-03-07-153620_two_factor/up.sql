@@ -0,0 +1,11 @@
+ALTER TABLE users
+    ADD COLUMN otp_secret VARCHAR,
+    ADD COLUMN otp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN otp_last_step BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE recovery_codes (
+    id BIGINT PRIMARY KEY,
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    code_hash VARCHAR NOT NULL,
+    UNIQUE (user_id, code_hash)
+);
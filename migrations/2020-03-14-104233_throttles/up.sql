@@ -0,0 +1,9 @@
+CREATE TABLE throttles (
+    id BIGINT PRIMARY KEY,
+    action VARCHAR NOT NULL,
+    key VARCHAR NOT NULL,
+    hits INTEGER NOT NULL DEFAULT 0,
+    last_hit_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    blocked_until TIMESTAMP WITH TIME ZONE,
+    UNIQUE (action, key)
+);
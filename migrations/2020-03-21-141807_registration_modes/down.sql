@@ -0,0 +1,3 @@
+DROP TABLE invites;
+
+ALTER TABLE users DROP COLUMN approved;
@@ -0,0 +1,14 @@
+-- everybody who signed up before now was let in straight away.
+ALTER TABLE users ADD COLUMN approved BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE TABLE invites (
+    id BIGINT PRIMARY KEY,
+    code VARCHAR NOT NULL UNIQUE,
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    max_uses INTEGER,
+    uses INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX invites_user_id_idx ON invites (user_id);
@@ -0,0 +1,35 @@
+-- secrets, codes and tokens are only stored hashed; see `crypto::hash_token`.
+CREATE TABLE oauth_applications (
+    id BIGINT PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    website VARCHAR,
+    -- newline-separated, since a client may register several.
+    redirect_uris TEXT NOT NULL,
+    scopes VARCHAR NOT NULL,
+    client_id VARCHAR NOT NULL UNIQUE,
+    client_secret_hash VARCHAR NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE oauth_authorization_codes (
+    id BIGINT PRIMARY KEY,
+    code_hash VARCHAR NOT NULL UNIQUE,
+    application_id BIGINT REFERENCES oauth_applications(id) ON DELETE CASCADE NOT NULL,
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    redirect_uri TEXT NOT NULL,
+    scopes VARCHAR NOT NULL,
+    code_challenge VARCHAR,
+    code_challenge_method VARCHAR,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE oauth_access_tokens (
+    id BIGINT PRIMARY KEY,
+    token_hash VARCHAR NOT NULL UNIQUE,
+    application_id BIGINT REFERENCES oauth_applications(id) ON DELETE CASCADE NOT NULL,
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    scopes VARCHAR NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX oauth_access_tokens_user_id_idx ON oauth_access_tokens (user_id);
@@ -0,0 +1,2 @@
+-- accounts we knew about before now get the time of the migration, which is the best we can do.
+ALTER TABLE accounts ADD COLUMN created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now();
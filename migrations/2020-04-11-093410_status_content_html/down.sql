@@ -0,0 +1,3 @@
+ALTER TABLE statuses
+    DROP COLUMN content_html_version,
+    DROP COLUMN content_html;
@@ -0,0 +1,5 @@
+-- the rendered HTML of a status, filled in the first time it's shown, and the version of the
+-- renderer which rendered it, so it's rendered again when statuses render differently.
+ALTER TABLE statuses
+    ADD COLUMN content_html TEXT,
+    ADD COLUMN content_html_version INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,15 @@
+-- emoji which can be used in statuses by their `:shortcode:`. Local emoji have an empty
+-- domain, like local accounts. every emoji's image is kept in the media directory, so
+-- people looking at remote ones don't load them from other servers; `remote_image_url` is
+-- where a remote emoji's image was fetched from.
+CREATE TABLE custom_emojis (
+    id BIGINT PRIMARY KEY,
+    shortcode VARCHAR NOT NULL,
+    domain VARCHAR NOT NULL,
+    image VARCHAR NOT NULL,
+    uri VARCHAR,
+    remote_image_url VARCHAR,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    UNIQUE (shortcode, domain)
+);
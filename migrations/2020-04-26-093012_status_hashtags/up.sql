@@ -0,0 +1,10 @@
+-- the hashtags used by each status, as posticle finds them, lowercased so that tags match
+-- regardless of case. statuses posted before this migration are indexed by
+-- `rustodonctl index-hashtags`.
+CREATE TABLE status_hashtags (
+    status_id BIGINT REFERENCES statuses(id) ON DELETE CASCADE NOT NULL,
+    name VARCHAR NOT NULL,
+    PRIMARY KEY (status_id, name)
+);
+
+CREATE INDEX status_hashtags_name_idx ON status_hashtags (name, status_id);
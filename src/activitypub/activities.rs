@@ -0,0 +1,39 @@
+//! Builders for the activities we send to other servers.
+
+use serde_json::{json, Value};
+
+use crate::db::id_generator;
+use crate::db::models::{Account, FollowRequest};
+
+/// Returns the `Follow` activity behind a follow request.
+pub fn follow(request: &FollowRequest, source: &Account, target: &Account) -> Value {
+    json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "type": "Follow",
+        "id": request.uri.clone().unwrap_or_else(|| format!("{}#follows/{}", source.get_uri(), request.id)),
+        "actor": source.get_uri(),
+        "object": target.get_uri(),
+    })
+}
+
+/// Returns an `Accept` of `follow`, sent by the followed account.
+pub fn accept_follow(follow: Value, target: &Account) -> Value {
+    json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "type": "Accept",
+        "id": format!("{}#accepts/follows/{}", target.get_uri(), id_generator().next()),
+        "actor": target.get_uri(),
+        "object": follow,
+    })
+}
+
+/// Returns a `Reject` of `follow`, sent by the followed account.
+pub fn reject_follow(follow: Value, target: &Account) -> Value {
+    json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "type": "Reject",
+        "id": format!("{}#rejects/follows/{}", target.get_uri(), id_generator().next()),
+        "actor": target.get_uri(),
+        "object": follow,
+    })
+}
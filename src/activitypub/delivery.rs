@@ -0,0 +1,131 @@
+//! Delivery of activities to remote inboxes.
+
+use chrono::offset::Utc;
+use failure::{format_err, Error};
+use lazy_static::lazy_static;
+use reqwest::header::{CONTENT_TYPE, DATE, HOST};
+use reqwest::Url;
+use serde_json::Value;
+use slog_scope::warn;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::{signatures, HTTP_CLIENT};
+use crate::db::models::Account;
+
+/// How many deliveries can be in flight at once.
+const DELIVERY_WORKERS: usize = 4;
+
+/// How many deliveries can wait for a worker; any more are dropped.
+const DELIVERY_QUEUE_LIMIT: usize = 1024;
+
+lazy_static! {
+    static ref DELIVERY_QUEUE: Mutex<SyncSender<Delivery>> = Mutex::new(start_workers());
+}
+
+/// A signed request, ready to POST to an inbox.
+struct Delivery {
+    url: Url,
+    host: String,
+    date: String,
+    digest: String,
+    signature: String,
+    body: String,
+}
+
+impl Delivery {
+    /// Sends the request, logging it if it fails.
+    fn send(self) {
+        let Delivery {
+            url,
+            host,
+            date,
+            digest,
+            signature,
+            body,
+        } = self;
+
+        let result = HTTP_CLIENT
+            .post(url.clone())
+            .header(HOST, host)
+            .header(DATE, date)
+            .header("Digest", digest)
+            .header("Signature", signature)
+            .header(CONTENT_TYPE, "application/activity+json")
+            .body(body)
+            .send()
+            .and_then(|response| response.error_for_status());
+
+        if let Err(e) = result {
+            warn!("Failed to deliver activity to {}: {:?}", url, e);
+        }
+    }
+}
+
+/// Starts the [`DELIVERY_WORKERS`] threads which send queued deliveries, returning the queue.
+fn start_workers() -> SyncSender<Delivery> {
+    let (sender, receiver) = sync_channel(DELIVERY_QUEUE_LIMIT);
+    let receiver: Arc<Mutex<Receiver<Delivery>>> = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..DELIVERY_WORKERS {
+        let receiver = Arc::clone(&receiver);
+        thread::spawn(move || loop {
+            // don't hold the lock while sending, so the other workers can pick up deliveries.
+            let next = receiver.lock().unwrap().recv();
+            match next {
+                Ok(delivery) => delivery.send(),
+                Err(_) => return,
+            }
+        });
+    }
+
+    sender
+}
+
+/// Signs `activity` as `sender` and queues it to be POSTed to `inbox` in the background.
+///
+/// Errors in building the request are returned, but failures to deliver it are only logged,
+/// as are deliveries dropped because the queue is full.
+pub fn deliver(activity: &Value, sender: &Account, inbox: &str) -> Result<(), Error> {
+    let url = Url::parse(inbox)?;
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return Err(format_err!("inbox {} has no host", inbox)),
+    };
+    let target = match url.query() {
+        Some(query) => format!("post {}?{}", url.path(), query),
+        None => format!("post {}", url.path()),
+    };
+
+    let body = serde_json::to_string(activity)?;
+    let date = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    let digest = signatures::digest(body.as_bytes());
+    let signature = signatures::sign(
+        sender,
+        &[
+            ("(request-target)", &target),
+            ("host", &host),
+            ("date", &date),
+            ("digest", &digest),
+        ],
+    )?;
+
+    let delivery = Delivery {
+        url,
+        host,
+        date,
+        digest,
+        signature,
+        body,
+    };
+    if let Err(TrySendError::Full(delivery)) = DELIVERY_QUEUE.lock().unwrap().try_send(delivery) {
+        warn!(
+            "Delivery queue is full, dropping activity to {}",
+            delivery.url
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+//! Resolution of remote ActivityPub objects.
+
+use failure::{format_err, Error};
+use openssl::rsa::Rsa;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+use serde_json::Value;
+use slog_scope::warn;
+use std::fs;
+use std::io::Read;
+
+use super::HTTP_CLIENT;
+use crate::db::models::{Account, CustomEmoji, NewAccount};
+use crate::db::validators::VALID_EMOJI_SHORTCODE_RE;
+use crate::db::{id_generator, DbConnection};
+use crate::MEDIA_DIR;
+
+/// The largest image of a remote custom emoji we'll fetch, in bytes.
+const MAX_EMOJI_IMAGE_SIZE: u64 = 256 * 1024;
+
+/// Returns the account for the actor at `uri`, fetching and storing it if it isn't known yet.
+///
+/// Fetching blocks the calling thread, for at most the client's timeout.
+pub fn fetch_actor(db_conn: &DbConnection, uri: &str) -> Result<Account, Error> {
+    if let Some(account) = Account::fetch_by_uri(db_conn, uri)? {
+        return Ok(account);
+    }
+
+    let url = Url::parse(uri)?;
+    let domain = url
+        .host_str()
+        .ok_or_else(|| format_err!("actor {} has no host", uri))?;
+
+    let actor: Value = HTTP_CLIENT
+        .get(url.clone())
+        .header(ACCEPT, "application/activity+json")
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    // don't let an actor claim to be somebody else
+    if actor["id"].as_str() != Some(uri) {
+        return Err(format_err!("actor fetched from {} has a different id", uri));
+    }
+
+    let username = actor["preferredUsername"]
+        .as_str()
+        .ok_or_else(|| format_err!("actor {} has no preferredUsername", uri))?;
+    let inbox = actor["inbox"]
+        .as_str()
+        .ok_or_else(|| format_err!("actor {} has no inbox", uri))?;
+    let pubkey_pem = actor["publicKey"]["publicKeyPem"]
+        .as_str()
+        .ok_or_else(|| format_err!("actor {} has no public key", uri))?;
+
+    let account = NewAccount {
+        id: id_generator().next(),
+        uri: Some(uri.to_string()),
+        domain: Some(domain.to_string()),
+
+        username: username.to_string(),
+
+        display_name: actor["name"].as_str().map(String::from),
+        summary: actor["summary"].as_str().map(String::from),
+
+        pubkey:  Rsa::public_key_from_pem(pubkey_pem.as_bytes())?.public_key_to_der()?,
+        privkey: None,
+
+        inbox_uri: Some(inbox.to_string()),
+    }
+    .insert(db_conn)?;
+
+    // the emoji used in the actor's name and bio.
+    store_emojis(db_conn, &account, &actor)?;
+
+    if actor["manuallyApprovesFollowers"].as_bool() == Some(true) {
+        account.set_locked(db_conn, true)?;
+        return Ok(Account {
+            locked: true,
+            ..account
+        });
+    }
+
+    Ok(account)
+}
+
+/// Stores the custom emoji tagged on `object` (an actor, or one of their statuses) as the
+/// emoji of `actor`'s domain, fetching their images into `MEDIA_DIR` so that they're served
+/// from here rather than from `actor`'s server.
+///
+/// Emoji are scoped by domain, so an actor can only ever define (or redefine) the emoji
+/// used by other actors on their own server. Emoji whose images can't be fetched are skipped.
+pub fn store_emojis(db_conn: &DbConnection, actor: &Account, object: &Value) -> Result<(), Error> {
+    let emojis = object["tag"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|tag| tag["type"].as_str() == Some("Emoji"));
+    let domain = actor.get_domain();
+
+    for emoji in emojis {
+        let shortcode = emoji["name"]
+            .as_str()
+            .map(|name| name.trim_matches(':'))
+            .filter(|shortcode| VALID_EMOJI_SHORTCODE_RE.is_match(shortcode));
+        let image_url = emoji["icon"]["url"]
+            .as_str()
+            .filter(|url| Url::parse(url).map_or(false, |url| url.scheme() == "https"));
+
+        let (shortcode, image_url) = match (shortcode, image_url) {
+            (Some(shortcode), Some(image_url)) => (shortcode, image_url),
+            _ => continue,
+        };
+
+        // only fetch images we haven't already.
+        let known = CustomEmoji::by_shortcode(db_conn, shortcode, domain)?;
+        let fetched_from = known
+            .as_ref()
+            .and_then(|known| known.remote_image_url.as_ref())
+            .map(String::as_str);
+        if fetched_from == Some(image_url) {
+            continue;
+        }
+
+        let path = match fetch_emoji_image(domain, shortcode, image_url) {
+            Ok(path) => path,
+            Err(e) => {
+                warn!(
+                    "couldn't fetch the image of :{}: from {}: {}",
+                    shortcode, domain, e
+                );
+                continue;
+            },
+        };
+        CustomEmoji::create_remote(
+            db_conn,
+            shortcode,
+            domain,
+            &path,
+            image_url,
+            emoji["id"].as_str(),
+        )?;
+
+        // an image of another type than before leaves the old one behind.
+        if let Some(known) = known.filter(|known| known.image != path) {
+            let _ = fs::remove_file(MEDIA_DIR.join(&known.image));
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetches the image at `url` of the emoji `shortcode` from `domain` into `MEDIA_DIR`,
+/// returning its path there.
+fn fetch_emoji_image(domain: &str, shortcode: &str, url: &str) -> Result<String, Error> {
+    // the domain is about to be part of a path, so it had better be a plain host name.
+    let is_host_name = domain
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    if !is_host_name || domain.starts_with('.') {
+        return Err(format_err!("{} isn't a host name", domain));
+    }
+
+    let response = HTTP_CLIENT.get(url).send()?.error_for_status()?;
+    let media_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase());
+    let extension = match media_type.as_ref().map(String::as_str) {
+        Some("image/png") => "png",
+        Some("image/gif") => "gif",
+        Some("image/jpeg") => "jpg",
+        Some("image/webp") => "webp",
+        _ => return Err(format_err!("{} isn't a PNG, GIF, JPEG or WebP image", url)),
+    };
+
+    let mut image = Vec::new();
+    response
+        .take(MAX_EMOJI_IMAGE_SIZE + 1)
+        .read_to_end(&mut image)?;
+    if image.len() as u64 > MAX_EMOJI_IMAGE_SIZE {
+        return Err(format_err!("{} is too big", url));
+    }
+
+    let dir = format!("emoji/remote/{}", domain);
+    fs::create_dir_all(MEDIA_DIR.join(&dir))?;
+    let path = format!("{}/{}.{}", dir, shortcode, extension);
+    fs::write(MEDIA_DIR.join(&path), &image)?;
+
+    Ok(path)
+}
@@ -0,0 +1,173 @@
+//! Handling of activities POSTed to local inboxes.
+
+use chrono::offset::Utc;
+use chrono::DateTime;
+use failure::Error;
+use serde_json::Value;
+use slog_scope::info;
+
+use super::{activities, delivery, fetch};
+use crate::db::models::{
+    Account, Block, Follow, FollowRequest, NewFollow, NewFollowRequest, NewStatus, Notification,
+    NotificationKind, Status,
+};
+use crate::db::{id_generator, DbConnection};
+use crate::transform;
+
+/// Processes `activity`, sent by `actor` to the inbox of the local account `recipient`.
+///
+/// Activities we don't understand (yet) are ignored.
+pub fn handle(
+    db_conn: &DbConnection,
+    recipient: &Account,
+    actor: &Account,
+    activity: &Value,
+) -> Result<(), Error> {
+    match activity["type"].as_str() {
+        Some("Follow") if activity["object"].as_str() == Some(&recipient.get_uri()) => {
+            handle_follow(db_conn, recipient, actor, activity)
+        },
+        Some("Create") if activity["object"]["type"].as_str() == Some("Note") => {
+            handle_note(db_conn, actor, &activity["object"])
+        },
+        Some("Like") => notify_about_status(db_conn, actor, activity, NotificationKind::Favourite),
+        Some("Announce") => notify_about_status(db_conn, actor, activity, NotificationKind::Reblog),
+        Some("Undo") if activity["object"]["type"].as_str() == Some("Follow") => {
+            // either the follow was accepted, or it's still pending; drop both.
+            Follow::delete_between(db_conn, actor.id, recipient.id)?;
+            FollowRequest::delete_between(db_conn, actor.id, recipient.id)?;
+            Ok(())
+        },
+        kind => {
+            info!("Ignoring {:?} activity from {}", kind, actor.get_uri());
+            Ok(())
+        },
+    }
+}
+
+/// Follows of locked accounts are queued up for approval, follows across a block are rejected,
+/// and anything else is accepted straight away.
+fn handle_follow(
+    db_conn: &DbConnection,
+    recipient: &Account,
+    actor: &Account,
+    activity: &Value,
+) -> Result<(), Error> {
+    if Block::exists_between(db_conn, recipient.id, actor.id)? {
+        return delivery::deliver(
+            &activities::reject_follow(activity.clone(), recipient),
+            recipient,
+            &actor.get_inbox_endpoint(),
+        );
+    }
+
+    if recipient.locked {
+        NewFollowRequest {
+            id: id_generator().next(),
+            source_id: actor.id,
+            target_id: recipient.id,
+            uri: activity["id"].as_str().map(String::from),
+        }
+        .insert(db_conn)?;
+        Notification::notify(
+            db_conn,
+            recipient,
+            actor,
+            NotificationKind::FollowRequest,
+            None,
+        )?;
+
+        return Ok(());
+    }
+
+    NewFollow {
+        id: id_generator().next(),
+        source_id: actor.id,
+        target_id: recipient.id,
+    }
+    .insert(db_conn)?;
+    Notification::notify(db_conn, recipient, actor, NotificationKind::Follow, None)?;
+
+    delivery::deliver(
+        &activities::accept_follow(activity.clone(), recipient),
+        recipient,
+        &actor.get_inbox_endpoint(),
+    )
+}
+
+/// Stores a `Note` authored by `actor`, notifying any local accounts it mentions.
+fn handle_note(db_conn: &DbConnection, actor: &Account, note: &Value) -> Result<(), Error> {
+    let uri = match note["id"].as_str() {
+        Some(uri) => uri,
+        None => return Ok(()),
+    };
+
+    // nobody gets to put words in somebody else's mouth, and we only need each note once.
+    if note["attributedTo"].as_str() != actor.uri.as_ref().map(String::as_str)
+        || Status::fetch_by_uri(db_conn, uri)?.is_some()
+    {
+        return Ok(());
+    }
+
+    // the emoji go first, so they're there to be shown as soon as the status is.
+    fetch::store_emojis(db_conn, actor, note)?;
+
+    let status = NewStatus {
+        id: id_generator().next(),
+        text: transform::html_to_text(note["content"].as_str().unwrap_or("")),
+        content_warning: note["summary"]
+            .as_str()
+            .filter(|summary| !summary.is_empty())
+            .map(String::from),
+        created_at: note["published"]
+            .as_str()
+            .and_then(|published| DateTime::parse_from_rfc3339(published).ok())
+            .map(|published| published.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now),
+        account_id: actor.id,
+        uri: Some(uri.to_string()),
+    }
+    .insert(db_conn)?;
+
+    let mentioned = note["tag"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|tag| tag["type"].as_str() == Some("Mention"))
+        .filter_map(|tag| tag["href"].as_str());
+    for href in mentioned {
+        if let Some(account) = Account::fetch_local_by_uri(db_conn, href)? {
+            Notification::notify(
+                db_conn,
+                &account,
+                actor,
+                NotificationKind::Mention,
+                Some(status.id),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Notifies the author of the local status an activity (a `Like` or an `Announce`) is about.
+fn notify_about_status(
+    db_conn: &DbConnection,
+    actor: &Account,
+    activity: &Value,
+    kind: NotificationKind,
+) -> Result<(), Error> {
+    // the object may be embedded, or just referenced by its id
+    let object = &activity["object"];
+    let uri = match object.as_str().or_else(|| object["id"].as_str()) {
+        Some(uri) => uri,
+        None => return Ok(()),
+    };
+
+    if let Some(status) = Status::fetch_by_uri(db_conn, uri)? {
+        let author = status.account(db_conn)?;
+        Notification::notify(db_conn, &author, actor, kind, Some(status.id))?;
+    }
+
+    Ok(())
+}
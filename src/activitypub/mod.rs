@@ -1,14 +1,35 @@
 use crate::crypto::HasPublicKey;
 use crate::db;
-use crate::db::models::{Account, Status};
-use crate::routes::ui::view_helpers::HasBio;
+use crate::db::models::{Account, CustomEmoji, Status};
+use crate::routes::ui::view_helpers::{HasBio, HasRenderedContent};
+use crate::transform;
 use failure::Error;
+use lazy_static::lazy_static;
+use reqwest::Client;
 use rocket::http::{self, Accept, ContentType, MediaType};
 use rocket::request::{self, FromRequest, Request};
 use rocket::response::{self, Content, Responder};
 use serde::Serialize;
 use serde_json::{json, Value};
 use slog_scope::error;
+use std::time::Duration;
+
+pub mod activities;
+pub mod delivery;
+pub mod fetch;
+pub mod inbox;
+pub mod signatures;
+
+/// How long requests to other servers may take before we give up on them.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    /// The client for requests to other servers, which gives up after [`HTTP_TIMEOUT`].
+    static ref HTTP_CLIENT: Client = Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .expect("Couldn't build an HTTP client!");
+}
 
 /// Newtype for JSON which represents JSON-LD ActivityStreams2 objects.
 ///
@@ -80,8 +101,32 @@ impl AsActivityPub for Account {
         &self,
         conn: &db::DbConnection,
     ) -> Result<ActivityStreams<serde_json::Value>, Error> {
+        // suspended actors only expose enough to be identified (and to verify their
+        // signatures), flagged with Mastodon's `suspended` extension.
+        if self.suspended {
+            return Ok(ActivityStreams(json!({
+                "@context": ["https://www.w3.org/ns/activitystreams", {"suspended": "toot:suspended", "toot": "http://joinmastodon.org/ns#"}],
+                "type": "Person",
+                "id": self.get_uri(),
+
+                "inbox": self.get_inbox_endpoint(),
+                "outbox": self.get_outbox_endpoint(),
+
+                "preferredUsername": self.username,
+                "name": "",
+                "summary": "",
+                "suspended": true,
+
+                "publicKey": {
+                    "id": format!("{}#main-key", self.get_uri()),
+                    "owner": self.get_uri(),
+                    "publicKeyPem": self.public_key_pem()?,
+                }
+            })));
+        }
+
         Ok(ActivityStreams(json!({
-            "@context": "https://www.w3.org/ns/activitystreams",
+            "@context": ["https://www.w3.org/ns/activitystreams", {"manuallyApprovesFollowers": "as:manuallyApprovesFollowers"}],
             "type": "Person",
             "id": self.get_uri(),
 
@@ -94,6 +139,7 @@ impl AsActivityPub for Account {
             "preferredUsername": self.username,
             "name": self.display_name.as_ref().map(String::as_str).unwrap_or(""),
             "summary": self.transformed_bio(&conn).as_ref().map(String::as_str).unwrap_or("<p></p>"),
+            "manuallyApprovesFollowers": self.locked,
 
             "publicKey": {
                 "id": format!("{}#main-key", self.get_uri()),
@@ -110,23 +156,57 @@ impl AsActivityPub for Status {
         conn: &db::DbConnection,
     ) -> Result<ActivityStreams<serde_json::Value>, Error> {
         let account = self.account(conn)?;
+        let emojis =
+            CustomEmoji::all_for_account(conn, &transform::emoji_shortcodes(&self.text), &account)?;
+
         Ok(ActivityStreams(json!({
-            "@context": ["https://www.w3.org/ns/activitystreams", {"sensitive": "as:sensitive"}],
+            "@context": ["https://www.w3.org/ns/activitystreams", {"sensitive": "as:sensitive", "toot": "http://joinmastodon.org/ns#", "Emoji": "toot:Emoji"}],
             "type": "Note",
             "id": self.get_uri(conn)?,
             "attributedTo": account.get_uri(),
 
-            "content": self.text,
+            "content": self.rendered_content(conn),
             "summary": self.content_warning,
-            "sensitive": self.content_warning.is_some(),
+            "sensitive": self.content_warning.is_some() || account.force_sensitive,
             "published": self.created_at.to_rfc3339(),
 
             "to": ["https://www.w3.org/ns/activitystreams#Public"],
             "cc": [account.get_followers_endpoint()],
+            "tag": emojis.iter().map(emoji_tag).collect::<Vec<_>>(),
         })))
     }
 }
 
+/// Returns the `Emoji` object for `emoji`, as it's tagged on the objects using it.
+fn emoji_tag(emoji: &CustomEmoji) -> Value {
+    json!({
+        "id": emoji.get_uri(),
+        "type": "Emoji",
+        "name": format!(":{}:", emoji.shortcode),
+        "updated": emoji.updated_at.to_rfc3339(),
+        "icon": {
+            "type": "Image",
+            "mediaType": emoji.media_type(),
+            "url": emoji.image_url(),
+        },
+    })
+}
+
+impl AsActivityPub for CustomEmoji {
+    fn as_activitypub(
+        &self,
+        _conn: &db::DbConnection,
+    ) -> Result<ActivityStreams<serde_json::Value>, Error> {
+        let mut emoji = emoji_tag(self);
+        emoji["@context"] = json!([
+            "https://www.w3.org/ns/activitystreams",
+            {"toot": "http://joinmastodon.org/ns#", "Emoji": "toot:Emoji"}
+        ]);
+
+        Ok(ActivityStreams(emoji))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,238 @@
+//! HTTP Signatures (draft-cavage-http-signatures), as used to authenticate
+//! server-to-server ActivityPub requests.
+
+use chrono::{DateTime, Duration, Utc};
+use failure::{format_err, Error};
+use openssl::base64;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::sha::sha256;
+use openssl::sign::{Signer, Verifier};
+use rocket::http::{Method, Status};
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+
+use crate::db::models::Account;
+
+/// Returns the value of a `Digest` header for `body`.
+pub fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", base64::encode_block(&sha256(body)))
+}
+
+/// Signs the given `(name, value)` header pairs with `account`'s private key, returning
+/// the value of a `Signature` header.
+///
+/// `headers` should include the `(request-target)` pseudo-header.
+pub fn sign(account: &Account, headers: &[(&str, &str)]) -> Result<String, Error> {
+    let privkey = account
+        .privkey
+        .as_ref()
+        .ok_or_else(|| format_err!("{} has no private key", account.get_uri()))?;
+    let pkey = PKey::from_rsa(Rsa::private_key_from_der(privkey)?)?;
+
+    let signing_string = headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let header_names = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut signer = Signer::new(MessageDigest::sha256(), &pkey)?;
+    signer.update(signing_string.as_bytes())?;
+    let signature = signer.sign_to_vec()?;
+
+    Ok(format!(
+        r#"keyId="{uri}#main-key",algorithm="rsa-sha256",headers="{headers}",signature="{signature}""#,
+        uri = account.get_uri(),
+        headers = header_names,
+        signature = base64::encode_block(&signature)
+    ))
+}
+
+/// How far the signed `Date` of a request may be from our clock, in hours, before we refuse
+/// it; otherwise a captured request could be replayed forever.
+const MAX_CLOCK_SKEW_HOURS: i64 = 12;
+
+/// Returns whether `names`, the lowercased names of the headers which were signed, include
+/// the ones a `method` request has to sign: `date`, and for a POST, `digest` and
+/// `(request-target)` too, so a delivery can't be replayed to another inbox.
+fn signs_required_headers(method: Method, names: &[String]) -> bool {
+    let signed = |name: &str| names.iter().any(|n| n == name);
+
+    signed("date") && (method != Method::Post || signed("digest") && signed("(request-target)"))
+}
+
+/// Returns whether the HTTP date `value` is within [`MAX_CLOCK_SKEW_HOURS`] of `now`.
+fn is_recent(value: &str, now: DateTime<Utc>) -> bool {
+    match DateTime::parse_from_rfc2822(value) {
+        Ok(date) => {
+            let skew = now.signed_duration_since(date.with_timezone(&Utc));
+            let limit = Duration::hours(MAX_CLOCK_SKEW_HOURS);
+
+            -limit <= skew && skew <= limit
+        },
+        Err(_) => false,
+    }
+}
+
+/// A Rocket guard for requests bearing a `Signature` header.
+///
+/// Requests have to sign a `Date` close to ours, and POSTs their `Digest` and target too.
+///
+/// Only parses the header and reconstructs the signed string; the signature still has to be
+/// checked against the signing actor's key with [`SignedRequest::verify`], and the body
+/// against [`SignedRequest::digest`].
+///
+/// [`SignedRequest::verify`]: #method.verify
+/// [`SignedRequest::digest`]: #structfield.digest
+#[derive(Debug)]
+pub struct SignedRequest {
+    /// The ID of the key which signed the request, usually `<actor uri>#main-key`.
+    pub key_id: String,
+    /// The value of the `Digest` header, if it was signed.
+    pub digest: Option<String>,
+    signing_string: String,
+    signature: Vec<u8>,
+}
+
+impl SignedRequest {
+    /// Returns the URI of the actor owning the signing key (ie, `key_id` sans fragment).
+    pub fn actor_uri(&self) -> &str {
+        self.key_id.split('#').next().unwrap_or("")
+    }
+
+    /// Returns whether the request was signed by `account`'s key.
+    pub fn verify(&self, account: &Account) -> Result<bool, Error> {
+        let pkey = PKey::from_rsa(Rsa::public_key_from_der(&account.pubkey)?)?;
+
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &pkey)?;
+        verifier.update(self.signing_string.as_bytes())?;
+
+        Ok(verifier.verify(&self.signature)?)
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for SignedRequest {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<SignedRequest, ()> {
+        let header = match request.headers().get_one("Signature") {
+            Some(header) => header,
+            None => return Outcome::Failure((Status::Unauthorized, ())),
+        };
+
+        // parse `key="value",key="value"` pairs into the parameters we care about
+        let (mut key_id, mut header_names, mut signature) = (None, None, None);
+        for param in header.split(',') {
+            let mut split = param.splitn(2, '=');
+            let key = split.next().unwrap_or("").trim();
+            let value = split.next().unwrap_or("").trim().trim_matches('"');
+
+            match key {
+                "keyId" => key_id = Some(value),
+                "headers" => header_names = Some(value),
+                "signature" => signature = base64::decode_block(value).ok(),
+                _ => {},
+            }
+        }
+
+        let (key_id, signature) = match (key_id, signature) {
+            (Some(key_id), Some(signature)) => (key_id.to_string(), signature),
+            _ => return Outcome::Failure((Status::BadRequest, ())),
+        };
+
+        let header_names = header_names
+            .unwrap_or("date")
+            .split(' ')
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        if !signs_required_headers(request.method(), &header_names) {
+            return Outcome::Failure((Status::BadRequest, ()));
+        }
+
+        let mut lines = Vec::new();
+        let mut digest = None;
+        for name in header_names {
+            let value = if name == "(request-target)" {
+                format!(
+                    "{} {}",
+                    request.method().as_str().to_lowercase(),
+                    request.uri()
+                )
+            } else {
+                let values = request.headers().get(&name).collect::<Vec<_>>();
+                if values.is_empty() {
+                    return Outcome::Failure((Status::BadRequest, ()));
+                }
+                values.join(", ")
+            };
+
+            if name == "date" && !is_recent(&value, Utc::now()) {
+                return Outcome::Failure((Status::Unauthorized, ()));
+            }
+            if name == "digest" {
+                digest = Some(value.clone());
+            }
+            lines.push(format!("{}: {}", name, value));
+        }
+
+        Outcome::Success(SignedRequest {
+            key_id,
+            digest,
+            signing_string: lines.join("\n"),
+            signature,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_computes_digests() {
+        assert_eq!(
+            digest(b"hello"),
+            "SHA-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ="
+        );
+    }
+
+    #[test]
+    fn it_requires_date_digest_and_target_to_be_signed() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert!(signs_required_headers(Method::Get, &names(&["date"])));
+        assert!(!signs_required_headers(
+            Method::Get,
+            &names(&["(request-target)", "host"])
+        ));
+        assert!(signs_required_headers(
+            Method::Post,
+            &names(&["(request-target)", "date", "digest"])
+        ));
+        assert!(!signs_required_headers(Method::Post, &names(&["date"])));
+        assert!(!signs_required_headers(
+            Method::Post,
+            &names(&["date", "digest"])
+        ));
+    }
+
+    #[test]
+    fn it_rejects_dates_too_far_from_now() {
+        let now = DateTime::parse_from_rfc3339("2020-04-25T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert!(is_recent("Sat, 25 Apr 2020 12:00:00 GMT", now));
+        assert!(is_recent("Sat, 25 Apr 2020 01:00:00 GMT", now));
+        assert!(is_recent("Sat, 25 Apr 2020 23:00:00 GMT", now));
+        assert!(!is_recent("Fri, 24 Apr 2020 12:00:00 GMT", now));
+        assert!(!is_recent("Sun, 26 Apr 2020 12:00:00 GMT", now));
+        assert!(!is_recent("yesterday", now));
+    }
+}
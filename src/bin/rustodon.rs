@@ -2,7 +2,7 @@ use dotenv::dotenv;
 use slog::slog_o;
 use std::env;
 
-use rustodon::{app, db, init_logger};
+use rustodon::{app, db, init_logger, mailer, streaming};
 
 fn main() {
     // load environment variables fron .env
@@ -18,6 +18,14 @@ fn main() {
     let db_connection_pool =
         db::init_connection_pool(db_url).expect("Couldn't establish connection to database!");
 
-    let app = app(db_connection_pool, rocket_logger);
+    let mailer = mailer::from_env().expect("Couldn't set up the mailer!");
+
+    // the streaming API runs next to Rocket, on its own port.
+    let streaming_bind =
+        env::var("STREAMING_BIND").unwrap_or_else(|_| streaming::DEFAULT_BIND.to_string());
+    streaming::spawn(db_connection_pool.clone(), &streaming_bind)
+        .expect("Couldn't start the streaming API!");
+
+    let app = app(db_connection_pool, mailer, rocket_logger);
     app.launch();
 }
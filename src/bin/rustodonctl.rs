@@ -1,8 +1,12 @@
 use dotenv::dotenv;
 use std::env;
+use std::fs;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 use rustodon::db;
+use rustodon::db::models::{CustomEmoji, Role};
+use rustodon::MEDIA_DIR;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "rustodonctl")]
@@ -17,6 +21,33 @@ enum Command {
     /// Should be run after db migrations complete when upgrading from pre-HTTP-signatures Rustodon versions.
     #[structopt(name = "generate-keys")]
     GenerateKeys,
+
+    /// Records the hashtags of every status, so they show up in hashtag timelines.
+    /// Should be run after db migrations complete when upgrading from Rustodon versions
+    /// which searched the text of statuses for hashtags.
+    #[structopt(name = "index-hashtags")]
+    IndexHashtags,
+
+    /// Changes the role (one of `user`, `moderator` or `admin`) of a local user.
+    #[structopt(name = "set-role")]
+    SetRole { username: String, role: Role },
+
+    /// Turns off two-factor authentication for a local user who's locked out of their account.
+    #[structopt(name = "disable-2fa")]
+    Disable2fa { username: String },
+
+    /// Adds a custom emoji, shown in place of `:shortcode:`, from a PNG, GIF, JPEG or WebP
+    /// image. An existing emoji with the same shortcode is replaced.
+    #[structopt(name = "add-emoji")]
+    AddEmoji {
+        shortcode: String,
+        #[structopt(parse(from_os_str))]
+        image: PathBuf,
+    },
+
+    /// Removes a custom emoji.
+    #[structopt(name = "remove-emoji")]
+    RemoveEmoji { shortcode: String },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -55,6 +86,95 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("done!");
             }
         },
+        Command::IndexHashtags => {
+            use diesel::prelude::*;
+            use rustodon::db::models::Status;
+            use rustodon::db::schema::statuses;
+
+            let mut last_id = None;
+            let mut indexed = 0;
+            loop {
+                let mut query = statuses::table.order(statuses::id.asc()).into_boxed();
+                if let Some(last_id) = last_id {
+                    query = query.filter(statuses::id.gt(last_id));
+                }
+                let batch = query.limit(1000).load::<Status>(&db_conn)?;
+                if batch.is_empty() {
+                    break;
+                }
+
+                for status in &batch {
+                    status.index_hashtags(&db_conn)?;
+                }
+                indexed += batch.len();
+                last_id = batch.last().map(|status| status.id);
+                println!("indexed {} statuses...", indexed);
+            }
+
+            println!("done!");
+        },
+        Command::SetRole { username, role } => {
+            use rustodon::db::models::User;
+
+            match User::by_username(&db_conn, &username)? {
+                Some(user) => {
+                    user.set_role(&db_conn, role)?;
+                    println!("{} is now a(n) {}", username, role);
+                },
+                None => println!("no local user named {}", username),
+            }
+        },
+        Command::Disable2fa { username } => {
+            use rustodon::db::models::User;
+
+            match User::by_username(&db_conn, &username)? {
+                Some(user) => {
+                    user.disable_two_factor(&db_conn)?;
+                    println!("two-factor authentication is now off for {}", username);
+                },
+                None => println!("no local user named {}", username),
+            }
+        },
+        Command::AddEmoji { shortcode, image } => {
+            use rustodon::db::validators::VALID_EMOJI_SHORTCODE_RE;
+
+            if !VALID_EMOJI_SHORTCODE_RE.is_match(&shortcode) {
+                println!("shortcodes must be at least 2 letters, digits or underscores");
+                return Ok(());
+            }
+
+            let extension = image
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(str::to_lowercase);
+            let extension = match extension.as_ref().map(String::as_str) {
+                Some(extension @ "png") | Some(extension @ "gif") | Some(extension @ "webp") => {
+                    extension
+                },
+                Some("jpg") | Some("jpeg") => "jpg",
+                _ => {
+                    println!("emoji must be PNG, GIF, JPEG or WebP images");
+                    return Ok(());
+                },
+            };
+
+            let path = format!("emoji/{}.{}", shortcode, extension);
+            fs::create_dir_all(MEDIA_DIR.join("emoji"))?;
+            fs::copy(&image, MEDIA_DIR.join(&path))?;
+
+            CustomEmoji::create_local(&db_conn, &shortcode, &path)?;
+            println!(":{}: is now a custom emoji", shortcode);
+        },
+        Command::RemoveEmoji { shortcode } => {
+            match CustomEmoji::delete_local(&db_conn, &shortcode)? {
+                Some(emoji) => {
+                    // the emoji's gone either way, so a missing image doesn't matter.
+                    let _ = fs::remove_file(MEDIA_DIR.join(&emoji.image));
+                    println!("removed :{}:", shortcode);
+                },
+                None => println!("no custom emoji named :{}:", shortcode),
+            }
+        },
     }
 
     Ok(())
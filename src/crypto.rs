@@ -1,6 +1,9 @@
 use crate::db::models::Account;
 use failure::Error;
+use openssl::memcmp;
+use openssl::rand::rand_bytes;
 use openssl::rsa::Rsa;
+use openssl::sha::sha256;
 
 const KEYSIZE: u32 = 4096;
 
@@ -17,6 +20,33 @@ pub fn generate_keypair() -> Result<DERKeypair, Error> {
     })
 }
 
+/// Number of random bytes in a token from [`random_token`](fn.random_token.html).
+const TOKEN_BYTES: usize = 32;
+
+/// Returns a fresh, unguessable token (base32-encoded random bytes), for use as eg a session ID.
+pub fn random_token() -> Result<String, Error> {
+    let mut bytes = [0; TOKEN_BYTES];
+    rand_bytes(&mut bytes)?;
+
+    Ok(base32::encode(base32::Alphabet::Crockford, &bytes))
+}
+
+/// Returns the hash of a token from [`random_token`](fn.random_token.html), for storing in
+/// place of the token itself; so somebody who can read the database can't use it.
+pub fn hash_token(token: &str) -> String {
+    sha256(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Returns whether `token` hashes to `hash`, taking the same time whatever either of them is.
+pub fn token_matches(token: &str, hash: &str) -> bool {
+    let token_hash = hash_token(token);
+
+    token_hash.len() == hash.len() && memcmp::eq(token_hash.as_bytes(), hash.as_bytes())
+}
+
 pub trait HasPublicKey {
     fn public_key_pem(&self) -> Result<String, Error>;
 }
@@ -28,3 +58,19 @@ impl HasPublicKey for Account {
         )?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_tokens_to_their_hashes() {
+        let token = random_token().unwrap();
+        let hash = hash_token(&token);
+
+        assert_ne!(hash, token);
+        assert!(token_matches(&token, &hash));
+        assert!(!token_matches(&random_token().unwrap(), &hash));
+        assert!(!token_matches(&token, ""));
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::offset::Utc;
+use chrono::DateTime;
+use diesel;
+use diesel::prelude::*;
+use failure::Error;
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use std::marker::PhantomData;
+
+use super::{Application, User};
+use crate::crypto;
+use crate::db::schema::oauth_access_tokens;
+use crate::db::{self, id_generator, DbConnection};
+use crate::oauth::{Scope, Scopes};
+
+/// Represents a bearer token a client uses to call the API, either as a user or (if
+/// `user_id` is `None`) just as itself. Only a hash of the token is stored.
+#[derive(Identifiable, Queryable, Associations, Clone, PartialEq, Debug)]
+#[belongs_to(Application)]
+#[belongs_to(User)]
+#[table_name = "oauth_access_tokens"]
+pub struct AccessToken {
+    pub id: i64,
+    pub token_hash: String,
+    pub application_id: i64,
+    pub user_id: Option<i64>,
+    pub scopes: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a new access token for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "oauth_access_tokens"]
+pub struct NewAccessToken {
+    pub id: i64,
+    pub token_hash: String,
+    pub application_id: i64,
+    pub user_id: Option<i64>,
+    pub scopes: String,
+}
+
+impl AccessToken {
+    /// Issues a token letting `application` act as the user `uid` (or as itself) with `scopes`,
+    /// returning it along with the token itself; the only time that's available.
+    pub fn create(
+        db_conn: &DbConnection,
+        application: &Application,
+        uid: Option<i64>,
+        scopes: &Scopes,
+    ) -> Result<(AccessToken, String), Error> {
+        let token = crypto::random_token()?;
+        let new_token = NewAccessToken {
+            id: id_generator().next(),
+            token_hash: crypto::hash_token(&token),
+            application_id: application.id,
+            user_id: uid,
+            scopes: scopes.to_string(),
+        };
+
+        let access_token = diesel::insert_into(oauth_access_tokens::table)
+            .values(&new_token)
+            .get_result(db_conn)?;
+
+        Ok((access_token, token))
+    }
+
+    /// Finds an access token by the token itself.
+    pub fn by_token(db_conn: &DbConnection, candidate: &str) -> QueryResult<Option<AccessToken>> {
+        use crate::db::schema::oauth_access_tokens::dsl::*;
+
+        oauth_access_tokens
+            .filter(token_hash.eq(crypto::hash_token(candidate)))
+            .first(db_conn)
+            .optional()
+    }
+
+    /// Revokes the token `candidate` of the application `app_id`, if there is one.
+    pub fn revoke(db_conn: &DbConnection, app_id: i64, candidate: &str) -> QueryResult<()> {
+        use crate::db::schema::oauth_access_tokens::dsl::*;
+
+        diesel::delete(
+            oauth_access_tokens
+                .filter(token_hash.eq(crypto::hash_token(candidate)))
+                .filter(application_id.eq(app_id)),
+        )
+        .execute(db_conn)
+        .and(Ok(()))
+    }
+
+    /// Returns the scopes this token grants.
+    pub fn scopes(&self) -> Scopes {
+        Scopes::from(self.scopes.as_str())
+    }
+}
+
+/// Reads the token from an `Authorization: Bearer` header.
+///
+/// Forwards if there's no such header, and fails with `Unauthorized` if the token isn't one
+/// we issued (or has been revoked).
+impl<'a, 'r> FromRequest<'a, 'r> for AccessToken {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<AccessToken, ()> {
+        let candidate = match request.headers().get_one("Authorization") {
+            Some(header) if header.starts_with("Bearer ") => header["Bearer ".len()..].trim(),
+            _ => return Outcome::Forward(()),
+        };
+
+        let db_conn = request.guard::<db::Connection>()?;
+        match AccessToken::by_token(&db_conn, candidate) {
+            Ok(Some(token)) => Outcome::Success(token),
+            Ok(None) => Outcome::Failure((Status::Unauthorized, ())),
+            Err(_) => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// A [`Scope`] which a [`RequireScope`] guard will check for.
+pub trait ScopeRequirement {
+    const SCOPE: Scope;
+}
+
+/// Marker for [`RequireScope`]; needs the `read` scope.
+pub enum ReadScope {}
+
+impl ScopeRequirement for ReadScope {
+    const SCOPE: Scope = Scope::Read;
+}
+
+/// Marker for [`RequireScope`]; needs the `write` scope.
+pub enum WriteScope {}
+
+impl ScopeRequirement for WriteScope {
+    const SCOPE: Scope = Scope::Write;
+}
+
+/// Request guard for the [`User`] an API client is acting as, through a bearer token which
+/// grants `S::SCOPE`.
+///
+/// Fails with `Unauthorized` for tokens not issued to a user, and with `Forbidden` for tokens
+/// without the scope, or whose user has been suspended.
+pub struct RequireScope<S: ScopeRequirement>(pub User, PhantomData<S>);
+
+/// Request guard for a user whose client may read their data.
+pub type CanRead = RequireScope<ReadScope>;
+
+/// Request guard for a user whose client may post and change their data.
+pub type CanWrite = RequireScope<WriteScope>;
+
+impl<'a, 'r, S: ScopeRequirement> FromRequest<'a, 'r> for RequireScope<S> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<RequireScope<S>, ()> {
+        let token = request.guard::<AccessToken>()?;
+        if !token.scopes().contains(S::SCOPE) {
+            return Outcome::Failure((Status::Forbidden, ()));
+        }
+
+        let uid = match token.user_id {
+            Some(uid) => uid,
+            None => return Outcome::Failure((Status::Unauthorized, ())),
+        };
+
+        let db_conn = request.guard::<db::Connection>()?;
+        let user = match User::by_id(&db_conn, uid) {
+            Ok(Some(user)) => user,
+            Ok(None) => return Outcome::Failure((Status::Unauthorized, ())),
+            Err(_) => return Outcome::Failure((Status::InternalServerError, ())),
+        };
+
+        match user.is_suspended(&db_conn) {
+            Ok(false) => Outcome::Success(RequireScope(user, PhantomData)),
+            Ok(true) => Outcome::Failure((Status::Forbidden, ())),
+            Err(_) => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
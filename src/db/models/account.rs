@@ -1,13 +1,18 @@
+use chrono::offset::Utc;
+use chrono::DateTime;
 use diesel;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::sql_types::BigInt;
 use openssl::pkey::Private;
 use openssl::rsa::Rsa;
 use std::borrow::Cow;
+use std::collections::HashMap;
 
 use crate::db::{self, DbConnection, LOCAL_ACCOUNT_DOMAIN};
 use crate::{BASE_URL, DOMAIN};
 
-use super::{Status, User};
+use super::{Page, Status, User};
 use crate::crypto::DERKeypair;
 use rocket::request::{self, FromRequest, Request};
 
@@ -19,7 +24,7 @@ use crate::db::schema::accounts;
 ///
 /// Uniqueness is enforced both on the username/domain pair and on the uri.
 
-#[derive(Identifiable, Queryable, Debug, PartialEq)]
+#[derive(Identifiable, Queryable, Clone, Debug, PartialEq)]
 #[table_name = "accounts"]
 pub struct Account {
     pub id: i64,
@@ -32,6 +37,67 @@ pub struct Account {
 
     pub pubkey:  Vec<u8>,
     pub privkey: Option<Vec<u8>>,
+
+    pub silenced: bool,
+    pub suspended: bool,
+    pub force_sensitive: bool,
+
+    pub locked:    bool,
+    pub inbox_uri: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+/// How many statuses, followers and followed accounts an account has.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct AccountCounts {
+    pub statuses:  i64,
+    pub followers: i64,
+    pub following: i64,
+}
+
+impl AccountCounts {
+    /// Counts for each of the accounts `ids`, in three queries however many there are.
+    /// Accounts with nothing to count are left out.
+    pub fn for_accounts(
+        db_conn: &DbConnection,
+        ids: &[i64],
+    ) -> QueryResult<HashMap<i64, AccountCounts>> {
+        use crate::db::schema::{follows, statuses};
+        use diesel::dsl::sql;
+
+        // diesel won't group by a column while selecting an aggregate, so count in SQL.
+        let count_star = || sql::<BigInt>("count(*)");
+
+        let statuses_counts: Vec<(i64, i64)> = statuses::table
+            .filter(statuses::account_id.eq_any(ids))
+            .group_by(statuses::account_id)
+            .select((statuses::account_id, count_star()))
+            .load(db_conn)?;
+        let followers_counts: Vec<(i64, i64)> = follows::table
+            .filter(follows::target_id.eq_any(ids))
+            .group_by(follows::target_id)
+            .select((follows::target_id, count_star()))
+            .load(db_conn)?;
+        let following_counts: Vec<(i64, i64)> = follows::table
+            .filter(follows::source_id.eq_any(ids))
+            .group_by(follows::source_id)
+            .select((follows::source_id, count_star()))
+            .load(db_conn)?;
+
+        let mut counts = HashMap::<i64, AccountCounts>::new();
+        for (id, count) in statuses_counts {
+            counts.entry(id).or_default().statuses = count;
+        }
+        for (id, count) in followers_counts {
+            counts.entry(id).or_default().followers = count;
+        }
+        for (id, count) in following_counts {
+            counts.entry(id).or_default().following = count;
+        }
+
+        Ok(counts)
+    }
 }
 
 /// Represents a new account for insertion into the database.
@@ -49,13 +115,21 @@ pub struct NewAccount {
 
     pub pubkey:  Vec<u8>,
     pub privkey: Option<Vec<u8>>,
+
+    pub inbox_uri: Option<String>,
 }
 
 impl NewAccount {
+    /// Inserts the account, forgetting the cached HTML of statuses which might mention it
+    /// so that the mentions link to it.
     pub fn insert(self, conn: &DbConnection) -> QueryResult<Account> {
         use crate::db::schema::accounts::dsl::*;
 
-        diesel::insert_into(accounts).values(&self).get_result(conn)
+        let account: Account = diesel::insert_into(accounts)
+            .values(&self)
+            .get_result(conn)?;
+        Status::forget_content_html_mentioning(conn, &account.username)?;
+        Ok(account)
     }
 }
 
@@ -104,6 +178,13 @@ impl Account {
         )
     }
 
+    /// Returns true if this account lives on this instance.
+    pub fn is_local(&self) -> bool {
+        self.domain
+            .as_ref()
+            .map_or(true, |domain| domain == LOCAL_ACCOUNT_DOMAIN)
+    }
+
     /// Returns the domain on which an account resides.
     pub fn get_domain(&self) -> &str {
         if let Some(domain_str) = self.domain.as_ref() {
@@ -144,7 +225,7 @@ impl Account {
 
     /// Returns the URI of the ActivityPub `inbox` endpoint for this account.
     pub fn get_inbox_endpoint(&self) -> Cow<'_, str> {
-        self.uri
+        self.inbox_uri
             .as_ref()
             .map(|x| String::as_str(x).into())
             .unwrap_or_else(|| {
@@ -262,6 +343,42 @@ impl Account {
             })
     }
 
+    /// Returns the page `page` of statuses authored by this account.
+    pub fn statuses_page(&self, db_conn: &DbConnection, page: &Page) -> QueryResult<Vec<Status>> {
+        use crate::db::schema::statuses::dsl::*;
+        page.load(
+            db_conn,
+            statuses.filter(account_id.eq(self.id)).into_boxed(),
+        )
+    }
+
+    /// Returns the number of statuses authored by this account.
+    pub fn statuses_count(&self, db_conn: &DbConnection) -> QueryResult<i64> {
+        use crate::db::schema::statuses::dsl::*;
+        statuses
+            .filter(account_id.eq(self.id))
+            .count()
+            .get_result(db_conn)
+    }
+
+    /// Returns the number of accounts following this one.
+    pub fn followers_count(&self, db_conn: &DbConnection) -> QueryResult<i64> {
+        use crate::db::schema::follows::dsl::*;
+        follows
+            .filter(target_id.eq(self.id))
+            .count()
+            .get_result(db_conn)
+    }
+
+    /// Returns the number of accounts this one follows.
+    pub fn following_count(&self, db_conn: &DbConnection) -> QueryResult<i64> {
+        use crate::db::schema::follows::dsl::*;
+        follows
+            .filter(source_id.eq(self.id))
+            .count()
+            .get_result(db_conn)
+    }
+
     pub fn set_summary(
         &self,
         db_conn: &DbConnection,
@@ -275,6 +392,145 @@ impl Account {
             .and(Ok(()))
     }
 
+    /// Returns `n` accounts (local _or_ remote) with IDs _strictly less than_ `max_id`,
+    /// newest first. Used to page through accounts for moderation.
+    pub fn before_id(
+        db_conn: &DbConnection,
+        max_id: Option<i64>,
+        n: usize,
+    ) -> QueryResult<Vec<Account>> {
+        use crate::db::schema::accounts::dsl;
+
+        let mut query = dsl::accounts.into_boxed();
+
+        if let Some(max_id) = max_id {
+            query = query.filter(dsl::id.lt(max_id));
+        }
+
+        query
+            .order(dsl::id.desc())
+            .limit(n as i64)
+            .get_results::<Account>(db_conn)
+    }
+
+    /// Finds an account (local _or_ remote) by its ID.
+    pub fn by_id(db_conn: &DbConnection, account_id: i64) -> QueryResult<Option<Account>> {
+        use crate::db::schema::accounts::dsl::accounts;
+
+        accounts.find(account_id).first(db_conn).optional()
+    }
+
+    /// Finds the accounts (local _or_ remote) with any of the IDs `ids`, in no particular order.
+    pub fn by_ids(db_conn: &DbConnection, ids: &[i64]) -> QueryResult<Vec<Account>> {
+        use crate::db::schema::accounts::dsl::*;
+
+        accounts.filter(id.eq_any(ids)).load(db_conn)
+    }
+
+    /// Returns the accounts, local and remote, with any of `usernames`.
+    pub fn by_usernames(db_conn: &DbConnection, usernames: &[String]) -> QueryResult<Vec<Account>> {
+        use crate::db::schema::accounts::dsl::*;
+
+        accounts.filter(username.eq_any(usernames)).load(db_conn)
+    }
+
+    /// Returns a query selecting the IDs of all accounts whose statuses should be kept
+    /// out of the public timelines (ie, silenced or suspended accounts).
+    pub fn hidden_from_timelines() -> accounts::BoxedQuery<'static, Pg, BigInt> {
+        use crate::db::schema::accounts::dsl::*;
+
+        accounts
+            .filter(silenced.or(suspended))
+            .select(id)
+            .into_boxed()
+    }
+
+    /// Returns a query selecting the IDs of all suspended accounts, whose statuses are kept
+    /// out of every timeline.
+    pub fn suspended_ids() -> accounts::BoxedQuery<'static, Pg, BigInt> {
+        use crate::db::schema::accounts::dsl::*;
+
+        accounts.filter(suspended).select(id).into_boxed()
+    }
+
+    /// Sets whether this account is silenced, ie hidden from the public timelines.
+    pub fn set_silenced(&self, db_conn: &DbConnection, value: bool) -> QueryResult<()> {
+        use crate::db::schema::accounts::dsl::silenced;
+
+        diesel::update(self)
+            .set(silenced.eq(value))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Sets whether this account is suspended. Suspended local users can't sign in,
+    /// and suspended accounts are hidden everywhere.
+    pub fn set_suspended(&self, db_conn: &DbConnection, value: bool) -> QueryResult<()> {
+        use crate::db::schema::accounts::dsl::suspended;
+
+        diesel::update(self)
+            .set(suspended.eq(value))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Sets whether all statuses from this account are marked as sensitive.
+    pub fn set_force_sensitive(&self, db_conn: &DbConnection, value: bool) -> QueryResult<()> {
+        use crate::db::schema::accounts::dsl::force_sensitive;
+
+        diesel::update(self)
+            .set(force_sensitive.eq(value))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Sets whether this account is locked, ie whether new followers must be approved.
+    pub fn set_locked(&self, db_conn: &DbConnection, value: bool) -> QueryResult<()> {
+        use crate::db::schema::accounts::dsl::locked;
+
+        diesel::update(self)
+            .set(locked.eq(value))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Finds a remote account by the URI of its ActivityPub actor.
+    pub fn fetch_by_uri(db_conn: &DbConnection, actor_uri: &str) -> QueryResult<Option<Account>> {
+        use crate::db::schema::accounts::dsl::{accounts, uri};
+
+        accounts
+            .filter(uri.eq(actor_uri))
+            .first::<Account>(db_conn)
+            .optional()
+    }
+
+    /// Finds a local account by the URI of its ActivityPub actor (ie, `{BASE_URL}/users/{username}`).
+    pub fn fetch_local_by_uri(
+        db_conn: &DbConnection,
+        actor_uri: &str,
+    ) -> QueryResult<Option<Account>> {
+        let prefix = format!("{}/users/", BASE_URL.as_str());
+
+        match actor_uri.get(prefix.len()..) {
+            Some(username) if actor_uri.starts_with(&prefix) && !username.contains('/') => {
+                Account::fetch_local_by_username(db_conn, username)
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the accounts waiting on approval to follow this account, most recent request first.
+    pub fn follow_requesters(&self, db_conn: &DbConnection) -> QueryResult<Vec<Account>> {
+        use crate::db::schema::follow_requests;
+
+        follow_requests::table
+            .inner_join(accounts::table.on(accounts::id.eq(follow_requests::source_id)))
+            .filter(follow_requests::target_id.eq(self.id))
+            .order(follow_requests::id.desc())
+            .select(accounts::all_columns)
+            .get_results::<Account>(db_conn)
+    }
+
     pub fn display_name_or_username(&self) -> &str {
         self.display_name.as_ref().unwrap_or(&self.username)
     }
@@ -333,6 +589,12 @@ mod tests {
             summary: None,
             pubkey: Vec::new(),
             privkey: None,
+            silenced: false,
+            suspended: false,
+            force_sensitive: false,
+            locked: false,
+            inbox_uri: None,
+            created_at: Utc::now(),
         };
         let account_with_local_domain: Account = Account {
             id: 2,
@@ -343,6 +605,12 @@ mod tests {
             summary: None,
             pubkey: Vec::new(),
             privkey: None,
+            silenced: false,
+            suspended: false,
+            force_sensitive: false,
+            locked: false,
+            inbox_uri: None,
+            created_at: Utc::now(),
         };
         let account_with_remote_domain: Account = Account {
             id: 3,
@@ -353,6 +621,12 @@ mod tests {
             summary: None,
             pubkey: Vec::new(),
             privkey: None,
+            silenced: false,
+            suspended: false,
+            force_sensitive: false,
+            locked: false,
+            inbox_uri: None,
+            created_at: Utc::now(),
         };
         assert_eq!(
             account_with_null_domain.get_domain(),
@@ -0,0 +1,133 @@
+use chrono::offset::Utc;
+use chrono::DateTime;
+use diesel;
+use diesel::prelude::*;
+use failure::Error;
+
+use crate::crypto;
+use crate::db::schema::oauth_applications;
+use crate::db::{id_generator, DbConnection};
+use crate::oauth::Scopes;
+
+/// The redirect URI for clients which can't receive redirects, and show the user the
+/// authorization code to paste into the client instead.
+pub const OUT_OF_BAND_URI: &str = "urn:ietf:wg:oauth:2.0:oob";
+
+/// Represents a third-party client registered to use the API through OAuth.
+#[derive(Identifiable, Queryable, PartialEq, Debug)]
+#[table_name = "oauth_applications"]
+pub struct Application {
+    pub id: i64,
+    pub name: String,
+    pub website: Option<String>,
+    pub redirect_uris: String,
+    pub scopes: String,
+    pub client_id: String,
+    /// Hash of the client secret; see [`crypto::hash_token`].
+    pub client_secret_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a new application for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "oauth_applications"]
+pub struct NewApplication {
+    pub id: i64,
+    pub name: String,
+    pub website: Option<String>,
+    pub redirect_uris: String,
+    pub scopes: String,
+    pub client_id: String,
+    pub client_secret_hash: String,
+}
+
+impl Application {
+    /// Registers a new application, with a fresh client ID and secret, returning it along
+    /// with the secret; the only time that's available.
+    pub fn create(
+        db_conn: &DbConnection,
+        name: &str,
+        website: Option<&str>,
+        redirect_uris: &str,
+        scopes: &Scopes,
+    ) -> Result<(Application, String), Error> {
+        let client_secret = crypto::random_token()?;
+        let new_application = NewApplication {
+            id: id_generator().next(),
+            name: name.to_string(),
+            website: website.map(String::from),
+            redirect_uris: redirect_uris.to_string(),
+            scopes: scopes.to_string(),
+            client_id: crypto::random_token()?,
+            client_secret_hash: crypto::hash_token(&client_secret),
+        };
+
+        let application = diesel::insert_into(oauth_applications::table)
+            .values(&new_application)
+            .get_result(db_conn)?;
+
+        Ok((application, client_secret))
+    }
+
+    /// Finds an application by its client ID.
+    pub fn by_client_id(
+        db_conn: &DbConnection,
+        candidate: &str,
+    ) -> QueryResult<Option<Application>> {
+        use crate::db::schema::oauth_applications::dsl::*;
+
+        oauth_applications
+            .filter(client_id.eq(candidate))
+            .first(db_conn)
+            .optional()
+    }
+
+    /// Finds an application by its ID.
+    pub fn by_id(db_conn: &DbConnection, app_id: i64) -> QueryResult<Option<Application>> {
+        use crate::db::schema::oauth_applications::dsl::*;
+
+        oauth_applications.find(app_id).first(db_conn).optional()
+    }
+
+    /// Checks (in constant time) whether `candidate` is this application's client secret.
+    pub fn valid_secret(&self, candidate: &str) -> bool {
+        crypto::token_matches(candidate, &self.client_secret_hash)
+    }
+
+    /// Returns whether `uri` is one of the redirect URIs this application registered.
+    pub fn allows_redirect_uri(&self, uri: &str) -> bool {
+        self.redirect_uris
+            .lines()
+            .any(|allowed| allowed.trim() == uri)
+    }
+
+    /// Returns the scopes this application registered for; it can't ask for more.
+    pub fn scopes(&self) -> Scopes {
+        Scopes::from(self.scopes.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_registered_redirect_uris_are_allowed() {
+        let app = Application {
+            id: 1,
+            name: "client".to_string(),
+            website: None,
+            redirect_uris: "https://client.example/callback\nurn:ietf:wg:oauth:2.0:oob".to_string(),
+            scopes: "read write".to_string(),
+            client_id: "ID".to_string(),
+            client_secret_hash: crypto::hash_token("SECRET"),
+            created_at: Utc::now(),
+        };
+
+        assert!(app.allows_redirect_uri("https://client.example/callback"));
+        assert!(app.allows_redirect_uri(OUT_OF_BAND_URI));
+        assert!(!app.allows_redirect_uri("https://evil.example/callback"));
+        assert!(app.valid_secret("SECRET"));
+        assert!(!app.valid_secret("SECRE"));
+    }
+}
@@ -0,0 +1,128 @@
+use chrono::offset::Utc;
+use chrono::{DateTime, Duration};
+use diesel;
+use diesel::prelude::*;
+use failure::Error;
+
+use super::{Application, User};
+use crate::crypto;
+use crate::db::schema::oauth_authorization_codes;
+use crate::db::{id_generator, DbConnection};
+use crate::oauth::{self, Scopes};
+
+/// How long an authorization code can be exchanged for a token, in minutes.
+pub const AUTHORIZATION_CODE_VALIDITY_MINUTES: i64 = 10;
+
+/// Represents a one-time code handed to a client once a user has approved it, which the
+/// client exchanges for an access token. Only a hash of the code is stored.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(Application)]
+#[belongs_to(User)]
+#[table_name = "oauth_authorization_codes"]
+pub struct AuthorizationCode {
+    pub id: i64,
+    pub code_hash: String,
+    pub application_id: i64,
+    pub user_id: i64,
+    pub redirect_uri: String,
+    pub scopes: String,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Represents a new authorization code for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "oauth_authorization_codes"]
+pub struct NewAuthorizationCode {
+    pub id: i64,
+    pub code_hash: String,
+    pub application_id: i64,
+    pub user_id: i64,
+    pub redirect_uri: String,
+    pub scopes: String,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AuthorizationCode {
+    /// Issues a code letting `application` act as the user `uid` with `scopes`, returning it
+    /// along with the code itself; the only time that's available.
+    ///
+    /// `code_challenge` is the client's PKCE challenge and its method, if it sent one.
+    pub fn create(
+        db_conn: &DbConnection,
+        application: &Application,
+        uid: i64,
+        redirect_uri: &str,
+        scopes: &Scopes,
+        code_challenge: Option<(String, String)>,
+    ) -> Result<(AuthorizationCode, String), Error> {
+        let (code_challenge, code_challenge_method) = match code_challenge {
+            Some((challenge, method)) => (Some(challenge), Some(method)),
+            None => (None, None),
+        };
+
+        let code = crypto::random_token()?;
+        let new_code = NewAuthorizationCode {
+            id: id_generator().next(),
+            code_hash: crypto::hash_token(&code),
+            application_id: application.id,
+            user_id: uid,
+            redirect_uri: redirect_uri.to_string(),
+            scopes: scopes.to_string(),
+            code_challenge,
+            code_challenge_method,
+            expires_at: Utc::now() + Duration::minutes(AUTHORIZATION_CODE_VALIDITY_MINUTES),
+        };
+
+        let authorization_code = diesel::insert_into(oauth_authorization_codes::table)
+            .values(&new_code)
+            .get_result(db_conn)?;
+
+        Ok((authorization_code, code))
+    }
+
+    /// Uses up the code `candidate` of the application `app_id`, returning it if it was
+    /// still valid. A code can only ever be taken once.
+    pub fn take(
+        db_conn: &DbConnection,
+        app_id: i64,
+        candidate: &str,
+    ) -> QueryResult<Option<AuthorizationCode>> {
+        use crate::db::schema::oauth_authorization_codes::dsl::*;
+
+        diesel::delete(
+            oauth_authorization_codes
+                .filter(code_hash.eq(crypto::hash_token(candidate)))
+                .filter(application_id.eq(app_id)),
+        )
+        .get_result::<AuthorizationCode>(db_conn)
+        .optional()
+        .map(|taken| taken.filter(|taken| taken.expires_at > Utc::now()))
+    }
+
+    /// Checks the PKCE `code_verifier` a client sent with this code, if any.
+    ///
+    /// Codes issued with a challenge need the matching verifier; codes issued without one
+    /// must not get one either.
+    pub fn verify(&self, code_verifier: Option<&str>) -> bool {
+        match (
+            &self.code_challenge,
+            &self.code_challenge_method,
+            code_verifier,
+        ) {
+            (Some(challenge), Some(method), Some(verifier)) => {
+                oauth::verify_code_challenge(verifier, challenge, method)
+            },
+            (None, _, None) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the scopes the user granted with this code.
+    pub fn scopes(&self) -> Scopes {
+        Scopes::from(self.scopes.as_str())
+    }
+}
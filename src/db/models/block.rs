@@ -0,0 +1,77 @@
+use diesel;
+use diesel::prelude::*;
+
+use crate::db::schema::blocks;
+use crate::db::DbConnection;
+
+/// Represents an account blocking another, `[account] -| [target]`.
+///
+/// Blocked accounts can't follow or notify the blocker, and vice versa.
+#[derive(Identifiable, Queryable, PartialEq, Debug)]
+#[table_name = "blocks"]
+pub struct Block {
+    pub id: i64,
+    pub account_id: i64,
+    pub target_id: i64,
+}
+
+/// Represents a new block for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "blocks"]
+pub struct NewBlock {
+    pub id: i64,
+    pub account_id: i64,
+    pub target_id: i64,
+}
+
+impl NewBlock {
+    /// Inserts the block, doing nothing if `account` already blocks `target`.
+    pub fn insert(self, conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::blocks::dsl::*;
+
+        diesel::insert_into(blocks)
+            .values(&self)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .and(Ok(()))
+    }
+}
+
+impl Block {
+    /// Returns whether either of the two accounts blocks the other.
+    pub fn exists_between(conn: &DbConnection, a: i64, b: i64) -> QueryResult<bool> {
+        use crate::db::schema::blocks::dsl::*;
+        use diesel::dsl::{exists, select};
+
+        select(exists(blocks.filter(
+            (account_id.eq(a).and(target_id.eq(b))).or(account_id.eq(b).and(target_id.eq(a))),
+        )))
+        .get_result(conn)
+    }
+
+    /// Returns whether `account` blocks `target`.
+    pub fn exists(conn: &DbConnection, account: i64, target: i64) -> QueryResult<bool> {
+        use crate::db::schema::blocks::dsl::*;
+        use diesel::dsl::{exists, select};
+
+        select(exists(
+            blocks
+                .filter(account_id.eq(account))
+                .filter(target_id.eq(target)),
+        ))
+        .get_result(conn)
+    }
+
+    /// Removes the block `account -| target`, if there is one.
+    pub fn delete_between(conn: &DbConnection, account: i64, target: i64) -> QueryResult<()> {
+        use crate::db::schema::blocks::dsl::*;
+
+        diesel::delete(
+            blocks
+                .filter(account_id.eq(account))
+                .filter(target_id.eq(target)),
+        )
+        .execute(conn)
+        .and(Ok(()))
+    }
+}
@@ -0,0 +1,271 @@
+use chrono::offset::Utc;
+use chrono::DateTime;
+use diesel;
+use diesel::prelude::*;
+use std::path::Path;
+
+use super::{Account, Status};
+use crate::db::schema::custom_emojis;
+use crate::db::{id_generator, DbConnection, LOCAL_ACCOUNT_DOMAIN};
+use crate::BASE_URL;
+
+/// Represents a custom emoji, shown in place of its `:shortcode:` in the statuses and bios
+/// of accounts from the same domain.
+#[derive(Identifiable, Queryable, PartialEq, Debug, Clone)]
+#[table_name = "custom_emojis"]
+pub struct CustomEmoji {
+    pub id: i64,
+    pub shortcode: String,
+    /// The domain the emoji comes from, or `LOCAL_ACCOUNT_DOMAIN` for our own.
+    pub domain: String,
+    /// The path of the image in `MEDIA_DIR`. The images of remote emoji are fetched into it.
+    pub image: String,
+    pub uri: Option<String>,
+    /// Where the image of a remote emoji was fetched from.
+    pub remote_image_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Represents a new custom emoji for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "custom_emojis"]
+pub struct NewCustomEmoji {
+    pub id: i64,
+    pub shortcode: String,
+    pub domain: String,
+    pub image: String,
+    pub uri: Option<String>,
+    pub remote_image_url: Option<String>,
+}
+
+impl NewCustomEmoji {
+    /// Inserts the emoji, replacing the image and URIs of any emoji with the same shortcode
+    /// from the same domain. If that changes anything, the cached HTML of statuses which
+    /// might use the emoji is forgotten.
+    pub fn upsert(self, db_conn: &DbConnection) -> QueryResult<CustomEmoji> {
+        use crate::db::schema::custom_emojis::dsl::*;
+
+        if let Some(existing) = CustomEmoji::by_shortcode(db_conn, &self.shortcode, &self.domain)? {
+            if existing.image == self.image
+                && existing.uri == self.uri
+                && existing.remote_image_url == self.remote_image_url
+            {
+                return Ok(existing);
+            }
+        }
+
+        let emoji: CustomEmoji = diesel::insert_into(custom_emojis)
+            .values(&self)
+            .on_conflict((shortcode, domain))
+            .do_update()
+            .set((
+                image.eq(&self.image),
+                uri.eq(&self.uri),
+                remote_image_url.eq(&self.remote_image_url),
+                updated_at.eq(Utc::now()),
+            ))
+            .get_result(db_conn)?;
+        Status::forget_content_html_with_emoji(db_conn, &emoji.shortcode, &emoji.domain)?;
+        Ok(emoji)
+    }
+}
+
+impl CustomEmoji {
+    /// Adds (or replaces) the local emoji `name`, whose image is `image_path` in `MEDIA_DIR`.
+    pub fn create_local(
+        db_conn: &DbConnection,
+        name: &str,
+        image_path: &str,
+    ) -> QueryResult<CustomEmoji> {
+        NewCustomEmoji {
+            id: id_generator().next(),
+            shortcode: name.to_string(),
+            domain: LOCAL_ACCOUNT_DOMAIN.to_string(),
+            image: image_path.to_string(),
+            uri: None,
+            remote_image_url: None,
+        }
+        .upsert(db_conn)
+    }
+
+    /// Adds (or updates) the emoji `name` from `emoji_domain`, whose image was fetched from
+    /// `image_url` into `image_path` in `MEDIA_DIR`.
+    pub fn create_remote(
+        db_conn: &DbConnection,
+        name: &str,
+        emoji_domain: &str,
+        image_path: &str,
+        image_url: &str,
+        emoji_uri: Option<&str>,
+    ) -> QueryResult<CustomEmoji> {
+        NewCustomEmoji {
+            id: id_generator().next(),
+            shortcode: name.to_string(),
+            domain: emoji_domain.to_string(),
+            image: image_path.to_string(),
+            uri: emoji_uri.map(String::from),
+            remote_image_url: Some(image_url.to_string()),
+        }
+        .upsert(db_conn)
+    }
+
+    /// Returns the emoji `name` from `emoji_domain` (`LOCAL_ACCOUNT_DOMAIN` for our own).
+    pub fn by_shortcode(
+        db_conn: &DbConnection,
+        name: &str,
+        emoji_domain: &str,
+    ) -> QueryResult<Option<CustomEmoji>> {
+        use crate::db::schema::custom_emojis::dsl::*;
+
+        custom_emojis
+            .filter(shortcode.eq(name))
+            .filter(domain.eq(emoji_domain))
+            .first(db_conn)
+            .optional()
+    }
+
+    /// Returns the emoji with the ID `emoji_id`.
+    pub fn by_id(db_conn: &DbConnection, emoji_id: i64) -> QueryResult<Option<CustomEmoji>> {
+        use crate::db::schema::custom_emojis::dsl::*;
+
+        custom_emojis.find(emoji_id).first(db_conn).optional()
+    }
+
+    /// Returns the emoji `name` which `account` can use: its domain's, or ours if it's local.
+    pub fn for_account(
+        db_conn: &DbConnection,
+        name: &str,
+        account: &Account,
+    ) -> QueryResult<Option<CustomEmoji>> {
+        use crate::db::schema::custom_emojis::dsl::*;
+
+        custom_emojis
+            .filter(shortcode.eq(name))
+            .filter(domain.eq(Self::domain_of(account)))
+            .first(db_conn)
+            .optional()
+    }
+
+    /// Returns the emoji among `names` which `account` can use, in no particular order.
+    pub fn all_for_account(
+        db_conn: &DbConnection,
+        names: &[String],
+        account: &Account,
+    ) -> QueryResult<Vec<CustomEmoji>> {
+        use crate::db::schema::custom_emojis::dsl::*;
+
+        custom_emojis
+            .filter(shortcode.eq_any(names))
+            .filter(domain.eq(Self::domain_of(account)))
+            .load(db_conn)
+    }
+
+    /// Returns every local emoji, ordered by shortcode.
+    pub fn local(db_conn: &DbConnection) -> QueryResult<Vec<CustomEmoji>> {
+        use crate::db::schema::custom_emojis::dsl::*;
+
+        custom_emojis
+            .filter(domain.eq(LOCAL_ACCOUNT_DOMAIN))
+            .order(shortcode.asc())
+            .load(db_conn)
+    }
+
+    /// Deletes the local emoji `name`, returning it if there was one.
+    pub fn delete_local(db_conn: &DbConnection, name: &str) -> QueryResult<Option<CustomEmoji>> {
+        use crate::db::schema::custom_emojis::dsl::*;
+
+        let deleted = diesel::delete(
+            custom_emojis
+                .filter(shortcode.eq(name))
+                .filter(domain.eq(LOCAL_ACCOUNT_DOMAIN)),
+        )
+        .get_result(db_conn)
+        .optional()?;
+        if deleted.is_some() {
+            Status::forget_content_html_with_emoji(db_conn, name, LOCAL_ACCOUNT_DOMAIN)?;
+        }
+        Ok(deleted)
+    }
+
+    /// The domain of the emoji `account` can use.
+    fn domain_of(account: &Account) -> &str {
+        if account.is_local() {
+            LOCAL_ACCOUNT_DOMAIN
+        } else {
+            account.get_domain()
+        }
+    }
+
+    /// Returns whether this emoji is one of ours.
+    pub fn is_local(&self) -> bool {
+        self.domain == LOCAL_ACCOUNT_DOMAIN
+    }
+
+    /// Returns the URL of the emoji's image, which we serve whether the emoji is ours or not.
+    pub fn image_url(&self) -> String {
+        format!("{}/media/{}", BASE_URL.as_str(), self.image)
+    }
+
+    /// Returns the media type of the emoji's image, going by its extension.
+    pub fn media_type(&self) -> &'static str {
+        let extension = Path::new(&self.image)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+
+        match extension.as_ref().map(String::as_str) {
+            Some("gif") => "image/gif",
+            Some("jpg") | Some("jpeg") => "image/jpeg",
+            Some("svg") => "image/svg+xml",
+            Some("webp") => "image/webp",
+            _ => "image/png",
+        }
+    }
+
+    /// Returns the URI of the emoji as an ActivityPub object.
+    pub fn get_uri(&self) -> String {
+        self.uri
+            .clone()
+            .unwrap_or_else(|| format!("{}/emojis/{}", BASE_URL.as_str(), self.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emoji(domain: &str, image: &str) -> CustomEmoji {
+        CustomEmoji {
+            id: 1,
+            shortcode: "blobcat".to_string(),
+            domain: domain.to_string(),
+            image: image.to_string(),
+            uri: None,
+            remote_image_url: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn it_finds_image_urls() {
+        std::env::set_var("DOMAIN", "localhost");
+
+        assert_eq!(
+            emoji("", "emoji/blobcat.png").image_url(),
+            "https://localhost/media/emoji/blobcat.png"
+        );
+        assert_eq!(
+            emoji("remote.example", "emoji/remote/remote.example/blobcat.png").image_url(),
+            "https://localhost/media/emoji/remote/remote.example/blobcat.png"
+        );
+    }
+
+    #[test]
+    fn it_guesses_media_types() {
+        assert_eq!(emoji("", "emoji/blobcat.GIF").media_type(), "image/gif");
+        assert_eq!(emoji("", "emoji/blobcat.svg").media_type(), "image/svg+xml");
+        assert_eq!(emoji("", "emoji/blobcat").media_type(), "image/png");
+    }
+}
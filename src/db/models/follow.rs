@@ -1,4 +1,8 @@
+use diesel;
+use diesel::prelude::*;
+
 use crate::db::schema::follows;
+use crate::db::DbConnection;
 
 /// Represents a following relationship `[source user] -> [target user]`.
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
@@ -8,3 +12,53 @@ pub struct Follow {
     pub source_id: i64,
     pub target_id: i64,
 }
+
+/// Represents a new follow for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "follows"]
+pub struct NewFollow {
+    pub id: i64,
+    pub source_id: i64,
+    pub target_id: i64,
+}
+
+impl NewFollow {
+    /// Inserts the follow, doing nothing if `source` already follows `target`.
+    pub fn insert(self, conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::follows::dsl::*;
+
+        diesel::insert_into(follows)
+            .values(&self)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .and(Ok(()))
+    }
+}
+
+impl Follow {
+    /// Returns whether `source` follows `target`.
+    pub fn exists(conn: &DbConnection, source: i64, target: i64) -> QueryResult<bool> {
+        use crate::db::schema::follows::dsl::*;
+        use diesel::dsl::{exists, select};
+
+        select(exists(
+            follows
+                .filter(source_id.eq(source))
+                .filter(target_id.eq(target)),
+        ))
+        .get_result(conn)
+    }
+
+    /// Removes the following relationship `source -> target`, if there is one.
+    pub fn delete_between(conn: &DbConnection, source: i64, target: i64) -> QueryResult<()> {
+        use crate::db::schema::follows::dsl::*;
+
+        diesel::delete(
+            follows
+                .filter(source_id.eq(source))
+                .filter(target_id.eq(target)),
+        )
+        .execute(conn)
+        .and(Ok(()))
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::offset::Utc;
+use chrono::DateTime;
+use diesel;
+use diesel::prelude::*;
+
+use super::{Account, NewFollow};
+use crate::db::schema::follow_requests;
+use crate::db::{id_generator, DbConnection};
+
+/// Represents a pending request to follow a locked account, `[source user] -> [target user]`.
+///
+/// A uri of None implies the request was made locally.
+#[derive(Identifiable, Queryable, PartialEq, Debug)]
+#[table_name = "follow_requests"]
+pub struct FollowRequest {
+    pub id: i64,
+    pub source_id: i64,
+    pub target_id: i64,
+    pub uri: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a new follow request for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "follow_requests"]
+pub struct NewFollowRequest {
+    pub id: i64,
+    pub source_id: i64,
+    pub target_id: i64,
+    pub uri: Option<String>,
+}
+
+impl NewFollowRequest {
+    /// Inserts the request, doing nothing if `source` is already waiting on `target`.
+    pub fn insert(self, conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::follow_requests::dsl::*;
+
+        diesel::insert_into(follow_requests)
+            .values(&self)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .and(Ok(()))
+    }
+}
+
+impl FollowRequest {
+    /// Returns the pending request `source -> target`, if there is one.
+    pub fn between(
+        db_conn: &DbConnection,
+        source: i64,
+        target: i64,
+    ) -> QueryResult<Option<FollowRequest>> {
+        use crate::db::schema::follow_requests::dsl::*;
+
+        follow_requests
+            .filter(source_id.eq(source))
+            .filter(target_id.eq(target))
+            .first::<FollowRequest>(db_conn)
+            .optional()
+    }
+
+    /// Returns the `Account` which asked to follow.
+    pub fn source(&self, db_conn: &DbConnection) -> QueryResult<Account> {
+        use crate::db::schema::accounts::dsl::accounts;
+
+        accounts.find(self.source_id).first::<Account>(db_conn)
+    }
+
+    /// Returns the `Account` which was asked to be followed.
+    pub fn target(&self, db_conn: &DbConnection) -> QueryResult<Account> {
+        use crate::db::schema::accounts::dsl::accounts;
+
+        accounts.find(self.target_id).first::<Account>(db_conn)
+    }
+
+    /// Accepts the request, turning it into a follow.
+    pub fn authorize(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        db_conn.transaction(|| {
+            NewFollow {
+                id: id_generator().next(),
+                source_id: self.source_id,
+                target_id: self.target_id,
+            }
+            .insert(db_conn)?;
+
+            diesel::delete(self).execute(db_conn).and(Ok(()))
+        })
+    }
+
+    /// Rejects the request, discarding it.
+    pub fn reject(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        diesel::delete(self).execute(db_conn).and(Ok(()))
+    }
+
+    /// Discards any pending request `source -> target`, eg when the request is withdrawn.
+    pub fn delete_between(db_conn: &DbConnection, source: i64, target: i64) -> QueryResult<()> {
+        use crate::db::schema::follow_requests::dsl::*;
+
+        diesel::delete(
+            follow_requests
+                .filter(source_id.eq(source))
+                .filter(target_id.eq(target)),
+        )
+        .execute(db_conn)
+        .and(Ok(()))
+    }
+}
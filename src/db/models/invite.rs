@@ -0,0 +1,135 @@
+use chrono::offset::Utc;
+use chrono::{DateTime, Duration};
+use diesel;
+use diesel::prelude::*;
+use failure::Error;
+
+use super::User;
+use crate::crypto;
+use crate::db::schema::invites;
+use crate::db::{id_generator, DbConnection};
+use crate::BASE_URL;
+
+/// Represents an invite code, which lets people sign up while registrations are invite-only.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(User)]
+#[table_name = "invites"]
+pub struct Invite {
+    pub id: i64,
+    pub code: String,
+    pub user_id: i64,
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a new invite for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "invites"]
+pub struct NewInvite {
+    pub id: i64,
+    pub code: String,
+    pub user_id: i64,
+    pub max_uses: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Invite {
+    /// Makes a new invite from the user `uid`, which can be used `max_uses` times (or any
+    /// number of times, if `None`) and runs out after `valid_for` (or never).
+    pub fn create(
+        db_conn: &DbConnection,
+        uid: i64,
+        max_uses: Option<i32>,
+        valid_for: Option<Duration>,
+    ) -> Result<Invite, Error> {
+        let new_invite = NewInvite {
+            id: id_generator().next(),
+            code: crypto::random_token()?,
+            user_id: uid,
+            max_uses,
+            expires_at: valid_for.map(|valid_for| Utc::now() + valid_for),
+        };
+
+        Ok(diesel::insert_into(invites::table)
+            .values(&new_invite)
+            .get_result(db_conn)?)
+    }
+
+    /// Uses up one use of the invite `invite_code`, if it exists and hasn't run out.
+    ///
+    /// Returns whether it did.
+    pub fn redeem(db_conn: &DbConnection, invite_code: &str) -> QueryResult<bool> {
+        use crate::db::schema::invites::dsl::*;
+
+        let updated = diesel::update(
+            invites
+                .filter(code.eq(invite_code))
+                .filter(max_uses.is_null().or(max_uses.gt(uses.nullable())))
+                .filter(expires_at.is_null().or(expires_at.gt(Utc::now()))),
+        )
+        .set(uses.eq(uses + 1))
+        .execute(db_conn)?;
+
+        Ok(updated > 0)
+    }
+
+    /// Returns every invite made by the user `uid`, newest first.
+    pub fn by_user(db_conn: &DbConnection, uid: i64) -> QueryResult<Vec<Invite>> {
+        use crate::db::schema::invites::dsl::*;
+
+        invites
+            .filter(user_id.eq(uid))
+            .order(id.desc())
+            .load(db_conn)
+    }
+
+    /// Deletes the invite `invite_id` made by the user `uid`, so it can't be used any more.
+    pub fn revoke(db_conn: &DbConnection, uid: i64, invite_id: i64) -> QueryResult<()> {
+        use crate::db::schema::invites::dsl::*;
+
+        diesel::delete(invites.filter(id.eq(invite_id)).filter(user_id.eq(uid)))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Returns whether this invite can still be used.
+    pub fn is_usable(&self) -> bool {
+        self.max_uses.map_or(true, |max_uses| self.uses < max_uses)
+            && self
+                .expires_at
+                .map_or(true, |expires_at| expires_at > Utc::now())
+    }
+
+    /// Returns the link people can sign up with this invite at.
+    pub fn link(&self) -> String {
+        format!("{}/auth/sign_up?invite={}", *BASE_URL, self.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invite(max_uses: Option<i32>, uses: i32, expires_in: Option<i64>) -> Invite {
+        Invite {
+            id: 1,
+            code: "CODE".to_string(),
+            user_id: 1,
+            max_uses,
+            uses,
+            expires_at: expires_in.map(|secs| Utc::now() + Duration::seconds(secs)),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn invites_run_out() {
+        assert!(invite(None, 100, None).is_usable());
+        assert!(invite(Some(2), 1, None).is_usable());
+        assert!(!invite(Some(2), 2, None).is_usable());
+        assert!(invite(None, 0, Some(60)).is_usable());
+        assert!(!invite(None, 0, Some(-60)).is_usable());
+    }
+}
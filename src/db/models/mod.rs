@@ -4,12 +4,43 @@
 //! The ordering must match that in the generated schema, which
 //! you can obtain with `diesel print-schema`.
 
-pub use self::account::{Account, NewAccount};
-pub use self::follow::Follow;
-pub use self::status::{NewStatus, Status};
-pub use self::user::{NewUser, User};
+pub use self::access_token::{AccessToken, CanRead, CanWrite, NewAccessToken, RequireScope};
+pub use self::account::{Account, AccountCounts, NewAccount};
+pub use self::application::{Application, NewApplication, OUT_OF_BAND_URI};
+pub use self::authorization_code::{AuthorizationCode, NewAuthorizationCode};
+pub use self::block::{Block, NewBlock};
+pub use self::custom_emoji::{CustomEmoji, NewCustomEmoji};
+pub use self::follow::{Follow, NewFollow};
+pub use self::follow_request::{FollowRequest, NewFollowRequest};
+pub use self::invite::{Invite, NewInvite};
+pub use self::mute::{Mute, NewMute};
+pub use self::notification::{NewNotification, Notification, NotificationKind};
+pub use self::recovery_code::{NewRecoveryCode, RecoveryCode, RECOVERY_CODE_COUNT};
+pub use self::relationship::Relationship;
+pub use self::session::{ClientInfo, NewSession, Session, SESSION_COOKIE};
+pub use self::status::{NewStatus, Page, Status};
+pub use self::throttle::{
+    email_key, ip_key, username_key, Limit, NewThrottle, Throttle, EMAIL_LIMIT, SIGN_IN_LIMIT,
+    SIGN_UP_LIMIT,
+};
+pub use self::user::{
+    Admin, Confirmed, Moderator, NewUser, RequireRole, Role, User, RESET_PASSWORD_VALIDITY_HOURS,
+};
 
+mod access_token;
 mod account;
+mod application;
+mod authorization_code;
+mod block;
+mod custom_emoji;
 mod follow;
+mod follow_request;
+mod invite;
+mod mute;
+mod notification;
+mod recovery_code;
+mod relationship;
+mod session;
 mod status;
+mod throttle;
 mod user;
@@ -0,0 +1,79 @@
+use diesel;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+
+use crate::db::schema::mutes;
+use crate::db::DbConnection;
+
+/// Represents an account muting another, `[account] -/ [target]`.
+///
+/// Unlike a block, a mute is invisible to the muted account; the muter just stops
+/// hearing from them.
+#[derive(Identifiable, Queryable, PartialEq, Debug)]
+#[table_name = "mutes"]
+pub struct Mute {
+    pub id: i64,
+    pub account_id: i64,
+    pub target_id: i64,
+}
+
+/// Represents a new mute for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "mutes"]
+pub struct NewMute {
+    pub id: i64,
+    pub account_id: i64,
+    pub target_id: i64,
+}
+
+impl NewMute {
+    /// Inserts the mute, doing nothing if `account` already mutes `target`.
+    pub fn insert(self, conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::mutes::dsl::*;
+
+        diesel::insert_into(mutes)
+            .values(&self)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .and(Ok(()))
+    }
+}
+
+impl Mute {
+    /// Returns whether `account` mutes `target`.
+    pub fn exists(conn: &DbConnection, account: i64, target: i64) -> QueryResult<bool> {
+        use crate::db::schema::mutes::dsl::*;
+        use diesel::dsl::{exists, select};
+
+        select(exists(
+            mutes
+                .filter(account_id.eq(account))
+                .filter(target_id.eq(target)),
+        ))
+        .get_result(conn)
+    }
+
+    /// Returns a query selecting the IDs of the accounts `account` mutes.
+    pub fn targets_of(account: i64) -> mutes::BoxedQuery<'static, Pg, BigInt> {
+        use crate::db::schema::mutes::dsl::*;
+
+        mutes
+            .filter(account_id.eq(account))
+            .select(target_id)
+            .into_boxed()
+    }
+
+    /// Removes the mute `account -/ target`, if there is one.
+    pub fn delete_between(conn: &DbConnection, account: i64, target: i64) -> QueryResult<()> {
+        use crate::db::schema::mutes::dsl::*;
+
+        diesel::delete(
+            mutes
+                .filter(account_id.eq(account))
+                .filter(target_id.eq(target)),
+        )
+        .execute(conn)
+        .and(Ok(()))
+    }
+}
@@ -0,0 +1,260 @@
+use chrono::offset::Utc;
+use chrono::DateTime;
+use chrono_humanize::Humanize;
+use diesel;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use rocket::http::RawStr;
+use rocket::request::FromFormValue;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use super::{Account, Block, Mute, Status};
+use crate::db::schema::notifications;
+use crate::db::{id_generator, DbConnection};
+use crate::events::{self, Event};
+
+/// Represents something that happened to a local account which it should hear about.
+#[derive(Identifiable, Queryable, PartialEq, Debug)]
+#[table_name = "notifications"]
+pub struct Notification {
+    pub id: i64,
+    pub account_id: i64,
+    pub from_account_id: i64,
+    pub kind: NotificationKind,
+    pub status_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub read: bool,
+}
+
+/// Represents a new notification for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "notifications"]
+pub struct NewNotification {
+    pub id: i64,
+    pub account_id: i64,
+    pub from_account_id: i64,
+    pub kind: NotificationKind,
+    pub status_id: Option<i64>,
+}
+
+/// What a notification is about.
+#[derive(AsExpression, FromSqlRow, Clone, Copy, PartialEq, Eq, Debug)]
+#[sql_type = "Text"]
+pub enum NotificationKind {
+    /// `from_account` mentioned the account in `status`.
+    Mention,
+    /// `from_account` followed the account.
+    Follow,
+    /// `from_account` asked to follow the (locked) account.
+    FollowRequest,
+    /// `from_account` favourited `status`.
+    Favourite,
+    /// `from_account` reblogged `status`.
+    Reblog,
+}
+
+impl NotificationKind {
+    /// Returns the name of this kind, as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotificationKind::Mention => "mention",
+            NotificationKind::Follow => "follow",
+            NotificationKind::FollowRequest => "follow_request",
+            NotificationKind::Favourite => "favourite",
+            NotificationKind::Reblog => "reblog",
+        }
+    }
+
+    /// Returns what happened, as shown after the name of the notifying account.
+    pub fn description(self) -> &'static str {
+        match self {
+            NotificationKind::Mention => "mentioned you",
+            NotificationKind::Follow => "followed you",
+            NotificationKind::FollowRequest => "asked to follow you",
+            NotificationKind::Favourite => "favourited your post",
+            NotificationKind::Reblog => "boosted your post",
+        }
+    }
+}
+
+impl fmt::Display for NotificationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NotificationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mention" => Ok(NotificationKind::Mention),
+            "follow" => Ok(NotificationKind::Follow),
+            "follow_request" => Ok(NotificationKind::FollowRequest),
+            "favourite" => Ok(NotificationKind::Favourite),
+            "reblog" => Ok(NotificationKind::Reblog),
+            _ => Err(format!("unknown notification kind {}", s)),
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for NotificationKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for NotificationKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let kind = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Ok(kind.parse()?)
+    }
+}
+
+impl<'v> FromFormValue<'v> for NotificationKind {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
+        form_value.as_str().parse().map_err(|_| form_value)
+    }
+}
+
+impl Notification {
+    /// Notifies `recipient` that `from` did something (to `status_id`, if given).
+    ///
+    /// Nothing is recorded if `recipient` is remote or is `from`, if `from` is suspended,
+    /// if either account blocks the other, or if `recipient` has muted `from`.
+    pub fn notify(
+        db_conn: &DbConnection,
+        recipient: &Account,
+        from: &Account,
+        kind: NotificationKind,
+        status_id: Option<i64>,
+    ) -> QueryResult<()> {
+        use crate::db::schema::notifications::dsl::notifications;
+
+        if recipient.uri.is_some() || recipient.id == from.id || from.suspended {
+            return Ok(());
+        }
+
+        if Block::exists_between(db_conn, recipient.id, from.id)?
+            || Mute::exists(db_conn, recipient.id, from.id)?
+        {
+            return Ok(());
+        }
+
+        let notification = NewNotification {
+            id: id_generator().next(),
+            account_id: recipient.id,
+            from_account_id: from.id,
+            kind,
+            status_id,
+        };
+
+        let notification = diesel::insert_into(notifications)
+            .values(&notification)
+            .get_result::<Notification>(db_conn)?;
+        events::publish(Event::Notified(notification));
+        Ok(())
+    }
+
+    /// Returns `n` notifications for the account `account_id`, with IDs _strictly less than_
+    /// `max_id`, newest first; optionally only those of the given `kind`.
+    pub fn before_id(
+        db_conn: &DbConnection,
+        account_id: i64,
+        max_id: Option<i64>,
+        kind: Option<NotificationKind>,
+        n: usize,
+    ) -> QueryResult<Vec<Notification>> {
+        use crate::db::schema::notifications::dsl;
+
+        let mut query = dsl::notifications
+            .filter(dsl::account_id.eq(account_id))
+            .into_boxed();
+
+        if let Some(max_id) = max_id {
+            query = query.filter(dsl::id.lt(max_id));
+        }
+
+        if let Some(kind) = kind {
+            query = query.filter(dsl::kind.eq(kind));
+        }
+
+        query
+            .order(dsl::id.desc())
+            .limit(n as i64)
+            .get_results::<Notification>(db_conn)
+    }
+
+    /// Returns the number of unread notifications for the account `account_id`.
+    pub fn count_unread(db_conn: &DbConnection, account_id: i64) -> QueryResult<i64> {
+        use crate::db::schema::notifications::dsl;
+
+        dsl::notifications
+            .filter(dsl::account_id.eq(account_id))
+            .filter(dsl::read.eq(false))
+            .count()
+            .get_result(db_conn)
+    }
+
+    /// Marks the given notifications of the account `account_id` as read.
+    pub fn mark_read(db_conn: &DbConnection, account_id: i64, ids: &[i64]) -> QueryResult<()> {
+        use crate::db::schema::notifications::dsl;
+
+        diesel::update(
+            dsl::notifications
+                .filter(dsl::account_id.eq(account_id))
+                .filter(dsl::id.eq_any(ids)),
+        )
+        .set(dsl::read.eq(true))
+        .execute(db_conn)
+        .and(Ok(()))
+    }
+
+    /// Returns a human-readble description of the age of this notification.
+    pub fn humanized_age(&self) -> String {
+        self.created_at.humanize()
+    }
+
+    /// Returns the `Account` which caused this notification.
+    pub fn from_account(&self, db_conn: &DbConnection) -> QueryResult<Account> {
+        use crate::db::schema::accounts::dsl::accounts;
+
+        accounts.find(self.from_account_id).first(db_conn)
+    }
+
+    /// Returns the `Status` this notification is about, if any.
+    pub fn status(&self, db_conn: &DbConnection) -> QueryResult<Option<Status>> {
+        use crate::db::schema::statuses::dsl::statuses;
+
+        match self.status_id {
+            Some(status_id) => statuses.find(status_id).first(db_conn).optional(),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_round_trip_through_strings() {
+        for kind in &[
+            NotificationKind::Mention,
+            NotificationKind::Follow,
+            NotificationKind::FollowRequest,
+            NotificationKind::Favourite,
+            NotificationKind::Reblog,
+        ] {
+            assert_eq!(kind.as_str().parse::<NotificationKind>(), Ok(*kind));
+        }
+        assert!("poke".parse::<NotificationKind>().is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use diesel;
+use diesel::prelude::*;
+use failure::Error;
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+
+use super::User;
+use crate::db::schema::recovery_codes;
+use crate::db::{id_generator, DbConnection};
+
+/// How many recovery codes a user gets at a time.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Represents a one-time code which can stand in for a TOTP code, for users who've lost
+/// their authenticator. Only a hash of the code is stored.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(User)]
+#[table_name = "recovery_codes"]
+pub struct RecoveryCode {
+    pub id: i64,
+    pub user_id: i64,
+    pub code_hash: String,
+}
+
+/// Represents a new recovery code for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "recovery_codes"]
+pub struct NewRecoveryCode {
+    pub id: i64,
+    pub user_id: i64,
+    pub code_hash: String,
+}
+
+/// Hashes a recovery code for storage, ignoring case, dashes and whitespace.
+fn hash_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    sha256(normalized.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Returns a fresh random code, like `k3x9q-7bm2w`.
+fn random_code() -> Result<String, Error> {
+    let mut bytes = [0; 7];
+    rand_bytes(&mut bytes)?;
+
+    let code = base32::encode(base32::Alphabet::Crockford, &bytes).to_ascii_lowercase();
+    Ok(format!("{}-{}", &code[..5], &code[5..10]))
+}
+
+impl RecoveryCode {
+    /// Replaces the recovery codes of the user `uid` with new ones, returning them.
+    ///
+    /// This is the only time the codes themselves are available, so show them to the user!
+    pub fn regenerate(db_conn: &DbConnection, uid: i64) -> Result<Vec<String>, Error> {
+        let codes = (0..RECOVERY_CODE_COUNT)
+            .map(|_| random_code())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        db_conn.transaction::<_, Error, _>(|| {
+            RecoveryCode::delete_all_for_user(db_conn, uid)?;
+
+            let new_codes: Vec<NewRecoveryCode> = codes
+                .iter()
+                .map(|code| NewRecoveryCode {
+                    id: id_generator().next(),
+                    user_id: uid,
+                    code_hash: hash_code(code),
+                })
+                .collect();
+            diesel::insert_into(recovery_codes::table)
+                .values(&new_codes)
+                .execute(db_conn)?;
+
+            Ok(())
+        })?;
+
+        Ok(codes)
+    }
+
+    /// Uses up `code`, if it's one of the unused recovery codes of the user `uid`.
+    ///
+    /// Returns whether it was.
+    pub fn redeem(db_conn: &DbConnection, uid: i64, code: &str) -> QueryResult<bool> {
+        use crate::db::schema::recovery_codes::dsl::*;
+
+        let deleted = diesel::delete(
+            recovery_codes
+                .filter(user_id.eq(uid))
+                .filter(code_hash.eq(hash_code(code))),
+        )
+        .execute(db_conn)?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Returns how many unused recovery codes the user `uid` has left.
+    pub fn count_for_user(db_conn: &DbConnection, uid: i64) -> QueryResult<i64> {
+        use crate::db::schema::recovery_codes::dsl::*;
+
+        recovery_codes
+            .filter(user_id.eq(uid))
+            .count()
+            .get_result(db_conn)
+    }
+
+    /// Removes every recovery code of the user `uid`.
+    pub fn delete_all_for_user(db_conn: &DbConnection, uid: i64) -> QueryResult<()> {
+        use crate::db::schema::recovery_codes::dsl::*;
+
+        diesel::delete(recovery_codes.filter(user_id.eq(uid)))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_hash_the_same_however_typed() {
+        assert_eq!(hash_code("k3x9q-7bm2w"), hash_code("K3X9Q7BM2W"));
+        assert_eq!(hash_code("k3x9q-7bm2w"), hash_code(" k3x9q 7bm2w\n"));
+        assert_ne!(hash_code("k3x9q-7bm2w"), hash_code("k3x9q-7bm2x"));
+    }
+
+    #[test]
+    fn random_codes_look_right() {
+        let code = random_code().unwrap();
+        assert_eq!(code.len(), 11);
+        assert_eq!(&code[5..6], "-");
+    }
+}
@@ -0,0 +1,31 @@
+use diesel::prelude::*;
+
+use super::{Account, Block, Follow, FollowRequest, Mute};
+use crate::db::DbConnection;
+
+/// Sums up how one account relates to another.
+#[derive(Default, PartialEq, Debug)]
+pub struct Relationship {
+    pub following: bool,
+    pub followed_by: bool,
+    pub requested: bool,
+    pub blocking: bool,
+    pub muting: bool,
+}
+
+impl Relationship {
+    /// Returns the relationship of `account` to `target`.
+    pub fn between(
+        conn: &DbConnection,
+        account: &Account,
+        target: &Account,
+    ) -> QueryResult<Relationship> {
+        Ok(Relationship {
+            following: Follow::exists(conn, account.id, target.id)?,
+            followed_by: Follow::exists(conn, target.id, account.id)?,
+            requested: FollowRequest::between(conn, account.id, target.id)?.is_some(),
+            blocking: Block::exists(conn, account.id, target.id)?,
+            muting: Mute::exists(conn, account.id, target.id)?,
+        })
+    }
+}
@@ -0,0 +1,270 @@
+use chrono::offset::Utc;
+use chrono::{DateTime, Duration};
+use chrono_humanize::Humanize;
+use diesel;
+use diesel::prelude::*;
+use failure::Error;
+use lazy_static::lazy_static;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use std::env;
+use std::net::IpAddr;
+
+use super::User;
+use crate::crypto;
+use crate::db::schema::sessions;
+use crate::db::{self, id_generator, DbConnection};
+
+/// Name of the private cookie holding the session token.
+pub const SESSION_COOKIE: &str = "session";
+
+/// Represents a signed-in browser session of a local user.
+///
+/// The token is kept in a private cookie, and only its hash in the database; deleting the
+/// row signs the browser out.
+#[derive(Identifiable, Queryable, Associations, Clone, PartialEq, Debug)]
+#[belongs_to(User)]
+#[table_name = "sessions"]
+pub struct Session {
+    pub id: i64,
+    pub user_id: i64,
+    /// Hash of the session token; see [`crypto::hash_token`].
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// Represents a new session for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "sessions"]
+pub struct NewSession {
+    pub id: i64,
+    pub user_id: i64,
+    pub token_hash: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl NewSession {
+    pub fn insert(self, conn: &DbConnection) -> QueryResult<Session> {
+        use crate::db::schema::sessions::dsl::*;
+
+        diesel::insert_into(sessions).values(&self).get_result(conn)
+    }
+}
+
+/// Identifying details of the client making a request, recorded on sessions.
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+lazy_static! {
+    /// Reverse proxies we trust to tell us who they're forwarding for in `X-Real-IP`, from
+    /// the comma-separated addresses in `TRUSTED_PROXIES`. With none, the header is ignored.
+    static ref TRUSTED_PROXIES: Vec<IpAddr> = env::var("TRUSTED_PROXIES")
+        .map(|proxies| {
+            proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| proxy.parse().expect("Invalid TRUSTED_PROXIES!"))
+                .collect()
+        })
+        .unwrap_or_default();
+}
+
+/// Returns the address of the client at the other end of a connection from `remote`: that
+/// address, unless it's one of the `trusted` proxies and says who it's forwarding for in
+/// `real_ip`. Anybody else could put whatever they liked there.
+fn client_ip(remote: Option<IpAddr>, real_ip: Option<&str>, trusted: &[IpAddr]) -> Option<IpAddr> {
+    match (remote, real_ip) {
+        (Some(remote), Some(real_ip)) if trusted.contains(&remote) => {
+            Some(real_ip.trim().parse().unwrap_or(remote))
+        },
+        (remote, _) => remote,
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for ClientInfo {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<ClientInfo, ()> {
+        let ip = client_ip(
+            request.remote().map(|remote| remote.ip()),
+            request.headers().get_one("X-Real-IP"),
+            &TRUSTED_PROXIES,
+        );
+
+        Outcome::Success(ClientInfo {
+            user_agent: request.headers().get_one("User-Agent").map(String::from),
+            ip: ip.map(|ip| ip.to_string()),
+        })
+    }
+}
+
+impl Session {
+    /// Starts a new session for the user `user_id`, with a fresh random token, returning
+    /// it along with the token itself; the only time that's available.
+    pub fn create(
+        db_conn: &DbConnection,
+        user_id: i64,
+        client: ClientInfo,
+    ) -> Result<(Session, String), Error> {
+        let token = crypto::random_token()?;
+        let session = NewSession {
+            id: id_generator().next(),
+            user_id,
+            token_hash: crypto::hash_token(&token),
+            user_agent: client.user_agent,
+            ip: client.ip,
+        }
+        .insert(db_conn)?;
+
+        Ok((session, token))
+    }
+
+    /// Finds a session by its token.
+    pub fn by_token(db_conn: &DbConnection, session_token: &str) -> QueryResult<Option<Session>> {
+        use crate::db::schema::sessions::dsl::*;
+
+        sessions
+            .filter(token_hash.eq(crypto::hash_token(session_token)))
+            .first::<Session>(db_conn)
+            .optional()
+    }
+
+    /// Returns a session of the user `user_id`, given the session's ID.
+    pub fn by_user_and_id(
+        db_conn: &DbConnection,
+        uid: i64,
+        session_id: i64,
+    ) -> QueryResult<Option<Session>> {
+        use crate::db::schema::sessions::dsl::*;
+
+        sessions
+            .find(session_id)
+            .filter(user_id.eq(uid))
+            .first::<Session>(db_conn)
+            .optional()
+    }
+
+    /// Returns the sessions of the user `user_id`, most recently used first.
+    pub fn for_user(db_conn: &DbConnection, uid: i64) -> QueryResult<Vec<Session>> {
+        use crate::db::schema::sessions::dsl::*;
+
+        sessions
+            .filter(user_id.eq(uid))
+            .order(last_seen_at.desc())
+            .get_results::<Session>(db_conn)
+    }
+
+    /// Records that the session was just used.
+    ///
+    /// To save on writes, this only touches the database once a minute or so.
+    pub fn touch(&self, db_conn: &DbConnection, client: &ClientInfo) -> QueryResult<()> {
+        use crate::db::schema::sessions::dsl::*;
+
+        if Utc::now() - self.last_seen_at < Duration::minutes(1) {
+            return Ok(());
+        }
+
+        diesel::update(self)
+            .set((
+                last_seen_at.eq(Utc::now()),
+                user_agent.eq(&client.user_agent),
+                ip.eq(&client.ip),
+            ))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Ends this session.
+    pub fn delete(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        diesel::delete(self).execute(db_conn).and(Ok(()))
+    }
+
+    /// Ends every session of the user `user_id` except (if given) `keep`.
+    pub fn delete_all_for_user(
+        db_conn: &DbConnection,
+        uid: i64,
+        keep: Option<&Session>,
+    ) -> QueryResult<()> {
+        use crate::db::schema::sessions::dsl::*;
+
+        let mut query = diesel::delete(sessions.filter(user_id.eq(uid))).into_boxed();
+
+        if let Some(keep) = keep {
+            query = query.filter(id.ne(keep.id));
+        }
+
+        query.execute(db_conn).and(Ok(()))
+    }
+
+    /// Returns a human-readble description of when this session was last used.
+    pub fn humanized_last_seen(&self) -> String {
+        self.last_seen_at.humanize()
+    }
+}
+
+/// Looks up the session named by the session cookie, if there's a live one.
+///
+/// The lookup is cached for the rest of the request, so guards built on top of this one
+/// (eg `User`) can be used together without hitting the database again.
+impl<'a, 'r> FromRequest<'a, 'r> for Session {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Session, ()> {
+        use rocket::http::Status;
+
+        let db_conn = request.guard::<db::Connection>()?;
+
+        let session = request.local_cache(|| {
+            let token = request.cookies().get_private(SESSION_COOKIE)?;
+            Session::by_token(&db_conn, token.value()).ok()?
+        });
+
+        match session {
+            Some(session) => {
+                let client = request.guard::<ClientInfo>()?;
+                match session.touch(&db_conn, &client) {
+                    Ok(()) => Outcome::Success(session.clone()),
+                    Err(_) => Outcome::Failure((Status::InternalServerError, ())),
+                }
+            },
+            None => Outcome::Forward(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_only_takes_the_real_ip_from_trusted_proxies() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+
+        assert_eq!(
+            client_ip(Some(client), Some("198.51.100.1"), &[]),
+            Some(client)
+        );
+        assert_eq!(
+            client_ip(Some(client), Some("198.51.100.1"), &[proxy]),
+            Some(client)
+        );
+        assert_eq!(
+            client_ip(Some(proxy), Some(" 203.0.113.7 "), &[proxy]),
+            Some(client)
+        );
+        assert_eq!(
+            client_ip(Some(proxy), Some("nonsense"), &[proxy]),
+            Some(proxy)
+        );
+        assert_eq!(client_ip(Some(proxy), None, &[proxy]), Some(proxy));
+        assert_eq!(client_ip(None, Some("203.0.113.7"), &[proxy]), None);
+    }
+}
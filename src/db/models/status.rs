@@ -1,19 +1,24 @@
-use crate::db::DbConnection;
-use crate::BASE_URL;
+use crate::db::{id_generator, DbConnection, LOCAL_ACCOUNT_DOMAIN};
+use crate::events::{self, Event};
+use crate::transform;
+use crate::{BASE_URL, DOMAIN};
 use chrono::offset::Utc;
 use chrono::DateTime;
 use chrono_humanize::Humanize;
 use diesel;
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::slice;
 
-use super::Account;
+use super::{Account, Mute, Notification, NotificationKind};
 use crate::db::schema::statuses;
 
 /// Represents a post.
 ///
 /// A uri of None implies a local status.
-#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[derive(Identifiable, Queryable, Associations, Clone, PartialEq, Debug)]
 #[belongs_to(Account)]
 #[table_name = "statuses"]
 pub struct Status {
@@ -23,6 +28,66 @@ pub struct Status {
     pub created_at: DateTime<Utc>,
     pub account_id: i64,
     pub uri: Option<String>,
+    /// The rendered HTML of `text`, once it's been rendered.
+    pub content_html: Option<String>,
+    /// The `transform::STATUS_HTML_VERSION` which rendered `content_html`.
+    pub content_html_version: i32,
+}
+
+/// A window onto a list of statuses by their (snowflake) IDs, the way the API pages through them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Page {
+    /// Only statuses strictly older than this one.
+    pub max_id: Option<i64>,
+    /// Only statuses strictly newer than this one.
+    pub since_id: Option<i64>,
+    /// Like `since_id`, but the page starts right after this status instead of at the newest one.
+    pub min_id: Option<i64>,
+    /// How many statuses to return at most.
+    pub limit: usize,
+}
+
+impl Page {
+    /// Returns the page of the `n` newest statuses strictly older than `max_id`.
+    pub fn before(max_id: Option<i64>, n: usize) -> Page {
+        Page {
+            max_id,
+            since_id: None,
+            min_id: None,
+            limit: n,
+        }
+    }
+
+    /// Loads the statuses selected by `query` which fall into this page, newest first.
+    pub fn load(
+        &self,
+        db_conn: &DbConnection,
+        query: statuses::BoxedQuery<'static, Pg>,
+    ) -> QueryResult<Vec<Status>> {
+        use crate::db::schema::statuses::dsl;
+
+        let mut query = query;
+        if let Some(max_id) = self.max_id {
+            query = query.filter(dsl::id.lt(max_id));
+        }
+        if let Some(lower) = self.min_id.or(self.since_id) {
+            query = query.filter(dsl::id.gt(lower));
+        }
+
+        if self.min_id.is_some() {
+            let mut page = query
+                .order(dsl::id.asc())
+                .limit(self.limit as i64)
+                .get_results::<Status>(db_conn)?;
+            page.reverse();
+            Ok(page)
+        } else {
+            query
+                .order(dsl::id.desc())
+                .limit(self.limit as i64)
+                .get_results::<Status>(db_conn)
+        }
+    }
 }
 
 /// Represents a new status for insertion into the database.
@@ -34,17 +99,65 @@ pub struct NewStatus {
     pub content_warning: Option<String>,
     pub created_at: DateTime<Utc>,
     pub account_id: i64,
+    pub uri: Option<String>,
 }
 
 impl NewStatus {
+    /// Inserts the status and indexes its hashtags, publishing it on the event bus.
     pub fn insert(self, conn: &DbConnection) -> QueryResult<Status> {
         use crate::db::schema::statuses::dsl::*;
 
-        diesel::insert_into(statuses).values(&self).get_result(conn)
+        let status = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let status: Status = diesel::insert_into(statuses)
+                .values(&self)
+                .get_result(conn)?;
+            status.index_hashtags(conn)?;
+            Ok(status)
+        })?;
+        events::publish(Event::StatusCreated(status.clone()));
+        Ok(status)
     }
 }
 
+/// Escapes the characters `LIKE` patterns treat specially in `value`.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
 impl Status {
+    /// Posts a status by the local account `author`, notifying the accounts it mentions.
+    pub fn create_local(
+        db_conn: &DbConnection,
+        author: &Account,
+        text: String,
+        content_warning: Option<String>,
+    ) -> QueryResult<Status> {
+        let status = NewStatus {
+            id: id_generator().next(),
+            created_at: Utc::now(),
+            text,
+            content_warning,
+            account_id: author.id,
+            uri: None,
+        }
+        .insert(db_conn)?;
+
+        for mentioned in status.mentioned_accounts(db_conn)? {
+            Notification::notify(
+                db_conn,
+                &mentioned,
+                author,
+                NotificationKind::Mention,
+                Some(status.id),
+            )?;
+        }
+
+        Ok(status)
+    }
+
     /// Returns the `Account` which authored this status.
     pub fn account(&self, db_conn: &DbConnection) -> QueryResult<Account> {
         use crate::db::schema::accounts::dsl;
@@ -53,6 +166,113 @@ impl Status {
             .first::<Account>(db_conn)
     }
 
+    /// Deletes this status, publishing that on the event bus.
+    pub fn delete(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        diesel::delete(self).execute(db_conn)?;
+        events::publish(Event::StatusDeleted {
+            status_id:  self.id,
+            account_id: self.account_id,
+        });
+        Ok(())
+    }
+
+    /// Stores the rendered HTML of this status, so it needn't be rendered again.
+    pub fn cache_content_html(&self, db_conn: &DbConnection, html: &str) -> QueryResult<()> {
+        use crate::db::schema::statuses::dsl::{content_html, content_html_version};
+
+        diesel::update(self)
+            .set((
+                content_html.eq(html),
+                content_html_version.eq(transform::STATUS_HTML_VERSION),
+            ))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Returns the cached HTML of this status, unless there is none or it was rendered by
+    /// an older version of `transform::status`.
+    pub fn cached_content_html(&self) -> Option<&str> {
+        self.content_html
+            .as_ref()
+            .filter(|_| self.content_html_version == transform::STATUS_HTML_VERSION)
+            .map(String::as_str)
+    }
+
+    /// Forgets the cached HTML of the statuses which might mention `username`, because
+    /// whether the mention links anywhere has changed.
+    pub fn forget_content_html_mentioning(
+        db_conn: &DbConnection,
+        username: &str,
+    ) -> QueryResult<()> {
+        use crate::db::schema::statuses::dsl::*;
+
+        let mention = format!("%@{}%", escape_like(username));
+        diesel::update(statuses.filter(text.ilike(mention)))
+            .set(content_html.eq(None::<String>))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Forgets the cached HTML of the statuses which might use the custom emoji `shortcode`
+    /// from `emoji_domain` (`LOCAL_ACCOUNT_DOMAIN` for our own), because it's been added,
+    /// changed or removed.
+    pub fn forget_content_html_with_emoji(
+        db_conn: &DbConnection,
+        shortcode: &str,
+        emoji_domain: &str,
+    ) -> QueryResult<()> {
+        use crate::db::schema::accounts::dsl as accounts;
+        use crate::db::schema::statuses::dsl::*;
+
+        let mut authors = accounts::accounts
+            .filter(accounts::domain.eq(emoji_domain))
+            .select(accounts::id)
+            .into_boxed();
+        if emoji_domain == LOCAL_ACCOUNT_DOMAIN {
+            authors = authors.or_filter(accounts::domain.is_null());
+        }
+
+        let emoji = format!("%:{}:%", escape_like(shortcode));
+        diesel::update(
+            statuses
+                .filter(account_id.eq_any(authors))
+                .filter(text.like(emoji)),
+        )
+        .set(content_html.eq(None::<String>))
+        .execute(db_conn)
+        .and(Ok(()))
+    }
+
+    /// Records the hashtags used by this status, lowercased, so that `hashtag_page` can find
+    /// it. Hashtags which are already recorded are left alone.
+    pub fn index_hashtags(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::status_hashtags::dsl::*;
+
+        let mut names: Vec<String> = transform::hashtags(&self.text)
+            .iter()
+            .map(|tag| tag.to_lowercase())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let rows: Vec<_> = names
+            .into_iter()
+            .map(|tag| (status_id.eq(self.id), name.eq(tag)))
+            .collect();
+        diesel::insert_into(status_hashtags)
+            .values(&rows)
+            .on_conflict_do_nothing()
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Finds a status (local _or_ remote) by its ID.
+    pub fn by_id(db_conn: &DbConnection, status_id: i64) -> QueryResult<Option<Status>> {
+        use crate::db::schema::statuses::dsl::statuses;
+
+        statuses.find(status_id).first(db_conn).optional()
+    }
+
     /// Returns an optional status given an account ID and a status ID.
     pub fn by_account_and_id(
         db_conn: &DbConnection,
@@ -67,6 +287,98 @@ impl Status {
             .optional()
     }
 
+    /// Finds a status (local _or_ remote) by the URI of its ActivityPub object.
+    pub fn fetch_by_uri(db_conn: &DbConnection, status_uri: &str) -> QueryResult<Option<Status>> {
+        use crate::db::schema::statuses::dsl::{statuses, uri};
+
+        // local statuses live at `{actor uri}/statuses/{id}`, and don't store their URIs.
+        let mut parts = status_uri.rsplitn(3, '/');
+        if let (Some(id), Some("statuses"), Some(actor_uri)) =
+            (parts.next(), parts.next(), parts.next())
+        {
+            if let (Ok(id), Some(account)) = (
+                id.parse::<i64>(),
+                Account::fetch_local_by_uri(db_conn, actor_uri)?,
+            ) {
+                return Status::by_account_and_id(db_conn, account.id, id);
+            }
+        }
+
+        statuses
+            .filter(uri.eq(status_uri))
+            .first::<Status>(db_conn)
+            .optional()
+    }
+
+    /// Returns the known accounts mentioned in the text of this (local) status.
+    pub fn mentioned_accounts(&self, db_conn: &DbConnection) -> QueryResult<Vec<Account>> {
+        let mut mentioned = Status::mentioned_accounts_of(db_conn, slice::from_ref(self))?;
+
+        Ok(mentioned.remove(&self.id).unwrap_or_default())
+    }
+
+    /// Returns the known accounts mentioned in the text of each of `statuses`, by status ID,
+    /// looking them all up at once rather than mention by mention.
+    pub fn mentioned_accounts_of(
+        db_conn: &DbConnection,
+        statuses: &[Status],
+    ) -> QueryResult<HashMap<i64, Vec<Account>>> {
+        let mentions = statuses
+            .iter()
+            .map(|status| {
+                let mentions = transform::mentions(&status.text)
+                    .into_iter()
+                    .map(|mention| {
+                        // mentions of our own domain are of local accounts.
+                        let domain = mention
+                            .ascii_domain()
+                            .filter(|domain| domain != DOMAIN.as_str())
+                            .unwrap_or_else(|| LOCAL_ACCOUNT_DOMAIN.to_string());
+
+                        (mention.username, domain)
+                    })
+                    .collect::<Vec<_>>();
+
+                (status.id, mentions)
+            })
+            .collect::<Vec<_>>();
+
+        let mut usernames = mentions
+            .iter()
+            .flat_map(|(_, mentions)| mentions.iter().map(|(username, _)| username.clone()))
+            .collect::<Vec<_>>();
+        usernames.sort();
+        usernames.dedup();
+
+        let known = if usernames.is_empty() {
+            Vec::new()
+        } else {
+            Account::by_usernames(db_conn, &usernames)?
+        };
+
+        Ok(mentions
+            .into_iter()
+            .map(|(id, mentions)| {
+                let mut accounts: Vec<Account> = Vec::new();
+
+                for (username, domain) in mentions {
+                    let account = known.iter().find(|account| {
+                        account.username == username
+                            && account.domain.as_ref().map(String::as_str) == Some(&domain)
+                    });
+
+                    if let Some(account) = account {
+                        if !accounts.contains(account) {
+                            accounts.push(account.clone());
+                        }
+                    }
+                }
+
+                (id, accounts)
+            })
+            .collect())
+    }
+
     /// Returns the number of local statuses
     pub fn count_local(db_conn: &DbConnection) -> QueryResult<i64> {
         use crate::db::schema::statuses::dsl::{statuses, uri};
@@ -88,44 +400,132 @@ impl Status {
     }
 
     /// Returns `n` local statuses which were authored _strictly before_ the status `max_id`.
+    ///
+    /// Statuses from silenced or suspended accounts are left out.
     pub fn local_before_id(
         db_conn: &DbConnection,
         max_id: Option<i64>,
         n: usize,
     ) -> QueryResult<Vec<Status>> {
-        use crate::db::schema::statuses::dsl;
-
-        let mut query = dsl::statuses.filter(dsl::uri.is_null()).into_boxed();
-
-        if let Some(max_id) = max_id {
-            query = query.filter(dsl::id.lt(max_id));
-        }
-
-        query
-            .order(dsl::id.desc())
-            .limit(n as i64)
-            .get_results::<Status>(db_conn)
+        Status::local_page(db_conn, &Page::before(max_id, n))
     }
 
     /// Returns `n` statuses in the database, authored _strictly before_ the
     /// status `max_id`.
+    ///
+    /// Statuses from silenced or suspended accounts are left out.
     pub fn federated_before_id(
         db_conn: &DbConnection,
         max_id: Option<i64>,
         n: usize,
     ) -> QueryResult<Vec<Status>> {
-        use crate::db::schema::statuses::{all_columns, dsl};
+        Status::federated_page(db_conn, &Page::before(max_id, n))
+    }
 
-        let mut query = dsl::statuses.select(all_columns).into_boxed();
+    /// Returns the page `page` of the local timeline.
+    ///
+    /// Statuses from silenced or suspended accounts are left out.
+    pub fn local_page(db_conn: &DbConnection, page: &Page) -> QueryResult<Vec<Status>> {
+        use crate::db::schema::statuses::dsl;
 
-        if let Some(max_id) = max_id {
-            query = query.filter(dsl::id.lt(max_id));
+        let query = dsl::statuses
+            .filter(dsl::uri.is_null())
+            .filter(dsl::account_id.ne_all(Account::hidden_from_timelines()))
+            .into_boxed();
+
+        page.load(db_conn, query)
+    }
+
+    /// Returns the page `page` of the federated timeline, which has every status we know of.
+    ///
+    /// Statuses from silenced or suspended accounts are left out.
+    pub fn federated_page(db_conn: &DbConnection, page: &Page) -> QueryResult<Vec<Status>> {
+        use crate::db::schema::statuses::dsl;
+
+        let query = dsl::statuses
+            .filter(dsl::account_id.ne_all(Account::hidden_from_timelines()))
+            .into_boxed();
+
+        page.load(db_conn, query)
+    }
+
+    /// Returns the page `page` of the home timeline of `account`: its own statuses, and those
+    /// of the accounts it follows.
+    ///
+    /// Statuses from suspended accounts, or from accounts `account` mutes, are left out.
+    pub fn home_page(
+        db_conn: &DbConnection,
+        account: &Account,
+        page: &Page,
+    ) -> QueryResult<Vec<Status>> {
+        use crate::db::schema::follows::dsl as follows;
+        use crate::db::schema::statuses::dsl;
+
+        let followed = follows::follows
+            .filter(follows::source_id.eq(account.id))
+            .select(follows::target_id);
+        let query = dsl::statuses
+            .filter(
+                dsl::account_id
+                    .eq(account.id)
+                    .or(dsl::account_id.eq_any(followed)),
+            )
+            .filter(dsl::account_id.ne_all(Account::suspended_ids()))
+            .filter(dsl::account_id.ne_all(Mute::targets_of(account.id)))
+            .into_boxed();
+
+        page.load(db_conn, query)
+    }
+
+    /// Returns which of the accounts `among` have this status on their home timeline: its
+    /// author, and the accounts following its author which don't mute them.
+    ///
+    /// Whether the author is suspended isn't checked, since callers have the author to hand.
+    pub fn home_timeline_owners(
+        &self,
+        db_conn: &DbConnection,
+        among: &[i64],
+    ) -> QueryResult<Vec<i64>> {
+        use crate::db::schema::follows::dsl as follows;
+        use crate::db::schema::mutes::dsl as mutes;
+
+        let muters = mutes::mutes
+            .filter(mutes::target_id.eq(self.account_id))
+            .select(mutes::account_id);
+        let mut owners = follows::follows
+            .filter(follows::target_id.eq(self.account_id))
+            .filter(follows::source_id.eq_any(among))
+            .filter(follows::source_id.ne_all(muters))
+            .select(follows::source_id)
+            .load::<i64>(db_conn)?;
+        if among.contains(&self.account_id) {
+            owners.push(self.account_id);
         }
 
-        query
-            .order(dsl::id.desc())
-            .limit(n as i64)
-            .get_results::<Status>(db_conn)
+        Ok(owners)
+    }
+
+    /// Returns the page `page` of statuses which use the hashtag `#name`, matched regardless
+    /// of case.
+    ///
+    /// Statuses from silenced or suspended accounts are left out.
+    pub fn hashtag_page(
+        db_conn: &DbConnection,
+        name: &str,
+        page: &Page,
+    ) -> QueryResult<Vec<Status>> {
+        use crate::db::schema::status_hashtags::dsl as status_hashtags;
+        use crate::db::schema::statuses::dsl;
+
+        let tagged = status_hashtags::status_hashtags
+            .filter(status_hashtags::name.eq(name.to_lowercase()))
+            .select(status_hashtags::status_id);
+        let query = dsl::statuses
+            .filter(dsl::id.eq_any(tagged))
+            .filter(dsl::account_id.ne_all(Account::hidden_from_timelines()))
+            .into_boxed();
+
+        page.load(db_conn, query)
     }
 
     /// Returns a tuple of upper and lower bounds on the IDs of statuses authored locally
@@ -196,3 +596,41 @@ impl Status {
         .into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(html: Option<&str>, version: i32) -> Status {
+        Status {
+            id: 1,
+            text: String::new(),
+            content_warning: None,
+            created_at: Utc::now(),
+            account_id: 1,
+            uri: None,
+            content_html: html.map(String::from),
+            content_html_version: version,
+        }
+    }
+
+    #[test]
+    fn it_only_uses_html_rendered_by_this_version() {
+        let version = transform::STATUS_HTML_VERSION;
+
+        assert_eq!(
+            status(Some("<p>hi</p>"), version).cached_content_html(),
+            Some("<p>hi</p>")
+        );
+        assert_eq!(
+            status(Some("<p>hi</p>"), version - 1).cached_content_html(),
+            None
+        );
+        assert_eq!(status(None, version).cached_content_html(), None);
+    }
+
+    #[test]
+    fn it_escapes_like_patterns() {
+        assert_eq!(escape_like("a_b%c\\d"), "a\\_b\\%c\\\\d");
+    }
+}
@@ -0,0 +1,200 @@
+use chrono::offset::Utc;
+use chrono::{DateTime, Duration};
+use diesel;
+use diesel::prelude::*;
+
+use crate::db::schema::throttles;
+use crate::db::{id_generator, DbConnection};
+
+/// Counts attempts at some action (eg failed sign-ins) made by something identified by a
+/// key (eg a username or an IP address), and when it's next allowed to try.
+#[derive(Identifiable, Queryable, PartialEq, Debug)]
+#[table_name = "throttles"]
+pub struct Throttle {
+    pub id: i64,
+    pub action: String,
+    pub key: String,
+    pub hits: i32,
+    pub last_hit_at: DateTime<Utc>,
+    pub blocked_until: Option<DateTime<Utc>>,
+}
+
+/// Represents a new throttle for insertion into the database.
+#[derive(Insertable, Debug)]
+#[table_name = "throttles"]
+pub struct NewThrottle<'a> {
+    pub id: i64,
+    pub action: &'a str,
+    pub key: &'a str,
+}
+
+/// How an action is rate-limited.
+///
+/// Every key gets `free_hits` attempts; after that, each one blocks the key for twice as long
+/// as the last, starting at a second, up to `max_block_secs`. Once that's reached the key is
+/// effectively locked out until the block runs out, or a moderator clears it. Attempts are
+/// forgotten `forget_after_secs` seconds after the latest one.
+#[derive(Clone, Copy, Debug)]
+pub struct Limit {
+    pub action: &'static str,
+    pub free_hits: i32,
+    pub max_block_secs: i64,
+    pub forget_after_secs: i64,
+}
+
+/// Failed sign-ins, keyed by username and by IP address.
+pub const SIGN_IN_LIMIT: Limit = Limit {
+    action: "sign_in",
+    free_hits: 5,
+    max_block_secs: 60 * 60,
+    forget_after_secs: 24 * 60 * 60,
+};
+
+/// Sign-ups, keyed by IP address.
+pub const SIGN_UP_LIMIT: Limit = Limit {
+    action: "sign_up",
+    free_hits: 3,
+    max_block_secs: 24 * 60 * 60,
+    forget_after_secs: 24 * 60 * 60,
+};
+
+/// Emails sent on request (password resets and confirmations), keyed by address and by IP
+/// address.
+pub const EMAIL_LIMIT: Limit = Limit {
+    action: "email",
+    free_hits: 3,
+    max_block_secs: 60 * 60,
+    forget_after_secs: 24 * 60 * 60,
+};
+
+/// Returns the throttle key for a username, which is case-insensitive.
+pub fn username_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+/// Returns the throttle key for an email address, which is case-insensitive.
+pub fn email_key(email: &str) -> String {
+    format!("email:{}", email.trim().to_lowercase())
+}
+
+/// Returns the throttle key for an IP address.
+pub fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+impl Limit {
+    /// How long the `hits`th attempt blocks a key for, if at all.
+    fn block_for(self, hits: i32) -> Option<Duration> {
+        let over = hits - self.free_hits;
+        if over <= 0 {
+            return None;
+        }
+
+        // 2^(over - 1) seconds, without overflowing on the way to the cap.
+        let secs = if over > 32 {
+            self.max_block_secs
+        } else {
+            (1i64 << (over - 1)).min(self.max_block_secs)
+        };
+        Some(Duration::seconds(secs))
+    }
+
+    /// Returns when the first of `keys` that's currently blocked will be allowed again,
+    /// or `None` if none of them are blocked.
+    pub fn blocked_until(
+        self,
+        db_conn: &DbConnection,
+        keys: &[String],
+    ) -> QueryResult<Option<DateTime<Utc>>> {
+        use crate::db::schema::throttles::dsl::*;
+        use diesel::dsl::max;
+
+        throttles
+            .filter(action.eq(self.action))
+            .filter(key.eq_any(keys))
+            .filter(blocked_until.gt(Utc::now()))
+            .select(max(blocked_until))
+            .first(db_conn)
+    }
+
+    /// Records an attempt by `attempt_key`, blocking it for a while if it's made too many.
+    pub fn hit(self, db_conn: &DbConnection, attempt_key: &str) -> QueryResult<()> {
+        use crate::db::schema::throttles::dsl::*;
+
+        db_conn.transaction(|| {
+            diesel::insert_into(throttles)
+                .values(&NewThrottle {
+                    id: id_generator().next(),
+                    action: self.action,
+                    key: attempt_key,
+                })
+                .on_conflict_do_nothing()
+                .execute(db_conn)?;
+
+            let throttle: Throttle = throttles
+                .filter(action.eq(self.action))
+                .filter(key.eq(attempt_key))
+                .for_update()
+                .first(db_conn)?;
+
+            let now = Utc::now();
+            let forgotten = throttle.last_hit_at < now - Duration::seconds(self.forget_after_secs);
+            let new_hits = if forgotten { 1 } else { throttle.hits + 1 };
+
+            diesel::update(&throttle)
+                .set((
+                    hits.eq(new_hits),
+                    last_hit_at.eq(now),
+                    blocked_until.eq(self.block_for(new_hits).map(|block| now + block)),
+                ))
+                .execute(db_conn)
+                .and(Ok(()))
+        })
+    }
+
+    /// Forgets every attempt by `attempt_key`, lifting any block on it.
+    pub fn clear(self, db_conn: &DbConnection, attempt_key: &str) -> QueryResult<()> {
+        use crate::db::schema::throttles::dsl::*;
+
+        diesel::delete(
+            throttles
+                .filter(action.eq(self.action))
+                .filter(key.eq(attempt_key)),
+        )
+        .execute(db_conn)
+        .and(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_back_off_exponentially() {
+        assert_eq!(SIGN_IN_LIMIT.block_for(1), None);
+        assert_eq!(SIGN_IN_LIMIT.block_for(5), None);
+        assert_eq!(SIGN_IN_LIMIT.block_for(6), Some(Duration::seconds(1)));
+        assert_eq!(SIGN_IN_LIMIT.block_for(7), Some(Duration::seconds(2)));
+        assert_eq!(SIGN_IN_LIMIT.block_for(10), Some(Duration::seconds(16)));
+        assert_eq!(
+            SIGN_IN_LIMIT.block_for(30),
+            Some(Duration::seconds(60 * 60))
+        );
+        assert_eq!(
+            SIGN_IN_LIMIT.block_for(i32::max_value()),
+            Some(Duration::seconds(60 * 60))
+        );
+    }
+
+    #[test]
+    fn username_keys_ignore_case() {
+        assert_eq!(username_key("Alice"), username_key("alice"));
+        assert_ne!(username_key("alice"), ip_key("alice"));
+        assert_eq!(
+            email_key("Alice@Example.com"),
+            email_key("alice@example.com")
+        );
+        assert_ne!(email_key("alice"), username_key("alice"));
+    }
+}
@@ -1,13 +1,24 @@
+use crate::crypto;
 use crate::db::{self, DbConnection, LOCAL_ACCOUNT_DOMAIN};
+use chrono::offset::Utc;
+use chrono::{DateTime, Duration};
 use diesel;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
 use diesel::prelude::*;
-use pwhash::bcrypt;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
 use resopt::try_resopt;
-use rocket::outcome::IntoOutcome;
 use rocket::request::{self, FromRequest, Request};
+use std::fmt;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::str::FromStr;
 
-use super::Account;
+use super::{Account, RecoveryCode, Session};
 use crate::db::schema::users;
+use crate::password::{self, CURRENT_SCHEME};
+use crate::totp;
 
 /// Represents a local user, and information required to authenticate that user.
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
@@ -19,6 +30,86 @@ pub struct User {
     pub encrypted_password: String,
 
     pub account_id: i64,
+
+    pub role: Role,
+
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// Hash of the token emailed to confirm the address; see [`crypto::hash_token`].
+    pub confirmation_token_hash: Option<String>,
+    /// Hash of the token emailed to reset the password.
+    pub reset_password_token_hash: Option<String>,
+    pub reset_password_sent_at: Option<DateTime<Utc>>,
+
+    pub otp_secret: Option<String>,
+    pub otp_enabled: bool,
+    /// The TOTP time step of the last code accepted from this user; see [`totp::verify`].
+    pub otp_last_step: i64,
+
+    pub approved: bool,
+}
+
+/// How long a password reset link stays valid, in hours.
+pub const RESET_PASSWORD_VALIDITY_HOURS: i64 = 2;
+
+/// The privileges a local user holds on this instance.
+///
+/// Variants are ordered from least to most privileged, so roles can be compared
+/// with `>=` to check whether a user is at least, eg, a moderator.
+#[derive(AsExpression, FromSqlRow, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[sql_type = "Text"]
+pub enum Role {
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    /// Returns the name of this role, as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// Returns true if someone with this role may take moderation actions against an
+    /// account held by someone with the `other` role; only against less privileged ones.
+    pub fn outranks(self, other: Role) -> bool {
+        self > other
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Role::User),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("unknown role {}", s)),
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for Role {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for Role {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let role = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Ok(role.parse()?)
+    }
 }
 
 /// Represents a new user for insertion into the database.
@@ -30,6 +121,9 @@ pub struct NewUser {
     pub encrypted_password: String,
 
     pub account_id: i64,
+
+    pub confirmation_token_hash: Option<String>,
+    pub approved: bool,
 }
 
 impl NewUser {
@@ -46,7 +140,7 @@ impl User {
     where
         S: AsRef<str>,
     {
-        bcrypt::verify(password.as_ref(), &self.encrypted_password)
+        password::verify(password.as_ref(), &self.encrypted_password)
     }
 
     /// Returns the hash of a plaintext password, for storage in the database.
@@ -54,7 +148,9 @@ impl User {
     where
         S: AsRef<str>,
     {
-        bcrypt::hash(password.as_ref()).expect("Couldn't hash password!")
+        CURRENT_SCHEME
+            .hash(password.as_ref())
+            .expect("Couldn't hash password!")
     }
 
     /// Finds a local user by their username, returning an `Option<User>`.
@@ -78,6 +174,55 @@ impl User {
             .optional()
     }
 
+    /// Finds a local user by their email address, returning an `Option<User>`.
+    pub fn by_email(db_conn: &DbConnection, address: &str) -> QueryResult<Option<User>> {
+        use crate::db::schema::users::dsl::*;
+
+        users
+            .filter(email.eq(address))
+            .first::<User>(db_conn)
+            .optional()
+    }
+
+    /// Finds the user a confirmation email was sent to, given the token in it.
+    pub fn by_confirmation_token(db_conn: &DbConnection, token: &str) -> QueryResult<Option<User>> {
+        use crate::db::schema::users::dsl::*;
+
+        let user = users
+            .filter(confirmation_token_hash.eq(crypto::hash_token(token)))
+            .first::<User>(db_conn)
+            .optional()?;
+
+        Ok(user.filter(|user| {
+            user.confirmation_token_hash
+                .as_ref()
+                .map_or(false, |hash| crypto::token_matches(token, hash))
+        }))
+    }
+
+    /// Finds the user a password reset link was sent to, given the token in it.
+    ///
+    /// Tokens older than [`RESET_PASSWORD_VALIDITY_HOURS`] aren't valid anymore.
+    pub fn by_reset_password_token(
+        db_conn: &DbConnection,
+        token: &str,
+    ) -> QueryResult<Option<User>> {
+        use crate::db::schema::users::dsl::*;
+
+        let cutoff = Utc::now() - Duration::hours(RESET_PASSWORD_VALIDITY_HOURS);
+        let user = users
+            .filter(reset_password_token_hash.eq(crypto::hash_token(token)))
+            .filter(reset_password_sent_at.gt(cutoff))
+            .first::<User>(db_conn)
+            .optional()?;
+
+        Ok(user.filter(|user| {
+            user.reset_password_token_hash
+                .as_ref()
+                .map_or(false, |hash| crypto::token_matches(token, hash))
+        }))
+    }
+
     /// Finds a local user by their ID, returning an `Option<User>`.
     pub fn by_id(db_conn: &DbConnection, uid: i64) -> QueryResult<Option<User>> {
         use crate::db::schema::users::dsl::*;
@@ -85,6 +230,242 @@ impl User {
         users.find(uid).first(db_conn).optional()
     }
 
+    /// Finds the local user an account belongs to, if it is a local account.
+    pub fn by_account_id(db_conn: &DbConnection, account: i64) -> QueryResult<Option<User>> {
+        use crate::db::schema::users::dsl::*;
+
+        users
+            .filter(account_id.eq(account))
+            .first::<User>(db_conn)
+            .optional()
+    }
+
+    /// Returns true if the account belonging to this user has been suspended.
+    pub fn is_suspended(&self, db_conn: &DbConnection) -> QueryResult<bool> {
+        use crate::db::schema::accounts::dsl::*;
+
+        accounts
+            .find(self.account_id)
+            .select(suspended)
+            .first(db_conn)
+    }
+
+    /// Changes the password of this user, signing out all of their sessions but `keep`.
+    pub fn set_password(
+        &self,
+        db_conn: &DbConnection,
+        password: &str,
+        keep: Option<&Session>,
+    ) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::encrypted_password;
+
+        db_conn.transaction(|| {
+            diesel::update(self)
+                .set(encrypted_password.eq(User::encrypt_password(password)))
+                .execute(db_conn)?;
+
+            Session::delete_all_for_user(db_conn, self.id, keep)
+        })
+    }
+
+    /// Re-hashes this user's password with the current scheme, if their stored hash was made
+    /// with another one (or with other parameters). `password` must already have been checked.
+    ///
+    /// Unlike `set_password`, this leaves every session alone.
+    pub fn upgrade_password_hash(&self, db_conn: &DbConnection, password: &str) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::encrypted_password;
+
+        if CURRENT_SCHEME.is_current(&self.encrypted_password) {
+            return Ok(());
+        }
+
+        diesel::update(self)
+            .set(encrypted_password.eq(User::encrypt_password(password)))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Returns true if this user has confirmed their email address.
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+
+    /// Marks the email address of this user as confirmed.
+    pub fn confirm(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::*;
+
+        diesel::update(self)
+            .set((
+                confirmed_at.eq(Some(self.confirmed_at.unwrap_or_else(Utc::now))),
+                confirmation_token_hash.eq(None::<String>),
+            ))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Makes `token` the confirmation token for this user, to be emailed to them.
+    pub fn set_confirmation_token(&self, db_conn: &DbConnection, token: &str) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::*;
+
+        diesel::update(self)
+            .set(confirmation_token_hash.eq(crypto::hash_token(token)))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Starts a password reset for this user, valid for [`RESET_PASSWORD_VALIDITY_HOURS`].
+    pub fn set_reset_password_token(&self, db_conn: &DbConnection, token: &str) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::*;
+
+        diesel::update(self)
+            .set((
+                reset_password_token_hash.eq(crypto::hash_token(token)),
+                reset_password_sent_at.eq(Utc::now()),
+            ))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Resets the password of this user, signing out all of their sessions.
+    ///
+    /// As the reset link was emailed to them, this also confirms their email address.
+    pub fn reset_password(&self, db_conn: &DbConnection, password: &str) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::*;
+
+        db_conn.transaction(|| {
+            self.set_password(db_conn, password, None)?;
+            self.confirm(db_conn)?;
+
+            diesel::update(self)
+                .set((
+                    reset_password_token_hash.eq(None::<String>),
+                    reset_password_sent_at.eq(None::<DateTime<Utc>>),
+                ))
+                .execute(db_conn)
+                .and(Ok(()))
+        })
+    }
+
+    /// Stores a new TOTP secret for this user, which doesn't take effect until
+    /// [`enable_two_factor`](#method.enable_two_factor) is called.
+    pub fn set_otp_secret(&self, db_conn: &DbConnection, secret: &str) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::*;
+
+        diesel::update(self)
+            .set((
+                otp_secret.eq(secret),
+                otp_enabled.eq(false),
+                otp_last_step.eq(0),
+            ))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Checks a TOTP code against this user's secret (even if 2FA isn't enabled yet), and
+    /// uses it up; a code is only accepted once, and never if it's older than the last one.
+    pub fn redeem_otp_code(&self, db_conn: &DbConnection, code: &str) -> QueryResult<bool> {
+        use crate::db::schema::users::dsl::*;
+
+        let step = match &self.otp_secret {
+            Some(secret) => totp::verify(secret, code, Utc::now().timestamp()),
+            None => None,
+        };
+        let step = match step {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+
+        // moving the step on in the database (rather than checking the one we loaded) means
+        // the same code sent twice at once still only works once.
+        let updated = diesel::update(self)
+            .filter(otp_last_step.lt(step))
+            .set(otp_last_step.eq(step))
+            .execute(db_conn)?;
+
+        Ok(updated == 1)
+    }
+
+    /// Starts asking this user for a TOTP code when they sign in.
+    pub fn enable_two_factor(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::*;
+
+        diesel::update(self)
+            .set(otp_enabled.eq(true))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Turns off two-factor authentication for this user, throwing away their secret
+    /// and recovery codes.
+    pub fn disable_two_factor(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::*;
+
+        db_conn.transaction(|| {
+            diesel::update(self)
+                .set((otp_secret.eq(None::<String>), otp_enabled.eq(false)))
+                .execute(db_conn)?;
+
+            RecoveryCode::delete_all_for_user(db_conn, self.id)
+        })
+    }
+
+    /// Changes the role of this user.
+    pub fn set_role(&self, db_conn: &DbConnection, new_role: Role) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::role;
+
+        diesel::update(self)
+            .set(role.eq(new_role))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Returns the users waiting for a moderator to approve them, with their accounts,
+    /// oldest first.
+    pub fn awaiting_approval(db_conn: &DbConnection) -> QueryResult<Vec<(User, Account)>> {
+        use crate::db::schema::{accounts, users};
+
+        users::table
+            .inner_join(accounts::table)
+            .filter(users::approved.eq(false))
+            .order(users::id.asc())
+            .load(db_conn)
+    }
+
+    /// Lets this user sign in, if they were waiting for approval.
+    pub fn approve(&self, db_conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::users::dsl::approved;
+
+        diesel::update(self)
+            .set(approved.eq(true))
+            .execute(db_conn)
+            .and(Ok(()))
+    }
+
+    /// Turns down this user's sign-up, deleting them and their account.
+    ///
+    /// Only meant for users awaiting approval. Others may have followed, mentioned or
+    /// blocked the account meanwhile; those follows go along with it here, and the rest
+    /// through their foreign keys.
+    pub fn reject(self, db_conn: &DbConnection) -> QueryResult<()> {
+        use crate::db::schema::accounts::dsl::accounts;
+        use crate::db::schema::follows::dsl::{follows, source_id, target_id};
+
+        db_conn.transaction(|| {
+            diesel::delete(
+                follows.filter(
+                    source_id
+                        .eq(self.account_id)
+                        .or(target_id.eq(self.account_id)),
+                ),
+            )
+            .execute(db_conn)?;
+            diesel::delete(&self).execute(db_conn)?;
+            diesel::delete(accounts.find(self.account_id))
+                .execute(db_conn)
+                .and(Ok(()))
+        })
+    }
+
     /// Returns the number of local users.
     pub fn count(db_conn: &DbConnection) -> QueryResult<i64> {
         use crate::db::schema::users::dsl::users;
@@ -116,17 +497,116 @@ impl<'a, 'r> FromRequest<'a, 'r> for User {
         use rocket::Outcome;
 
         let db_conn = request.guard::<db::Connection>()?;
+        let session = request.guard::<Session>()?;
 
-        let uid = request
-            .cookies()
-            .get_private("uid")
-            .and_then(|cookie| cookie.value().parse::<i64>().ok())
-            .or_forward(())?;
+        let user = match User::by_id(&db_conn, session.user_id) {
+            Ok(Some(user)) => user,
+            Ok(None) => return Outcome::Forward(()),
+            Err(_) => return Outcome::Failure((Status::InternalServerError, ())),
+        };
 
-        match User::by_id(&db_conn, uid) {
-            Ok(Some(user)) => Outcome::Success(user),
-            Ok(None) => Outcome::Forward(()),
+        // suspended users keep their session, but can't act as themselves anymore.
+        match user.is_suspended(&db_conn) {
+            Ok(false) => Outcome::Success(user),
+            Ok(true) => Outcome::Failure((Status::Forbidden, ())),
             Err(_) => Outcome::Failure((Status::InternalServerError, ())),
         }
     }
 }
+
+/// A minimum [`Role`] which a [`RequireRole`] guard will check for.
+pub trait RoleRequirement {
+    const MINIMUM: Role;
+}
+
+/// Marker for [`RequireRole`]; accepts moderators and admins.
+pub enum ModeratorRole {}
+
+impl RoleRequirement for ModeratorRole {
+    const MINIMUM: Role = Role::Moderator;
+}
+
+/// Marker for [`RequireRole`]; accepts only admins.
+pub enum AdminRole {}
+
+impl RoleRequirement for AdminRole {
+    const MINIMUM: Role = Role::Admin;
+}
+
+/// Request guard for a signed-in [`User`] whose role is at least `R::MINIMUM`.
+///
+/// Fails with `Forbidden` if the user is signed in but doesn't have a sufficient role.
+pub struct RequireRole<R: RoleRequirement>(pub User, PhantomData<R>);
+
+/// Request guard which only succeeds for moderators and admins.
+pub type Moderator = RequireRole<ModeratorRole>;
+
+/// Request guard which only succeeds for admins.
+pub type Admin = RequireRole<AdminRole>;
+
+impl<'a, 'r, R: RoleRequirement> FromRequest<'a, 'r> for RequireRole<R> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<RequireRole<R>, ()> {
+        use rocket::http::Status;
+        use rocket::Outcome;
+
+        let user = request.guard::<User>()?;
+
+        if user.role >= R::MINIMUM {
+            Outcome::Success(RequireRole(user, PhantomData))
+        } else {
+            Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
+
+/// Request guard for a signed-in [`User`] who has confirmed their email address.
+///
+/// Fails with `Forbidden` if the user is signed in but hasn't confirmed yet.
+pub struct Confirmed(pub User);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Confirmed {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Confirmed, ()> {
+        use rocket::http::Status;
+        use rocket::Outcome;
+
+        let user = request.guard::<User>()?;
+
+        if user.is_confirmed() {
+            Outcome::Success(Confirmed(user))
+        } else {
+            Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_round_trip_through_strings() {
+        for role in &[Role::User, Role::Moderator, Role::Admin] {
+            assert_eq!(role.as_str().parse::<Role>(), Ok(*role));
+        }
+        assert!("superuser".parse::<Role>().is_err());
+    }
+
+    #[test]
+    fn roles_are_ordered_by_privilege() {
+        assert!(Role::Admin > Role::Moderator);
+        assert!(Role::Moderator > Role::User);
+    }
+
+    #[test]
+    fn roles_only_outrank_less_privileged_roles() {
+        assert!(Role::Admin.outranks(Role::Moderator));
+        assert!(Role::Moderator.outranks(Role::User));
+        assert!(!Role::Moderator.outranks(Role::Moderator));
+        assert!(!Role::Moderator.outranks(Role::Admin));
+        assert!(!Role::Admin.outranks(Role::Admin));
+    }
+}
@@ -51,6 +51,162 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         privkey -> Nullable<Bytea>,
+        /// The `silenced` column of the `accounts` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        silenced -> Bool,
+        /// The `suspended` column of the `accounts` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        suspended -> Bool,
+        /// The `force_sensitive` column of the `accounts` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        force_sensitive -> Bool,
+        /// The `locked` column of the `accounts` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locked -> Bool,
+        /// The `inbox_uri` column of the `accounts` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        inbox_uri -> Nullable<Varchar>,
+        /// The `created_at` column of the `accounts` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    /// Representation of the `blocks` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    blocks (id) {
+        /// The `id` column of the `blocks` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `account_id` column of the `blocks` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        account_id -> Int8,
+        /// The `target_id` column of the `blocks` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target_id -> Int8,
+    }
+}
+
+table! {
+    /// Representation of the `custom_emojis` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    custom_emojis (id) {
+        /// The `id` column of the `custom_emojis` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `shortcode` column of the `custom_emojis` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        shortcode -> Varchar,
+        /// The `domain` column of the `custom_emojis` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        domain -> Varchar,
+        /// The `image` column of the `custom_emojis` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        image -> Varchar,
+        /// The `uri` column of the `custom_emojis` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        uri -> Nullable<Varchar>,
+        /// The `remote_image_url` column of the `custom_emojis` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        remote_image_url -> Nullable<Varchar>,
+        /// The `created_at` column of the `custom_emojis` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `updated_at` column of the `custom_emojis` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    /// Representation of the `follow_requests` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    follow_requests (id) {
+        /// The `id` column of the `follow_requests` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `source_id` column of the `follow_requests` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        source_id -> Int8,
+        /// The `target_id` column of the `follow_requests` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target_id -> Int8,
+        /// The `uri` column of the `follow_requests` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        uri -> Nullable<Varchar>,
+        /// The `created_at` column of the `follow_requests` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
     }
 }
 
@@ -80,6 +236,390 @@ table! {
     }
 }
 
+table! {
+    /// Representation of the `invites` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    invites (id) {
+        /// The `id` column of the `invites` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `code` column of the `invites` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code -> Varchar,
+        /// The `user_id` column of the `invites` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int8,
+        /// The `max_uses` column of the `invites` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        max_uses -> Nullable<Int4>,
+        /// The `uses` column of the `invites` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        uses -> Int4,
+        /// The `expires_at` column of the `invites` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamptz>,
+        /// The `created_at` column of the `invites` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    /// Representation of the `mutes` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    mutes (id) {
+        /// The `id` column of the `mutes` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `account_id` column of the `mutes` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        account_id -> Int8,
+        /// The `target_id` column of the `mutes` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        target_id -> Int8,
+    }
+}
+
+table! {
+    /// Representation of the `notifications` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    notifications (id) {
+        /// The `id` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `account_id` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        account_id -> Int8,
+        /// The `from_account_id` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        from_account_id -> Int8,
+        /// The `kind` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Varchar,
+        /// The `status_id` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status_id -> Nullable<Int8>,
+        /// The `created_at` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `read` column of the `notifications` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        read -> Bool,
+    }
+}
+
+table! {
+    /// Representation of the `oauth_access_tokens` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    oauth_access_tokens (id) {
+        /// The `id` column of the `oauth_access_tokens` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `token_hash` column of the `oauth_access_tokens` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_hash -> Varchar,
+        /// The `application_id` column of the `oauth_access_tokens` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        application_id -> Int8,
+        /// The `user_id` column of the `oauth_access_tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Nullable<Int8>,
+        /// The `scopes` column of the `oauth_access_tokens` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        scopes -> Varchar,
+        /// The `created_at` column of the `oauth_access_tokens` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    /// Representation of the `oauth_applications` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    oauth_applications (id) {
+        /// The `id` column of the `oauth_applications` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `name` column of the `oauth_applications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `website` column of the `oauth_applications` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        website -> Nullable<Varchar>,
+        /// The `redirect_uris` column of the `oauth_applications` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        redirect_uris -> Text,
+        /// The `scopes` column of the `oauth_applications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        scopes -> Varchar,
+        /// The `client_id` column of the `oauth_applications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        client_id -> Varchar,
+        /// The `client_secret_hash` column of the `oauth_applications` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        client_secret_hash -> Varchar,
+        /// The `created_at` column of the `oauth_applications` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    /// Representation of the `oauth_authorization_codes` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    oauth_authorization_codes (id) {
+        /// The `id` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `code_hash` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_hash -> Varchar,
+        /// The `application_id` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        application_id -> Int8,
+        /// The `user_id` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int8,
+        /// The `redirect_uri` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        redirect_uri -> Text,
+        /// The `scopes` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        scopes -> Varchar,
+        /// The `code_challenge` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_challenge -> Nullable<Varchar>,
+        /// The `code_challenge_method` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_challenge_method -> Nullable<Varchar>,
+        /// The `expires_at` column of the `oauth_authorization_codes` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Timestamptz,
+    }
+}
+
+table! {
+    /// Representation of the `recovery_codes` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    recovery_codes (id) {
+        /// The `id` column of the `recovery_codes` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `user_id` column of the `recovery_codes` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int8,
+        /// The `code_hash` column of the `recovery_codes` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_hash -> Varchar,
+    }
+}
+
+table! {
+    /// Representation of the `sessions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    sessions (id) {
+        /// The `id` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `user_id` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Int8,
+        /// The `token_hash` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        token_hash -> Varchar,
+        /// The `created_at` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `last_seen_at` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_seen_at -> Timestamptz,
+        /// The `user_agent` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_agent -> Nullable<Varchar>,
+        /// The `ip` column of the `sessions` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        ip -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    /// Representation of the `status_hashtags` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    status_hashtags (status_id, name) {
+        /// The `status_id` column of the `status_hashtags` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status_id -> Int8,
+        /// The `name` column of the `status_hashtags` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+    }
+}
+
 table! {
     /// Representation of the `statuses` table.
     ///
@@ -121,6 +661,62 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         uri -> Nullable<Text>,
+        /// The `content_html` column of the `statuses` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        content_html -> Nullable<Text>,
+        /// The `content_html_version` column of the `statuses` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        content_html_version -> Int4,
+    }
+}
+
+table! {
+    /// Representation of the `throttles` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    throttles (id) {
+        /// The `id` column of the `throttles` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `action` column of the `throttles` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Varchar,
+        /// The `key` column of the `throttles` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        key -> Varchar,
+        /// The `hits` column of the `throttles` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        hits -> Int4,
+        /// The `last_hit_at` column of the `throttles` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_hit_at -> Timestamptz,
+        /// The `blocked_until` column of the `throttles` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        blocked_until -> Nullable<Timestamptz>,
     }
 }
 
@@ -153,10 +749,91 @@ table! {
         ///
         /// (Automatically generated by Diesel.)
         account_id -> Int8,
+        /// The `role` column of the `users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role -> Varchar,
+        /// The `confirmed_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        confirmed_at -> Nullable<Timestamptz>,
+        /// The `confirmation_token_hash` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        confirmation_token_hash -> Nullable<Varchar>,
+        /// The `reset_password_token_hash` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reset_password_token_hash -> Nullable<Varchar>,
+        /// The `reset_password_sent_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reset_password_sent_at -> Nullable<Timestamptz>,
+        /// The `otp_secret` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        otp_secret -> Nullable<Varchar>,
+        /// The `otp_enabled` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        otp_enabled -> Bool,
+        /// The `otp_last_step` column of the `users` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        otp_last_step -> Int8,
+        /// The `approved` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        approved -> Bool,
     }
 }
 
+joinable!(invites -> users (user_id));
+joinable!(notifications -> statuses (status_id));
+joinable!(oauth_access_tokens -> oauth_applications (application_id));
+joinable!(oauth_access_tokens -> users (user_id));
+joinable!(oauth_authorization_codes -> oauth_applications (application_id));
+joinable!(oauth_authorization_codes -> users (user_id));
+joinable!(recovery_codes -> users (user_id));
+joinable!(sessions -> users (user_id));
+joinable!(status_hashtags -> statuses (status_id));
 joinable!(statuses -> accounts (account_id));
 joinable!(users -> accounts (account_id));
 
-allow_tables_to_appear_in_same_query!(accounts, follows, statuses, users,);
+allow_tables_to_appear_in_same_query!(
+    accounts,
+    blocks,
+    custom_emojis,
+    follow_requests,
+    follows,
+    invites,
+    mutes,
+    notifications,
+    oauth_access_tokens,
+    oauth_applications,
+    oauth_authorization_codes,
+    recovery_codes,
+    sessions,
+    status_hashtags,
+    statuses,
+    throttles,
+    users,
+);
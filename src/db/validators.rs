@@ -1,7 +1,130 @@
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::env;
+use unicode_segmentation::UnicodeSegmentation;
+use validator::ValidationError;
+
+/// Passwords must be at least this many characters long.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Passwords can be at most this many characters long, so hashing them stays cheap.
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+/// Statuses can be at most this many characters long, unless `MAX_STATUS_LENGTH` says otherwise.
+pub const DEFAULT_MAX_STATUS_LENGTH: usize = 500;
 
 lazy_static! {
+    /// Statuses can be at most this many characters long, counted by `posticle::count`.
+    pub static ref MAX_STATUS_LENGTH: usize = env::var("MAX_STATUS_LENGTH")
+        .map(|max| max.parse().expect("Invalid MAX_STATUS_LENGTH!"))
+        .unwrap_or(DEFAULT_MAX_STATUS_LENGTH);
+
+    /// Custom emoji shortcodes (between the colons) must be matched by this regex.
+    pub static ref VALID_EMOJI_SHORTCODE_RE: Regex = Regex::new(r"^[a-zA-Z0-9_]{2,}$").unwrap();
+
     /// During registrations, usernames must be matched by this regex to be considered valid.
     pub static ref VALID_USERNAME_RE: Regex = Regex::new(r"^[[:alnum:]_]+$").unwrap();
+
+    /// Passwords too common to be allowed, lowercased.
+    static ref COMMON_PASSWORDS: HashSet<String> =
+        include_str!("../../data/common_passwords.txt")
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+}
+
+/// Checks a new password against our password policy: it must be a reasonable length,
+/// and not be one of the passwords everybody tries first.
+pub fn validate_password(password: &str) -> Result<(), ValidationError> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH || length > MAX_PASSWORD_LENGTH {
+        let mut e = ValidationError::new("password_length");
+        e.message = Some(Cow::from(format!(
+            "Password must be between {} and {} characters long.",
+            MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+        )));
+        return Err(e);
+    }
+
+    if COMMON_PASSWORDS.contains(&password.to_lowercase()) {
+        let mut e = ValidationError::new("password_common");
+        e.message = Some(Cow::from(
+            "That password is too common; please pick something harder to guess.",
+        ));
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Checks a new status isn't longer than `MAX_STATUS_LENGTH`, counting its content warning
+/// along with its content, and characters the way Mastodon does: as grapheme clusters, so an
+/// emoji made of several code points counts once.
+pub fn validate_status_length(content: &str, content_warning: &str) -> Result<(), ValidationError> {
+    let content_warning_length = content_warning.graphemes(true).count();
+
+    if posticle::count(content) + content_warning_length > *MAX_STATUS_LENGTH {
+        let mut e = ValidationError::new("status_length");
+        e.message = Some(Cow::from(format!(
+            "Content and content warning must be at most {} characters long together.",
+            *MAX_STATUS_LENGTH
+        )));
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_enforces_the_password_length() {
+        assert!(validate_password("short").is_err());
+        assert!(validate_password(&"x".repeat(MAX_PASSWORD_LENGTH + 1)).is_err());
+        assert!(validate_password("correct horse battery staple").is_ok());
+    }
+
+    #[test]
+    fn it_refuses_common_passwords() {
+        assert!(!COMMON_PASSWORDS.is_empty());
+        assert!(validate_password("password123").is_err());
+        assert!(validate_password("PassWord123").is_err());
+        assert!(validate_password("qwertyuiop").is_err());
+    }
+
+    #[test]
+    fn it_enforces_the_status_length() {
+        assert!(validate_status_length(&"x".repeat(*MAX_STATUS_LENGTH), "").is_ok());
+        assert!(validate_status_length(&"x".repeat(*MAX_STATUS_LENGTH + 1), "").is_err());
+
+        // links count as 23 characters, however long they are.
+        let link = format!("https://example.com/{}", "x".repeat(*MAX_STATUS_LENGTH));
+        assert!(validate_status_length(&link, "").is_ok());
+    }
+
+    #[test]
+    fn it_counts_the_content_warning_towards_the_status_length() {
+        let half = *MAX_STATUS_LENGTH / 2;
+        let rest = *MAX_STATUS_LENGTH - half;
+
+        assert!(validate_status_length(&"x".repeat(half), &"y".repeat(rest)).is_ok());
+        assert!(validate_status_length(&"x".repeat(half), &"y".repeat(rest + 1)).is_err());
+        assert!(validate_status_length("x", &"y".repeat(*MAX_STATUS_LENGTH)).is_err());
+    }
+
+    #[test]
+    fn it_counts_emoji_in_the_content_warning_once() {
+        // a family: four people joined by zero width joiners, seven code points in all.
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+        let content = "x".repeat(*MAX_STATUS_LENGTH - 2);
+
+        assert!(validate_status_length(&content, &format!("{} ", family)).is_ok());
+        assert!(validate_status_length(&content, &format!("{}  ", family)).is_err());
+    }
 }
@@ -0,0 +1,71 @@
+//! An in-process bus for things other parts of the server want to hear about as they
+//! happen, like the streaming API.
+//!
+//! Events are published as soon as the change is made, so one made in a transaction which is
+//! later rolled back is published all the same. Subscribers should cope with that.
+
+use lazy_static::lazy_static;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use crate::db::models::{Notification, Status};
+
+/// Something that happened.
+#[derive(Debug)]
+pub enum Event {
+    /// A status was posted here, or arrived from another instance.
+    StatusCreated(Status),
+    /// The status `status_id`, by the account `account_id`, was deleted.
+    StatusDeleted { status_id: i64, account_id: i64 },
+    /// A local account got a notification.
+    Notified(Notification),
+}
+
+lazy_static! {
+    static ref SUBSCRIBERS: Mutex<Vec<Sender<Arc<Event>>>> = Mutex::new(Vec::new());
+}
+
+/// Returns a receiver which gets every event published from now on.
+///
+/// Dropping the receiver unsubscribes it.
+pub fn subscribe() -> Receiver<Arc<Event>> {
+    let (sender, receiver) = channel();
+    SUBSCRIBERS.lock().unwrap().push(sender);
+    receiver
+}
+
+/// Hands `event` to every subscriber, forgetting the ones which have gone away.
+pub fn publish(event: Event) {
+    let event = Arc::new(event);
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|subscriber| subscriber.send(Arc::clone(&event)).is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_delivers_events_to_subscribers() {
+        let receiver = subscribe();
+        let dropped = subscribe();
+        drop(dropped);
+
+        publish(Event::StatusDeleted {
+            status_id:  1,
+            account_id: 2,
+        });
+
+        // other tests may publish events too, so look for ours among them.
+        let delivered = receiver.try_iter().any(|event| match *event {
+            Event::StatusDeleted {
+                status_id: 1,
+                account_id: 2,
+            } => true,
+            _ => false,
+        });
+        assert!(delivered);
+    }
+}
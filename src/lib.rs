@@ -16,7 +16,14 @@ mod activitypub;
 pub mod crypto;
 pub mod db;
 mod error;
+pub mod events;
+pub mod mailer;
+mod oauth;
+mod password;
+mod registrations;
 mod routes;
+pub mod streaming;
+mod totp;
 mod transform;
 mod util;
 
@@ -28,6 +35,7 @@ use slog::slog_o;
 use slog::Drain;
 use slog_scope::warn;
 use std::env;
+use std::path::PathBuf;
 
 lazy_static! {
     pub static ref BASE_URL: String = format!(
@@ -35,6 +43,10 @@ lazy_static! {
         env::var("DOMAIN").expect("DOMAIN must be set")
     );
     pub static ref DOMAIN: String = env::var("DOMAIN").expect("DOMAIN must be set");
+
+    /// Where uploaded files, like the images of custom emoji, are kept.
+    pub static ref MEDIA_DIR: PathBuf =
+        PathBuf::from(env::var("MEDIA_DIR").unwrap_or_else(|_| "media".to_string()));
 }
 
 pub const GIT_REV: &str = include_str!(concat!(env!("OUT_DIR"), "/commit-info.txt"));
@@ -80,12 +92,15 @@ fn rocket_load_config() -> Config {
     config
 }
 
-pub fn app(db: db::Pool, logger: slog::Logger) -> Rocket {
+pub fn app(db: db::Pool, mailer: Box<dyn mailer::Mailer>, logger: slog::Logger) -> Rocket {
     rocket::custom(rocket_load_config()) // use our own config loading which turns off Rocket's built-in logging.
         .mount("/", routes::ui::routes())
         .mount("/", routes::ap::routes())
         .mount("/", routes::well_known::routes())
+        .mount("/", routes::api::routes())
+        .register(routes::ui::catchers())
         .manage(db) // store the db pool as Rocket managed state
                     // (this lets us use the db::Connection guard)
+        .manage(mailer)
         .attach(SlogFairing::new(logger))
 }
@@ -0,0 +1,192 @@
+//! Sending email, eg for confirming addresses and resetting passwords.
+//!
+//! What actually happens to a message depends on the configured [`Mailer`]; see
+//! [`from_env`](fn.from_env.html).
+
+use askama::Template;
+use chrono::offset::Utc;
+use failure::{format_err, Error};
+use lettre::smtp::authentication::Credentials;
+use lettre::{SmtpClient, SmtpTransport, Transport};
+use lettre_email::EmailBuilder;
+use slog_scope::info;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::DOMAIN;
+
+/// A plain-text email, ready to be sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Email {
+    /// Renders the body of a message from the template `body`.
+    pub fn new<T: Template>(to: &str, subject: &str, body: &T) -> Result<Email, Error> {
+        Ok(Email {
+            to: to.to_string(),
+            subject: subject.to_string(),
+            body: body.render()?,
+        })
+    }
+}
+
+/// Something that can deliver emails.
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Email) -> Result<(), Error>;
+}
+
+/// Delivers email through an SMTP server, over TLS.
+pub struct SmtpMailer {
+    from: String,
+    transport: Mutex<SmtpTransport>,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        host: &str,
+        credentials: Option<(String, String)>,
+        from: String,
+    ) -> Result<SmtpMailer, Error> {
+        let mut client = SmtpClient::new_simple(host)?;
+        if let Some((username, password)) = credentials {
+            client = client.credentials(Credentials::new(username, password));
+        }
+
+        Ok(SmtpMailer {
+            from,
+            transport: Mutex::new(client.transport()),
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<(), Error> {
+        let message = EmailBuilder::new()
+            .to(email.to.as_str())
+            .from(self.from.as_str())
+            .subject(email.subject.as_str())
+            .text(email.body.as_str())
+            .build()?;
+
+        let mut transport = self
+            .transport
+            .lock()
+            .map_err(|_| format_err!("SMTP transport poisoned"))?;
+        transport.send(message.into())?;
+
+        Ok(())
+    }
+}
+
+/// Writes every email to a file in `dir` (and logs where), rather than sending it anywhere.
+///
+/// Meant for development and tests.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<FileMailer, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        Ok(FileMailer { dir })
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: &Email) -> Result<(), Error> {
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S%.f"),
+            email.to.replace(
+                |c: char| !c.is_ascii_alphanumeric() && c != '@' && c != '.',
+                "_"
+            )
+        ));
+
+        fs::write(
+            &path,
+            format!(
+                "To: {}\nSubject: {}\n\n{}",
+                email.to, email.subject, email.body
+            ),
+        )?;
+        info!("Wrote email to {} at {}", email.to, path.display());
+
+        Ok(())
+    }
+}
+
+/// Builds the mailer selected by the `MAILER` environment variable.
+///
+/// - `MAILER=smtp` sends through `SMTP_HOST`, signing in with `SMTP_USERNAME` and
+///   `SMTP_PASSWORD` if they're set, from `MAIL_FROM` (by default, `rustodon@$DOMAIN`).
+/// - `MAILER=file` (the default) writes messages into `MAIL_DIR` (by default, `tmp/mail`).
+pub fn from_env() -> Result<Box<dyn Mailer>, Error> {
+    match env::var("MAILER").as_ref().map(String::as_str) {
+        Ok("smtp") => {
+            let host = env::var("SMTP_HOST").map_err(|_| format_err!("SMTP_HOST must be set"))?;
+            let credentials = match (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+                (Ok(username), Ok(password)) => Some((username, password)),
+                _ => None,
+            };
+            let from = env::var("MAIL_FROM").unwrap_or_else(|_| format!("rustodon@{}", *DOMAIN));
+
+            Ok(Box::new(SmtpMailer::new(&host, credentials, from)?))
+        },
+        Ok("file") | Err(_) => {
+            let dir = env::var("MAIL_DIR").unwrap_or_else(|_| "tmp/mail".to_string());
+
+            Ok(Box::new(FileMailer::new(dir)?))
+        },
+        Ok(other) => Err(format_err!("unknown MAILER {}", other)),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "mail/confirmation.txt")]
+pub struct ConfirmationEmail<'a> {
+    pub username: &'a str,
+    pub link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "mail/password_reset.txt")]
+pub struct PasswordResetEmail<'a> {
+    pub username: &'a str,
+    pub link: &'a str,
+    pub hours: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_mailer_writes_messages() {
+        let dir = env::temp_dir().join(format!("rustodon-mail-{}", std::process::id()));
+        let mailer = FileMailer::new(&dir).unwrap();
+
+        let email = Email {
+            to: "someone@example.com".to_string(),
+            subject: "hello".to_string(),
+            body: "it's me".to_string(),
+        };
+        mailer.send(&email).unwrap();
+
+        let written: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(written.len(), 1);
+        let contents = fs::read_to_string(written[0].as_ref().unwrap().path()).unwrap();
+        assert!(contents.starts_with("To: someone@example.com\nSubject: hello\n"));
+        assert!(contents.ends_with("it's me"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
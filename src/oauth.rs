@@ -0,0 +1,172 @@
+//! The parts of OAuth 2 which don't touch the database: scopes, and PKCE (RFC 7636).
+
+use openssl::base64;
+use openssl::memcmp;
+use openssl::sha::sha256;
+use std::fmt;
+use std::str::FromStr;
+
+/// Something a client can be allowed to do on behalf of a user.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scope {
+    /// Read the user's data, timelines and so on.
+    Read,
+    /// Post, and change the user's data.
+    Write,
+    /// Follow, block and mute other accounts.
+    Follow,
+    /// Moderate the instance; only moderators and admins can grant it.
+    Admin,
+}
+
+impl Scope {
+    /// Returns the name of this scope, as used in requests and stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Follow => "follow",
+            Scope::Admin => "admin",
+        }
+    }
+
+    /// Describes what this scope allows, for the consent page.
+    pub fn description(self) -> &'static str {
+        match self {
+            Scope::Read => "read your posts, timelines and settings",
+            Scope::Write => "post and change things for you",
+            Scope::Follow => "follow, block and mute accounts for you",
+            Scope::Admin => "moderate this instance, as far as you're allowed to",
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    /// Parses the name of a scope. Mastodon's granular scopes, like `read:statuses` or
+    /// `admin:write:accounts`, are taken as the whole of the scope they're part of.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(':').next().unwrap_or("") {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "follow" => Ok(Scope::Follow),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(format!("unknown scope {}", s)),
+        }
+    }
+}
+
+/// A set of scopes, written as a space-separated list.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Scopes(Vec<Scope>);
+
+impl Scopes {
+    /// The scopes a client gets when it doesn't ask for any in particular.
+    pub fn default_scopes() -> Scopes {
+        Scopes(vec![Scope::Read])
+    }
+
+    /// Returns whether `scope` is one of these.
+    pub fn contains(&self, scope: Scope) -> bool {
+        self.0.contains(&scope)
+    }
+
+    /// Returns whether every one of these scopes is also in `other`.
+    pub fn is_subset_of(&self, other: &Scopes) -> bool {
+        self.0.iter().all(|&scope| other.contains(scope))
+    }
+
+    /// Returns the names of these scopes.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.0.iter().map(|scope| scope.as_str()).collect()
+    }
+
+    /// Returns what these scopes allow, to list on the consent page.
+    pub fn descriptions(&self) -> Vec<&'static str> {
+        self.0.iter().map(|scope| scope.description()).collect()
+    }
+}
+
+impl<'a> From<&'a str> for Scopes {
+    /// Parses a space-separated list of scopes, ignoring duplicates and any we don't know
+    /// (like Mastodon's `push`), since clients ask for whatever Mastodon offers.
+    fn from(s: &'a str) -> Self {
+        let mut scopes = Vec::new();
+        for scope in s.split_whitespace().filter_map(|name| name.parse().ok()) {
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+
+        Scopes(scopes)
+    }
+}
+
+impl fmt::Display for Scopes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.names().join(" "))
+    }
+}
+
+/// Checks a PKCE `code_verifier` against the `code_challenge` a client sent when it asked for
+/// authorization, made with `method` (`S256`, or `plain`).
+pub fn verify_code_challenge(verifier: &str, challenge: &str, method: &str) -> bool {
+    let expected = match method {
+        "S256" => base64::encode_block(&sha256(verifier.as_bytes()))
+            .trim_end_matches('=')
+            .replace('+', "-")
+            .replace('/', "_"),
+        "plain" => verifier.to_string(),
+        _ => return false,
+    };
+
+    expected.len() == challenge.len() && memcmp::eq(expected.as_bytes(), challenge.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_round_trip() {
+        let scopes = Scopes::from("read write read follow");
+        assert_eq!(scopes.to_string(), "read write follow");
+        assert!(scopes.contains(Scope::Follow));
+        assert!(!scopes.contains(Scope::Admin));
+    }
+
+    #[test]
+    fn scopes_take_granular_scopes_whole_and_drop_unknown_ones() {
+        assert_eq!(
+            Scopes::from("read write follow push").to_string(),
+            "read write follow"
+        );
+        assert_eq!(
+            Scopes::from("read:statuses write:favourites read:accounts admin:read:accounts")
+                .to_string(),
+            "read write admin"
+        );
+        assert_eq!(Scopes::from("push nonsense"), Scopes::default());
+    }
+
+    #[test]
+    fn scopes_can_be_narrowed() {
+        let all = Scopes::from("read write follow");
+        let some = Scopes::from("read follow");
+        assert!(some.is_subset_of(&all));
+        assert!(!all.is_subset_of(&some));
+    }
+
+    #[test]
+    fn it_verifies_pkce_challenges() {
+        // the example from RFC 7636, appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+        assert!(verify_code_challenge(verifier, challenge, "S256"));
+        assert!(!verify_code_challenge("wrong", challenge, "S256"));
+        assert!(verify_code_challenge(verifier, verifier, "plain"));
+        assert!(!verify_code_challenge(verifier, challenge, "S512"));
+    }
+}
@@ -0,0 +1,193 @@
+//! Hashing and checking passwords.
+//!
+//! New hashes use the scheme configured by the environment (see [`Scheme::from_env`]);
+//! hashes made with any scheme we know can still be verified, so the scheme (or its cost)
+//! can be changed at any time, with old hashes upgraded as users sign in.
+
+use argon2::{self, Config, ThreadMode, Variant, Version};
+use failure::{format_err, Error};
+use lazy_static::lazy_static;
+use openssl::rand::rand_bytes;
+use pwhash::bcrypt::{self, BcryptSetup, BcryptVariant};
+use std::env;
+
+use crate::db::validators::MAX_PASSWORD_LENGTH;
+
+/// Number of random bytes in an argon2 salt.
+const SALT_BYTES: usize = 16;
+
+// argon2id parameters recommended by OWASP.
+const DEFAULT_ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
+const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+
+const DEFAULT_BCRYPT_COST: u32 = 12;
+
+/// A way of hashing passwords, with its cost parameters.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scheme {
+    /// argon2id, using `memory` KiB, `iterations` passes and `parallelism` lanes.
+    Argon2id {
+        memory: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+    /// bcrypt, with a work factor of `2^cost`.
+    Bcrypt { cost: u32 },
+}
+
+lazy_static! {
+    /// The scheme new password hashes are made with.
+    pub static ref CURRENT_SCHEME: Scheme =
+        Scheme::from_env().expect("Invalid password hashing configuration!");
+}
+
+impl Scheme {
+    /// Reads the scheme from the environment.
+    ///
+    /// `PASSWORD_HASH` picks `argon2id` (the default) or `bcrypt`. The cost of argon2id is
+    /// set by `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and `ARGON2_PARALLELISM`, and that
+    /// of bcrypt by `BCRYPT_COST`; anything unset keeps its default.
+    pub fn from_env() -> Result<Scheme, Error> {
+        fn param(name: &str, default: u32) -> Result<u32, Error> {
+            match env::var(name) {
+                Ok(value) => value
+                    .parse()
+                    .map_err(|_| format_err!("{} must be a number", name)),
+                Err(_) => Ok(default),
+            }
+        }
+
+        match env::var("PASSWORD_HASH").as_ref().map(String::as_str) {
+            Ok("argon2id") | Err(_) => Ok(Scheme::Argon2id {
+                memory: param("ARGON2_MEMORY_KIB", DEFAULT_ARGON2_MEMORY_KIB)?,
+                iterations: param("ARGON2_ITERATIONS", DEFAULT_ARGON2_ITERATIONS)?,
+                parallelism: param("ARGON2_PARALLELISM", DEFAULT_ARGON2_PARALLELISM)?,
+            }),
+            Ok("bcrypt") => Ok(Scheme::Bcrypt {
+                cost: param("BCRYPT_COST", DEFAULT_BCRYPT_COST)?,
+            }),
+            Ok(other) => Err(format_err!("unknown PASSWORD_HASH {}", other)),
+        }
+    }
+
+    /// Hashes `password` with this scheme, returning the hash in its usual encoding
+    /// (which includes the scheme and its parameters).
+    pub fn hash(self, password: &str) -> Result<String, Error> {
+        match self {
+            Scheme::Argon2id {
+                memory,
+                iterations,
+                parallelism,
+            } => {
+                let mut salt = [0; SALT_BYTES];
+                rand_bytes(&mut salt)?;
+
+                let config = Config {
+                    variant: Variant::Argon2id,
+                    version: Version::Version13,
+                    mem_cost: memory,
+                    time_cost: iterations,
+                    lanes: parallelism,
+                    thread_mode: ThreadMode::from_threads(parallelism),
+                    ..Config::default()
+                };
+                Ok(argon2::hash_encoded(password.as_bytes(), &salt, &config)?)
+            },
+            Scheme::Bcrypt { cost } => Ok(bcrypt::hash_with(
+                BcryptSetup {
+                    cost: Some(cost),
+                    variant: Some(BcryptVariant::V2b),
+                    ..Default::default()
+                },
+                password,
+            )?),
+        }
+    }
+
+    /// Returns whether `hash` was made with exactly this scheme and these parameters;
+    /// if not, it should be replaced next time we have the password.
+    pub fn is_current(self, hash: &str) -> bool {
+        match self {
+            Scheme::Argon2id {
+                memory,
+                iterations,
+                parallelism,
+            } => hash.starts_with(&format!(
+                "$argon2id$v=19$m={},t={},p={}$",
+                memory, iterations, parallelism
+            )),
+            Scheme::Bcrypt { cost } => {
+                hash.starts_with("$2") && hash.get(4..7) == Some(format!("{:02}$", cost).as_str())
+            },
+        }
+    }
+}
+
+/// Checks `password` against `hash`, whichever scheme it was made with.
+///
+/// Passwords longer than any we'd have accepted are refused without being hashed, so they
+/// can't be used to make us do lots of work.
+pub fn verify(password: &str, hash: &str) -> bool {
+    if password.chars().nth(MAX_PASSWORD_LENGTH).is_some() {
+        return false;
+    }
+
+    if hash.starts_with("$argon2") {
+        argon2::verify_encoded(hash, password.as_bytes()).unwrap_or(false)
+    } else {
+        bcrypt::verify(password, hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cheap parameters, so the tests don't take forever.
+    const ARGON2: Scheme = Scheme::Argon2id {
+        memory: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+    const BCRYPT: Scheme = Scheme::Bcrypt { cost: 4 };
+
+    #[test]
+    fn it_verifies_hashes_of_any_scheme() {
+        for scheme in &[ARGON2, BCRYPT] {
+            let hash = scheme.hash("correct horse").unwrap();
+            assert!(verify("correct horse", &hash));
+            assert!(!verify("battery staple", &hash));
+        }
+    }
+
+    #[test]
+    fn it_refuses_overlong_passwords() {
+        let longest = "a".repeat(MAX_PASSWORD_LENGTH);
+        let hash = ARGON2.hash(&longest).unwrap();
+        assert!(verify(&longest, &hash));
+
+        let too_long = "a".repeat(MAX_PASSWORD_LENGTH + 1);
+        let hash = ARGON2.hash(&too_long).unwrap();
+        assert!(!verify(&too_long, &hash));
+    }
+
+    #[test]
+    fn it_spots_outdated_hashes() {
+        let argon2 = ARGON2.hash("correct horse").unwrap();
+        let bcrypt = BCRYPT.hash("correct horse").unwrap();
+
+        assert!(ARGON2.is_current(&argon2));
+        assert!(!ARGON2.is_current(&bcrypt));
+        assert!(BCRYPT.is_current(&bcrypt));
+        assert!(!BCRYPT.is_current(&argon2));
+
+        let stronger = Scheme::Argon2id {
+            memory: 128,
+            iterations: 1,
+            parallelism: 1,
+        };
+        assert!(!stronger.is_current(&argon2));
+        assert!(!Scheme::Bcrypt { cost: 5 }.is_current(&bcrypt));
+    }
+}
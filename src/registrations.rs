@@ -0,0 +1,68 @@
+//! Who may sign up for an account on this instance.
+
+use failure::{format_err, Error};
+use lazy_static::lazy_static;
+use std::env;
+
+/// How new accounts can be made.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RegistrationMode {
+    /// Anybody can sign up.
+    Open,
+    /// Anybody can sign up, but can't sign in until a moderator approves them.
+    Approval,
+    /// Only people with a valid invite code can sign up.
+    Invite,
+    /// Nobody can sign up.
+    Closed,
+}
+
+lazy_static! {
+    /// The registration mode of this instance.
+    pub static ref REGISTRATION_MODE: RegistrationMode =
+        RegistrationMode::from_env().expect("Invalid registration mode!");
+}
+
+impl RegistrationMode {
+    /// Reads the mode from `REGISTRATIONS`, which is one of `open` (the default),
+    /// `approval`, `invite` or `closed`.
+    pub fn from_env() -> Result<RegistrationMode, Error> {
+        match env::var("REGISTRATIONS").as_ref().map(String::as_str) {
+            Ok("open") | Err(_) => Ok(RegistrationMode::Open),
+            Ok("approval") => Ok(RegistrationMode::Approval),
+            Ok("invite") => Ok(RegistrationMode::Invite),
+            Ok("closed") => Ok(RegistrationMode::Closed),
+            Ok(other) => Err(format_err!("unknown REGISTRATIONS {}", other)),
+        }
+    }
+
+    /// Returns the name of this mode, as set in `REGISTRATIONS`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RegistrationMode::Open => "open",
+            RegistrationMode::Approval => "approval",
+            RegistrationMode::Invite => "invite",
+            RegistrationMode::Closed => "closed",
+        }
+    }
+
+    /// Returns whether the sign-up form is available at all.
+    pub fn allows_signups(self) -> bool {
+        self != RegistrationMode::Closed
+    }
+
+    /// Returns whether anybody can sign up without an invite, as reported by nodeinfo.
+    pub fn is_open(self) -> bool {
+        self == RegistrationMode::Open || self == RegistrationMode::Approval
+    }
+
+    /// Returns whether signing up needs an invite code.
+    pub fn requires_invite(self) -> bool {
+        self == RegistrationMode::Invite
+    }
+
+    /// Returns whether new users have to be approved before they can sign in.
+    pub fn requires_approval(self) -> bool {
+        self == RegistrationMode::Approval
+    }
+}
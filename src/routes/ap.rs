@@ -1,14 +1,24 @@
 use resopt::try_resopt;
-use rocket::Route;
+use rocket::http;
+use rocket::{Data, Route};
+use serde_json::Value;
+use std::io::Read;
 
-use crate::activitypub::{ActivityGuard, ActivityStreams, AsActivityPub};
+use crate::activitypub::fetch::fetch_actor;
+use crate::activitypub::signatures::{self, SignedRequest};
+use crate::activitypub::{inbox, ActivityGuard, ActivityStreams, AsActivityPub};
 use crate::db;
-use crate::db::models::{Account, Status};
+use crate::db::models::{Account, CustomEmoji, Status};
 use crate::error::Perhaps;
 use crate::util::StatusID;
 
 pub fn routes() -> Vec<Route> {
-    routes![ap_user_object, ap_status_object,]
+    routes![
+        ap_user_object,
+        ap_user_inbox,
+        ap_status_object,
+        ap_emoji_object,
+    ]
 }
 
 /// Returns a user as an ActivityPub object.
@@ -40,3 +50,57 @@ pub fn ap_status_object(
 
     Ok(Some(status.as_activitypub(&db_conn)?))
 }
+
+/// Returns a local custom emoji as an ActivityPub object.
+#[get("/emojis/<id>")]
+pub fn ap_emoji_object(
+    id: i64,
+    _ag: ActivityGuard,
+    db_conn: db::Connection,
+) -> Perhaps<ActivityStreams> {
+    let emoji = try_resopt!(CustomEmoji::by_id(&db_conn, id));
+    if !emoji.is_local() {
+        return Ok(None);
+    }
+
+    Ok(Some(emoji.as_activitypub(&db_conn)?))
+}
+
+/// Maximum size of an activity POSTed to an inbox.
+const INBOX_LIMIT: u64 = 1 << 20;
+
+/// Accepts an activity for a local user.
+///
+/// The request must be signed by the activity's actor, who is fetched if we don't know them yet.
+#[post("/users/<username>/inbox", data = "<body>")]
+pub fn ap_user_inbox(
+    username: String,
+    signature: SignedRequest,
+    body: Data,
+    db_conn: db::Connection,
+) -> Perhaps<http::Status> {
+    let recipient = try_resopt!(Account::fetch_local_by_username(&db_conn, username));
+
+    let mut body_bytes = Vec::new();
+    body.open().take(INBOX_LIMIT).read_to_end(&mut body_bytes)?;
+
+    if signature.digest != Some(signatures::digest(&body_bytes)) {
+        return Ok(Some(http::Status::BadRequest));
+    }
+
+    let activity: Value = serde_json::from_slice(&body_bytes)?;
+    let actor = fetch_actor(&db_conn, signature.actor_uri())?;
+
+    if !signature.verify(&actor)?
+        || activity["actor"].as_str() != actor.uri.as_ref().map(String::as_str)
+    {
+        return Ok(Some(http::Status::Unauthorized));
+    }
+
+    // suspended actors don't get a say in anything.
+    if !actor.suspended {
+        inbox::handle(&db_conn, &recipient, &actor, &activity)?;
+    }
+
+    Ok(Some(http::Status::Accepted))
+}
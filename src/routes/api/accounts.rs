@@ -0,0 +1,44 @@
+use rocket::request::LenientForm;
+use rocket_contrib::json::JsonValue;
+
+use super::{entities, ApiError, ApiResult, PageParams, Paginated};
+use crate::db;
+use crate::db::models::{Account, CanRead};
+
+/// Returns the account of the user the client is acting as.
+#[get("/api/v1/accounts/verify_credentials")]
+pub fn verify_credentials(user: CanRead, db_conn: db::Connection) -> ApiResult<JsonValue> {
+    let account = user.0.get_account(&db_conn)?;
+    Ok(JsonValue(entities::account(&db_conn, &account)?))
+}
+
+/// Finds an account which isn't suspended, by ID.
+fn visible_account(db_conn: &db::Connection, id: i64) -> ApiResult<Account> {
+    Account::by_id(db_conn, id)?
+        .filter(|account| !account.suspended)
+        .ok_or_else(ApiError::not_found)
+}
+
+#[get("/api/v1/accounts/<id>")]
+pub fn account(id: i64, db_conn: db::Connection) -> ApiResult<JsonValue> {
+    let account = visible_account(&db_conn, id)?;
+    Ok(JsonValue(entities::account(&db_conn, &account)?))
+}
+
+/// Returns a page of the statuses an account has authored, newest first.
+#[get("/api/v1/accounts/<id>/statuses?<params..>")]
+pub fn statuses(
+    id: i64,
+    params: LenientForm<PageParams>,
+    db_conn: db::Connection,
+) -> ApiResult<Paginated> {
+    let account = visible_account(&db_conn, id)?;
+    let statuses = account.statuses_page(&db_conn, &params.page())?;
+
+    let body = entities::statuses(&db_conn, &statuses)?;
+    Ok(Paginated::new(
+        JsonValue(body.into()),
+        &format!("/api/v1/accounts/{}/statuses", account.id),
+        &statuses,
+    ))
+}
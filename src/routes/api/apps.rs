@@ -0,0 +1,62 @@
+use rocket::http::Status;
+use rocket_contrib::json::JsonValue;
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use super::{ApiError, ApiResult, FormOrJson};
+use crate::db;
+use crate::db::models::Application;
+use crate::oauth::Scopes;
+
+#[derive(Debug, FromForm, Deserialize)]
+pub struct CreateAppForm {
+    client_name: String,
+    /// Newline-separated, or `urn:ietf:wg:oauth:2.0:oob` for clients that can't be redirected to.
+    redirect_uris: String,
+    /// Space-separated; defaults to `read`. Scopes we don't know are left out.
+    scopes: Option<String>,
+    website: Option<String>,
+}
+
+/// Registers a client application.
+#[post("/api/v1/apps", data = "<form>")]
+pub fn create_app(
+    form: FormOrJson<CreateAppForm>,
+    db_conn: db::Connection,
+) -> ApiResult<JsonValue> {
+    let scopes = form
+        .scopes
+        .as_ref()
+        .map_or_else(Scopes::default_scopes, |scopes| {
+            Scopes::from(scopes.as_str())
+        });
+
+    if form.client_name.trim().is_empty() || form.redirect_uris.trim().is_empty() {
+        return Err(ApiError::new(
+            Status::UnprocessableEntity,
+            "client_name and redirect_uris can't be blank",
+        ));
+    }
+
+    let website = form
+        .website
+        .as_ref()
+        .map(String::as_str)
+        .filter(|w| !w.is_empty());
+    let (app, client_secret) = Application::create(
+        &db_conn,
+        form.client_name.trim(),
+        website,
+        form.redirect_uris.trim(),
+        &scopes,
+    )?;
+
+    Ok(JsonValue(json!({
+        "id": app.id.to_string(),
+        "name": app.name,
+        "website": app.website,
+        "redirect_uri": app.redirect_uris,
+        "client_id": app.client_id,
+        "client_secret": client_secret,
+    })))
+}
@@ -0,0 +1,16 @@
+use rocket_contrib::json::JsonValue;
+
+use super::{entities, ApiResult};
+use crate::db;
+use crate::db::models::CustomEmoji;
+
+/// Lists the custom emoji of this instance, for clients' emoji pickers.
+#[get("/api/v1/custom_emojis")]
+pub fn custom_emojis(db_conn: db::Connection) -> ApiResult<JsonValue> {
+    let body = CustomEmoji::local(&db_conn)?
+        .iter()
+        .map(entities::emoji)
+        .collect::<Vec<_>>();
+
+    Ok(JsonValue(body.into()))
+}
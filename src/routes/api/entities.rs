@@ -0,0 +1,249 @@
+//! Serializes our models into the JSON entities the Mastodon API hands out.
+
+use failure::{format_err, Error};
+use rocket::http::uri::Uri;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::db;
+use crate::db::models::{Account, AccountCounts, CustomEmoji, Notification, Status};
+use crate::routes::ui::view_helpers::{HasBio, HasRenderedContent};
+use crate::transform;
+use crate::BASE_URL;
+
+/// Returns the picture shown for accounts which haven't got an avatar or a header.
+fn missing_image() -> String {
+    format!("{base}/static/img/missing.png", base = BASE_URL.as_str())
+}
+
+/// Returns an account's address the way Mastodon writes it: `username` for local accounts and
+/// `username@domain` for remote ones.
+fn acct(account: &Account) -> String {
+    match &account.domain {
+        Some(domain) if !account.is_local() => format!("{}@{}", account.username, domain),
+        _ => account.username.clone(),
+    }
+}
+
+/// Returns the Emoji entity for `emoji`.
+pub fn emoji(emoji: &CustomEmoji) -> Value {
+    json!({
+        "shortcode": emoji.shortcode,
+        "url": emoji.image_url(),
+        "static_url": emoji.image_url(),
+        "visible_in_picker": true,
+    })
+}
+
+/// Returns the Account entity for `account`.
+pub fn account(db_conn: &db::DbConnection, account: &Account) -> Result<Value, Error> {
+    let counts = AccountCounts::for_accounts(db_conn, &[account.id])?
+        .remove(&account.id)
+        .unwrap_or_default();
+
+    account_with_counts(db_conn, account, counts)
+}
+
+/// Returns the Account entity for `account`, which has `counts`.
+fn account_with_counts(
+    db_conn: &db::DbConnection,
+    account: &Account,
+    counts: AccountCounts,
+) -> Result<Value, Error> {
+    Ok(json!({
+        "id": account.id.to_string(),
+        "username": account.username,
+        "acct": acct(account),
+        "display_name": account.display_name.as_ref().map_or("", String::as_str),
+        "locked": account.locked,
+        "bot": false,
+        "created_at": account.created_at.to_rfc3339(),
+        "note": account.transformed_bio(db_conn).unwrap_or_default(),
+        "url": account.get_uri(),
+        "avatar": missing_image(),
+        "avatar_static": missing_image(),
+        "header": missing_image(),
+        "header_static": missing_image(),
+        "followers_count": counts.followers,
+        "following_count": counts.following,
+        "statuses_count": counts.statuses,
+        "emojis": [],
+        "fields": [],
+    }))
+}
+
+/// Returns the Tag entities for the hashtags in `status`, named in lowercase the way they're
+/// indexed. We haven't got pages for hashtags, so they link to their timelines.
+fn tags(status: &Status) -> Vec<Value> {
+    let mut names = transform::hashtags(&status.text)
+        .iter()
+        .map(|name| name.to_lowercase())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    names
+        .iter()
+        .map(|name| {
+            json!({
+                "name": name,
+                "url": format!(
+                    "{base}/api/v1/timelines/tag/{name}",
+                    base = BASE_URL.as_str(),
+                    name = Uri::percent_encode(name)
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Returns the Status entity for `status`, which was authored by `account`.
+pub fn status(
+    db_conn: &db::DbConnection,
+    status: &Status,
+    account: &Account,
+) -> Result<Value, Error> {
+    status_with_author(
+        db_conn,
+        status,
+        account,
+        self::account(db_conn, account)?,
+        &status.mentioned_accounts(db_conn)?,
+    )
+}
+
+/// Returns the Status entity for `status`, which was authored by `account`, whose Account
+/// entity is `account_entity`, and mentions the accounts `mentioned`.
+fn status_with_author(
+    db_conn: &db::DbConnection,
+    status: &Status,
+    account: &Account,
+    account_entity: Value,
+    mentioned: &[Account],
+) -> Result<Value, Error> {
+    let mentions = mentioned
+        .iter()
+        .map(|mentioned| {
+            json!({
+                "id": mentioned.id.to_string(),
+                "username": mentioned.username,
+                "acct": acct(mentioned),
+                "url": mentioned.get_uri(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let emojis =
+        CustomEmoji::all_for_account(db_conn, &transform::emoji_shortcodes(&status.text), account)?;
+
+    let uri = status.uri_with_account(account);
+    Ok(json!({
+        "id": status.id.to_string(),
+        "uri": uri,
+        "url": uri,
+        "account": account_entity,
+        "in_reply_to_id": null,
+        "in_reply_to_account_id": null,
+        "reblog": null,
+        "content": status.rendered_content(db_conn),
+        "created_at": status.created_at.to_rfc3339(),
+        "emojis": emojis.iter().map(emoji).collect::<Vec<_>>(),
+        "replies_count": 0,
+        "reblogs_count": 0,
+        "favourites_count": 0,
+        "sensitive": status.content_warning.is_some() || account.force_sensitive,
+        "spoiler_text": status.content_warning.as_ref().map_or("", String::as_str),
+        "visibility": "public",
+        "media_attachments": [],
+        "mentions": mentions,
+        "tags": tags(status),
+        "application": null,
+        "language": null,
+    }))
+}
+
+/// Returns the Status entities for `statuses`, looking up their authors (and what to count
+/// for them) and the accounts they mention all at once rather than status by status.
+pub fn statuses(db_conn: &db::DbConnection, statuses: &[Status]) -> Result<Vec<Value>, Error> {
+    let mut author_ids = statuses.iter().map(|s| s.account_id).collect::<Vec<_>>();
+    author_ids.sort();
+    author_ids.dedup();
+
+    let mut counts = AccountCounts::for_accounts(db_conn, &author_ids)?;
+    let mut authors = HashMap::new();
+    for author in Account::by_ids(db_conn, &author_ids)? {
+        let counts = counts.remove(&author.id).unwrap_or_default();
+        let entity = account_with_counts(db_conn, &author, counts)?;
+        authors.insert(author.id, (author, entity));
+    }
+
+    let mut mentioned = Status::mentioned_accounts_of(db_conn, statuses)?;
+
+    statuses
+        .iter()
+        .map(|status| {
+            let (author, entity) = authors
+                .get(&status.account_id)
+                .ok_or_else(|| format_err!("status {} has no author", status.id))?;
+            let mentioned = mentioned.remove(&status.id).unwrap_or_default();
+            status_with_author(db_conn, status, author, entity.clone(), &mentioned)
+        })
+        .collect()
+}
+
+/// Returns the Notification entity for `notification`.
+pub fn notification(
+    db_conn: &db::DbConnection,
+    notification: &Notification,
+) -> Result<Value, Error> {
+    let from = notification.from_account(db_conn)?;
+    let status = match notification.status(db_conn)? {
+        Some(status) => self::status(db_conn, &status, &status.account(db_conn)?)?,
+        None => Value::Null,
+    };
+
+    Ok(json!({
+        "id": notification.id.to_string(),
+        "type": notification.kind.as_str(),
+        "created_at": notification.created_at.to_rfc3339(),
+        "account": self::account(db_conn, &from)?,
+        "status": status,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_lists_the_hashtags_of_statuses() {
+        use chrono::offset::Utc;
+        use std::env;
+        env::set_var("DOMAIN", "localhost");
+
+        let status = Status {
+            id: 1,
+            text: "#Rust and #rust, #café and #not-quite".to_string(),
+            content_warning: None,
+            created_at: Utc::now(),
+            account_id: 1,
+            uri: None,
+            content_html: None,
+            content_html_version: 0,
+        };
+
+        assert_eq!(
+            tags(&status),
+            vec![
+                json!({
+                    "name": "café",
+                    "url": format!("{}/api/v1/timelines/tag/caf%C3%A9", BASE_URL.as_str()),
+                }),
+                json!({
+                    "name": "rust",
+                    "url": format!("{}/api/v1/timelines/tag/rust", BASE_URL.as_str()),
+                }),
+            ]
+        );
+    }
+}
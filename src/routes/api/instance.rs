@@ -0,0 +1,38 @@
+use rocket_contrib::json::JsonValue;
+use serde_json::json;
+
+use super::ApiResult;
+
+use crate::db;
+use crate::db::models::{Status, User};
+use crate::db::validators::MAX_STATUS_LENGTH;
+use crate::registrations::REGISTRATION_MODE;
+use crate::{DOMAIN, GIT_REV};
+
+/// Describes this instance to clients.
+#[get("/api/v1/instance")]
+pub fn instance(db_conn: db::Connection) -> ApiResult<JsonValue> {
+    Ok(JsonValue(json!({
+        "uri": DOMAIN.as_str(),
+        "title": DOMAIN.as_str(),
+        "description": "",
+        "short_description": "",
+        "email": "",
+        // clients look at this to work out which API they can use, so claim what we implement.
+        "version": format!("2.7.0 (compatible; rustodon {})", GIT_REV.trim()),
+        "urls": {
+            "streaming_api": format!("wss://{}", DOMAIN.as_str()),
+        },
+        "stats": {
+            "user_count": User::count(&db_conn)?,
+            "status_count": Status::count_local(&db_conn)?,
+            "domain_count": 0,
+        },
+        "languages": ["en"],
+        "max_toot_chars": *MAX_STATUS_LENGTH,
+        "registrations": REGISTRATION_MODE.is_open(),
+        "approval_required": REGISTRATION_MODE.requires_approval(),
+        "invites_enabled": REGISTRATION_MODE.requires_invite(),
+        "contact_account": null,
+    })))
+}
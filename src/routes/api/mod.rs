@@ -0,0 +1,250 @@
+//! The REST API, for third-party clients. Clients authenticate through OAuth (see `oauth`).
+
+use rocket::data::{self, Data, FromDataSimple};
+use rocket::http::{Header, Status};
+use rocket::request::{FormItems, FromForm, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::{Outcome, Route};
+use rocket_contrib::json::JsonValue;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use slog_scope::error;
+use std::io::Read;
+
+use crate::db::models::{self, Status as StatusModel};
+use crate::BASE_URL;
+
+mod accounts;
+mod apps;
+mod custom_emojis;
+pub mod entities;
+mod instance;
+mod oauth;
+mod statuses;
+mod timelines;
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        accounts::verify_credentials,
+        accounts::account,
+        accounts::statuses,
+        apps::create_app,
+        custom_emojis::custom_emojis,
+        instance::instance,
+        oauth::token,
+        oauth::revoke,
+        statuses::create,
+        statuses::status,
+        statuses::delete,
+        timelines::public,
+        timelines::home,
+        timelines::tag
+    ]
+}
+
+/// An error returned from the API, as a JSON object with a human-readable `error`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Status,
+    pub error:  String,
+}
+
+impl ApiError {
+    pub fn new<S: Into<String>>(status: Status, error: S) -> ApiError {
+        ApiError {
+            status,
+            error: error.into(),
+        }
+    }
+
+    pub fn not_found() -> ApiError {
+        ApiError::new(Status::NotFound, "Record not found")
+    }
+}
+
+impl From<failure::Error> for ApiError {
+    fn from(e: failure::Error) -> ApiError {
+        error!("error handling API request: {}", e);
+        ApiError::new(Status::InternalServerError, "Internal server error")
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(e: diesel::result::Error) -> ApiError {
+        ApiError::from(failure::Error::from(e))
+    }
+}
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        Response::build_from(JsonValue(json!({ "error": self.error })).respond_to(request)?)
+            .status(self.status)
+            .ok()
+    }
+}
+
+/// The result of an API route.
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// A request body sent either as a form or as JSON, as clients do both.
+///
+/// Forms are parsed leniently, since clients often send fields we don't know about.
+#[derive(Debug)]
+pub struct FormOrJson<T>(pub T);
+
+impl<T> std::ops::Deref for FormOrJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> FromDataSimple for FormOrJson<T>
+where
+    T: DeserializeOwned + for<'f> FromForm<'f>,
+{
+    type Error = String;
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Self, String> {
+        let is_json = request.content_type().map_or(false, |ct| ct.is_json());
+        let limit_name = if is_json { "json" } else { "forms" };
+        let limit = request.limits().get(limit_name).unwrap_or(32 * 1024);
+
+        let mut body = String::new();
+        if let Err(e) = data.open().take(limit).read_to_string(&mut body) {
+            return Outcome::Failure((Status::BadRequest, e.to_string()));
+        }
+
+        let parsed = if is_json {
+            serde_json::from_str(&body).map_err(|e| e.to_string())
+        } else {
+            T::from_form(&mut FormItems::from(body.as_str()), false)
+                .map_err(|_| "invalid form".to_string())
+        };
+
+        match parsed {
+            Ok(value) => Outcome::Success(FormOrJson(value)),
+            Err(e) => Outcome::Failure((Status::UnprocessableEntity, e)),
+        }
+    }
+}
+
+/// How many statuses a page has when the client doesn't say.
+pub const DEFAULT_PAGE_LIMIT: usize = 20;
+
+/// The most statuses a client can get in one page.
+pub const MAX_PAGE_LIMIT: usize = 40;
+
+/// The query parameters clients page through lists of statuses with.
+#[derive(Debug, FromForm)]
+pub struct PageParams {
+    max_id:   Option<i64>,
+    since_id: Option<i64>,
+    min_id:   Option<i64>,
+    limit:    Option<usize>,
+}
+
+impl PageParams {
+    pub fn page(&self) -> models::Page {
+        models::Page {
+            max_id:   self.max_id,
+            since_id: self.since_id,
+            min_id:   self.min_id,
+            limit:    self
+                .limit
+                .unwrap_or(DEFAULT_PAGE_LIMIT)
+                .min(MAX_PAGE_LIMIT)
+                .max(1),
+        }
+    }
+}
+
+/// A page of statuses, with a `Link` header pointing at the pages before and after it.
+#[derive(Debug)]
+pub struct Paginated {
+    body: JsonValue,
+    link: Option<String>,
+}
+
+impl Paginated {
+    /// Wraps the JSON `body` for the page `statuses` (newest first) of the list at `path`,
+    /// which may have a query of its own.
+    pub fn new(body: JsonValue, path: &str, statuses: &[StatusModel]) -> Paginated {
+        let separator = if path.contains('?') { '&' } else { '?' };
+        let link = match (statuses.first(), statuses.last()) {
+            (Some(newest), Some(oldest)) => Some(format!(
+                "<{base}{path}{sep}max_id={oldest}>; rel=\"next\", \
+                 <{base}{path}{sep}min_id={newest}>; rel=\"prev\"",
+                base = BASE_URL.as_str(),
+                path = path,
+                sep = separator,
+                oldest = oldest.id,
+                newest = newest.id,
+            )),
+            _ => None,
+        };
+
+        Paginated { body, link }
+    }
+}
+
+impl<'r> Responder<'r> for Paginated {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let mut response = Response::build_from(self.body.respond_to(request)?);
+        if let Some(link) = self.link {
+            response.header(Header::new("Link", link));
+        }
+        response.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_clamps_page_limits() {
+        let params = |limit| PageParams {
+            max_id: Some(5),
+            since_id: None,
+            min_id: None,
+            limit,
+        };
+
+        assert_eq!(params(None).page().limit, DEFAULT_PAGE_LIMIT);
+        assert_eq!(params(Some(0)).page().limit, 1);
+        assert_eq!(params(Some(1000)).page().limit, MAX_PAGE_LIMIT);
+        assert_eq!(params(Some(3)).page().max_id, Some(5));
+    }
+
+    #[test]
+    fn it_links_to_neighbouring_pages() {
+        use chrono::offset::Utc;
+        use std::env;
+        env::set_var("DOMAIN", "localhost");
+
+        let status = |id| StatusModel {
+            id,
+            text: String::new(),
+            content_warning: None,
+            created_at: Utc::now(),
+            account_id: 1,
+            uri: None,
+            content_html: None,
+            content_html_version: 0,
+        };
+
+        let page = Paginated::new(JsonValue(json!([])), "/api/v1/timelines/home", &[]);
+        assert_eq!(page.link, None);
+
+        let page = Paginated::new(
+            JsonValue(json!([])),
+            "/api/v1/timelines/public?local=true",
+            &[status(30), status(20)],
+        );
+        let link = page.link.unwrap();
+        assert!(link.contains("/api/v1/timelines/public?local=true&max_id=20>; rel=\"next\""));
+        assert!(link.contains("/api/v1/timelines/public?local=true&min_id=30>; rel=\"prev\""));
+    }
+}
@@ -0,0 +1,108 @@
+//! The OAuth token endpoints. Users approve clients on the consent page, in `ui::oauth`.
+
+use rocket::http::Status;
+use rocket_contrib::json::JsonValue;
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use super::{ApiError, ApiResult, FormOrJson};
+use crate::db;
+use crate::db::models::{AccessToken, Application, AuthorizationCode};
+use crate::oauth::Scopes;
+
+#[derive(Debug, FromForm, Deserialize)]
+pub struct TokenForm {
+    grant_type: String,
+    client_id: String,
+    client_secret: Option<String>,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    code_verifier: Option<String>,
+    scope: Option<String>,
+}
+
+/// Finds the application `client_id`, checking its secret if it was sent.
+fn authenticate_client(
+    db_conn: &db::Connection,
+    client_id: &str,
+    client_secret: Option<&str>,
+) -> ApiResult<Application> {
+    let invalid_client = || ApiError::new(Status::Unauthorized, "invalid_client");
+
+    let app = Application::by_client_id(db_conn, client_id)?.ok_or_else(invalid_client)?;
+    match client_secret {
+        Some(secret) if !app.valid_secret(secret) => Err(invalid_client()),
+        _ => Ok(app),
+    }
+}
+
+/// Exchanges an authorization code (`grant_type=authorization_code`), or just the client's
+/// credentials (`grant_type=client_credentials`), for an access token.
+///
+/// Clients which used PKCE to get their code may leave out their secret.
+#[post("/oauth/token", data = "<form>")]
+pub fn token(form: FormOrJson<TokenForm>, db_conn: db::Connection) -> ApiResult<JsonValue> {
+    let secret = form.client_secret.as_ref().map(String::as_str);
+    let app = authenticate_client(&db_conn, &form.client_id, secret)?;
+    let invalid_grant = || ApiError::new(Status::BadRequest, "invalid_grant");
+
+    let (token, access_token) = match form.grant_type.as_str() {
+        "authorization_code" => {
+            let code = form.code.as_ref().ok_or_else(invalid_grant)?;
+            let code =
+                AuthorizationCode::take(&db_conn, app.id, code)?.ok_or_else(invalid_grant)?;
+
+            let redirect_uri = form.redirect_uri.as_ref().map(String::as_str);
+            if redirect_uri != Some(code.redirect_uri.as_str()) {
+                return Err(invalid_grant());
+            }
+            if !code.verify(form.code_verifier.as_ref().map(String::as_str)) {
+                return Err(invalid_grant());
+            }
+            if secret.is_none() && code.code_challenge.is_none() {
+                return Err(ApiError::new(Status::Unauthorized, "invalid_client"));
+            }
+
+            AccessToken::create(&db_conn, &app, Some(code.user_id), &code.scopes())?
+        },
+        "client_credentials" => {
+            if secret.is_none() {
+                return Err(ApiError::new(Status::Unauthorized, "invalid_client"));
+            }
+
+            let scopes = form
+                .scope
+                .as_ref()
+                .map_or_else(Scopes::default_scopes, |scope| Scopes::from(scope.as_str()));
+            if !scopes.is_subset_of(&app.scopes()) {
+                return Err(ApiError::new(Status::BadRequest, "invalid_scope"));
+            }
+
+            AccessToken::create(&db_conn, &app, None, &scopes)?
+        },
+        _ => return Err(ApiError::new(Status::BadRequest, "unsupported_grant_type")),
+    };
+
+    Ok(JsonValue(json!({
+        "access_token": access_token,
+        "token_type": "Bearer",
+        "scope": token.scopes,
+        "created_at": token.created_at.timestamp(),
+    })))
+}
+
+#[derive(Debug, FromForm, Deserialize)]
+pub struct RevokeForm {
+    client_id: String,
+    client_secret: String,
+    token: String,
+}
+
+/// Revokes an access token. Like RFC 7009 says, this succeeds even if there was no such token.
+#[post("/oauth/revoke", data = "<form>")]
+pub fn revoke(form: FormOrJson<RevokeForm>, db_conn: db::Connection) -> ApiResult<JsonValue> {
+    let app = authenticate_client(&db_conn, &form.client_id, Some(&form.client_secret))?;
+    AccessToken::revoke(&db_conn, app.id, &form.token)?;
+
+    Ok(JsonValue(json!({})))
+}
@@ -0,0 +1,87 @@
+use rocket::http::Status as HttpStatus;
+use rocket_contrib::json::JsonValue;
+use serde_derive::Deserialize;
+
+use super::{entities, ApiError, ApiResult, FormOrJson};
+use crate::db;
+use crate::db::models::{Account, CanWrite, Status};
+use crate::db::validators::{self, MAX_STATUS_LENGTH};
+
+#[derive(Debug, FromForm, Deserialize)]
+pub struct CreateStatusForm {
+    status: String,
+    spoiler_text: Option<String>,
+}
+
+/// Posts a status as the user the client is acting as.
+#[post("/api/v1/statuses", data = "<form>")]
+pub fn create(
+    user: CanWrite,
+    form: FormOrJson<CreateStatusForm>,
+    db_conn: db::Connection,
+) -> ApiResult<JsonValue> {
+    if !user.0.is_confirmed() {
+        return Err(ApiError::new(
+            HttpStatus::Forbidden,
+            "Your login is missing a confirmed e-mail address",
+        ));
+    }
+
+    if form.status.trim().is_empty() {
+        return Err(ApiError::new(
+            HttpStatus::UnprocessableEntity,
+            "Validation failed: Text can't be blank",
+        ));
+    }
+
+    let spoiler_text = form.spoiler_text.as_ref().map_or("", String::as_str);
+    if validators::validate_status_length(&form.status, spoiler_text).is_err() {
+        return Err(ApiError::new(
+            HttpStatus::UnprocessableEntity,
+            format!(
+                "Validation failed: Text character limit of {} exceeded",
+                *MAX_STATUS_LENGTH
+            ),
+        ));
+    }
+
+    // like the web UI, store a blank content warning as no content warning at all.
+    let content_warning = form.spoiler_text.clone().filter(|cw| !cw.is_empty());
+
+    let account = user.0.get_account(&db_conn)?;
+    let status = Status::create_local(&db_conn, &account, form.status.clone(), content_warning)?;
+
+    Ok(JsonValue(entities::status(&db_conn, &status, &account)?))
+}
+
+/// Finds a status by ID, along with its author, unless the author has been suspended.
+fn visible_status(db_conn: &db::Connection, id: i64) -> ApiResult<(Status, Account)> {
+    let status = Status::by_id(db_conn, id)?.ok_or_else(ApiError::not_found)?;
+    let account = status.account(db_conn)?;
+
+    if account.suspended {
+        return Err(ApiError::not_found());
+    }
+
+    Ok((status, account))
+}
+
+#[get("/api/v1/statuses/<id>")]
+pub fn status(id: i64, db_conn: db::Connection) -> ApiResult<JsonValue> {
+    let (status, account) = visible_status(&db_conn, id)?;
+    Ok(JsonValue(entities::status(&db_conn, &status, &account)?))
+}
+
+/// Deletes one of the statuses of the user the client is acting as, returning it.
+#[delete("/api/v1/statuses/<id>")]
+pub fn delete(id: i64, user: CanWrite, db_conn: db::Connection) -> ApiResult<JsonValue> {
+    let (status, account) = visible_status(&db_conn, id)?;
+    if status.account_id != user.0.account_id {
+        return Err(ApiError::not_found());
+    }
+
+    let deleted = entities::status(&db_conn, &status, &account)?;
+    status.delete(&db_conn)?;
+
+    Ok(JsonValue(deleted))
+}
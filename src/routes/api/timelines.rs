@@ -0,0 +1,63 @@
+use rocket::http::uri::Uri;
+use rocket::request::LenientForm;
+use rocket_contrib::json::JsonValue;
+
+use super::{entities, ApiResult, PageParams, Paginated};
+use crate::db;
+use crate::db::models::{CanRead, Status};
+
+/// Returns a page of the federated timeline, or of the local one with `local=true`.
+#[get("/api/v1/timelines/public?<local>&<params..>")]
+pub fn public(
+    local: Option<bool>,
+    params: LenientForm<PageParams>,
+    db_conn: db::Connection,
+) -> ApiResult<Paginated> {
+    let local = local.unwrap_or(false);
+    let page = params.page();
+    let statuses = if local {
+        Status::local_page(&db_conn, &page)?
+    } else {
+        Status::federated_page(&db_conn, &page)?
+    };
+
+    let path = if local {
+        "/api/v1/timelines/public?local=true"
+    } else {
+        "/api/v1/timelines/public"
+    };
+    let body = entities::statuses(&db_conn, &statuses)?;
+    Ok(Paginated::new(JsonValue(body.into()), path, &statuses))
+}
+
+/// Returns a page of the home timeline of the user the client is acting as.
+#[get("/api/v1/timelines/home?<params..>")]
+pub fn home(
+    user: CanRead,
+    params: LenientForm<PageParams>,
+    db_conn: db::Connection,
+) -> ApiResult<Paginated> {
+    let account = user.0.get_account(&db_conn)?;
+    let statuses = Status::home_page(&db_conn, &account, &params.page())?;
+
+    let body = entities::statuses(&db_conn, &statuses)?;
+    Ok(Paginated::new(
+        JsonValue(body.into()),
+        "/api/v1/timelines/home",
+        &statuses,
+    ))
+}
+
+/// Returns a page of the statuses which use the hashtag `#hashtag`.
+#[get("/api/v1/timelines/tag/<hashtag>?<params..>")]
+pub fn tag(
+    hashtag: String,
+    params: LenientForm<PageParams>,
+    db_conn: db::Connection,
+) -> ApiResult<Paginated> {
+    let statuses = Status::hashtag_page(&db_conn, &hashtag, &params.page())?;
+
+    let body = entities::statuses(&db_conn, &statuses)?;
+    let path = format!("/api/v1/timelines/tag/{}", Uri::percent_encode(&hashtag));
+    Ok(Paginated::new(JsonValue(body.into()), &path, &statuses))
+}
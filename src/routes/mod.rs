@@ -1,3 +1,4 @@
 pub mod ap;
+pub mod api;
 pub mod ui;
 pub mod well_known;
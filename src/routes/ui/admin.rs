@@ -0,0 +1,249 @@
+use crate::db::models::{
+    ip_key, username_key, Account, Invite, Moderator, Role, Session, User, SIGN_IN_LIMIT,
+};
+use crate::db::{self, DbConnection};
+use crate::error::Perhaps;
+use crate::registrations::REGISTRATION_MODE;
+use crate::routes::ui::csrf::{CsrfForm, CsrfToken, Empty};
+use crate::routes::ui::templates::{
+    AdminAccountsTemplate, AdminInvitesTemplate, AdminRegistrationsTemplate, PendingUser,
+};
+use chrono::Duration;
+use diesel::QueryResult;
+use failure::Error;
+use resopt::try_resopt;
+use rocket::http::RawStr;
+use rocket::request::{FlashMessage, FromParam};
+use rocket::response::{Flash, Redirect};
+
+/// A moderation action which can be taken against an account.
+#[derive(Debug)]
+pub enum AccountAction {
+    Silence,
+    Unsilence,
+    Suspend,
+    Unsuspend,
+    ForceSensitive,
+    UnforceSensitive,
+    /// Lifts any block on signing in as this (local) account, after too many wrong passwords.
+    Unlock,
+}
+
+impl<'r> FromParam<'r> for AccountAction {
+    type Error = &'r RawStr;
+
+    fn from_param(param: &'r RawStr) -> Result<Self, Self::Error> {
+        match param.as_str() {
+            "silence" => Ok(AccountAction::Silence),
+            "unsilence" => Ok(AccountAction::Unsilence),
+            "suspend" => Ok(AccountAction::Suspend),
+            "unsuspend" => Ok(AccountAction::Unsuspend),
+            "force_sensitive" => Ok(AccountAction::ForceSensitive),
+            "unforce_sensitive" => Ok(AccountAction::UnforceSensitive),
+            "unlock" => Ok(AccountAction::Unlock),
+            _ => Err(param),
+        }
+    }
+}
+
+#[get("/admin/accounts?<max_id>")]
+pub fn accounts<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    moderator: Moderator,
+    max_id: Option<i64>,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<AdminAccountsTemplate<'static, 'b, 'c>, Error> {
+    let accounts = Account::before_id(&db_conn, max_id, 50)?;
+    let prev_page_id = if accounts.len() == 50 {
+        accounts.iter().map(|a| a.id).min()
+    } else {
+        None
+    };
+
+    Ok(HtmlTemplate!(AdminAccountsTemplate, csrf, flash, {
+        account: moderator.0.get_account(&db_conn)?,
+        accounts: accounts,
+        prev_page_id: prev_page_id
+    }))
+}
+
+#[post("/admin/accounts/<id>/<action>", data = "<_form>")]
+pub fn account_action(
+    id: i64,
+    action: AccountAction,
+    moderator: Moderator,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Perhaps<Flash<Redirect>> {
+    let account = try_resopt!(Account::by_id(&db_conn, id));
+    let redirect = Redirect::to("/admin/accounts");
+
+    if account.id == moderator.0.account_id {
+        return Ok(Some(Flash::error(redirect, "that's you!")));
+    }
+
+    // remote accounts have no user, and so no role beyond an ordinary user's
+    let target_role = User::by_account_id(&db_conn, account.id)?.map_or(Role::User, |u| u.role);
+    if !moderator.0.role.outranks(target_role) {
+        return Ok(Some(Flash::error(
+            redirect,
+            format!("you can't moderate a {}", target_role),
+        )));
+    }
+
+    match action {
+        AccountAction::Silence => account.set_silenced(&db_conn, true)?,
+        AccountAction::Unsilence => account.set_silenced(&db_conn, false)?,
+        AccountAction::Suspend => account.set_suspended(&db_conn, true)?,
+        AccountAction::Unsuspend => account.set_suspended(&db_conn, false)?,
+        AccountAction::ForceSensitive => account.set_force_sensitive(&db_conn, true)?,
+        AccountAction::UnforceSensitive => account.set_force_sensitive(&db_conn, false)?,
+        AccountAction::Unlock => unlock(&db_conn, &account)?,
+    }
+
+    Ok(Some(Flash::success(
+        redirect,
+        format!("updated {}", account.fully_qualified_username()),
+    )))
+}
+
+/// Lifts the sign-in limit on `account`'s username, and on the addresses its user has signed
+/// in from, so they aren't still blocked by failures counted against their IP address.
+fn unlock(db_conn: &DbConnection, account: &Account) -> QueryResult<()> {
+    SIGN_IN_LIMIT.clear(db_conn, &username_key(&account.username))?;
+
+    if let Some(user) = User::by_account_id(db_conn, account.id)? {
+        for session in Session::for_user(db_conn, user.id)? {
+            if let Some(ip) = &session.ip {
+                SIGN_IN_LIMIT.clear(db_conn, &ip_key(ip))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A decision on a user waiting for approval.
+#[derive(Debug)]
+pub enum RegistrationAction {
+    Approve,
+    Reject,
+}
+
+impl<'r> FromParam<'r> for RegistrationAction {
+    type Error = &'r RawStr;
+
+    fn from_param(param: &'r RawStr) -> Result<Self, Self::Error> {
+        match param.as_str() {
+            "approve" => Ok(RegistrationAction::Approve),
+            "reject" => Ok(RegistrationAction::Reject),
+            _ => Err(param),
+        }
+    }
+}
+
+/// Lists the users waiting for approval, oldest first.
+#[get("/admin/registrations")]
+pub fn registrations<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    moderator: Moderator,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<AdminRegistrationsTemplate<'static, 'b, 'c>, Error> {
+    let pending = User::awaiting_approval(&db_conn)?
+        .into_iter()
+        .map(|(user, account)| PendingUser { user, account })
+        .collect();
+
+    Ok(HtmlTemplate!(AdminRegistrationsTemplate, csrf, flash, {
+        account: moderator.0.get_account(&db_conn)?,
+        pending: pending,
+        mode: REGISTRATION_MODE.as_str()
+    }))
+}
+
+#[post("/admin/registrations/<id>/<action>", data = "<_form>")]
+pub fn registration_action(
+    id: i64,
+    action: RegistrationAction,
+    _moderator: Moderator,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Perhaps<Flash<Redirect>> {
+    let user = try_resopt!(User::by_id(&db_conn, id));
+    if user.approved {
+        return Ok(None);
+    }
+
+    let message = match action {
+        RegistrationAction::Approve => {
+            user.approve(&db_conn)?;
+            "approved"
+        },
+        RegistrationAction::Reject => {
+            user.reject(&db_conn)?;
+            "rejected"
+        },
+    };
+
+    Ok(Some(Flash::success(
+        Redirect::to("/admin/registrations"),
+        message,
+    )))
+}
+
+/// Lists the invites the current moderator has made.
+#[get("/admin/invites")]
+pub fn invites<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    moderator: Moderator,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<AdminInvitesTemplate<'static, 'b, 'c>, Error> {
+    let invites = Invite::by_user(&db_conn, moderator.0.id)?;
+
+    Ok(HtmlTemplate!(AdminInvitesTemplate, csrf, flash, {
+        account: moderator.0.get_account(&db_conn)?,
+        invites: invites
+    }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct CreateInviteForm {
+    /// How many times the invite can be used; blank for no limit.
+    max_uses: Option<i32>,
+    /// How many days the invite is valid for; blank for no expiry.
+    expires_in_days: Option<i64>,
+}
+
+#[post("/admin/invites", data = "<form>")]
+pub fn create_invite(
+    form: CsrfForm<CreateInviteForm>,
+    moderator: Moderator,
+    db_conn: db::Connection,
+) -> Result<Flash<Redirect>, Error> {
+    let max_uses = form.max_uses.filter(|&n| n > 0);
+    let valid_for = form.expires_in_days.filter(|&n| n > 0).map(Duration::days);
+    let invite = Invite::create(&db_conn, moderator.0.id, max_uses, valid_for)?;
+
+    Ok(Flash::success(
+        Redirect::to("/admin/invites"),
+        format!("invite created: {}", invite.link()),
+    ))
+}
+
+#[post("/admin/invites/<id>/revoke", data = "<_form>")]
+pub fn revoke_invite(
+    id: i64,
+    moderator: Moderator,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Result<Flash<Redirect>, Error> {
+    Invite::revoke(&db_conn, moderator.0.id, id)?;
+
+    Ok(Flash::success(
+        Redirect::to("/admin/invites"),
+        "invite revoked",
+    ))
+}
@@ -1,20 +1,39 @@
 use crate::crypto;
-use crate::db::models::{NewAccount, NewUser, User};
+use crate::db::models::{
+    email_key, ip_key, username_key, Account, ClientInfo, Invite, NewAccount, NewUser,
+    RecoveryCode, Session, User, EMAIL_LIMIT, RESET_PASSWORD_VALIDITY_HOURS, SESSION_COOKIE,
+    SIGN_IN_LIMIT, SIGN_UP_LIMIT,
+};
 use crate::db::validators;
-use crate::db::{self, id_generator, LOCAL_ACCOUNT_DOMAIN};
-use crate::routes::ui::templates::{SigninTemplate, SignupTemplate};
+use crate::db::{self, id_generator, DbConnection, LOCAL_ACCOUNT_DOMAIN};
+use crate::error::Perhaps;
+use crate::mailer::{ConfirmationEmail, Email, Mailer, PasswordResetEmail};
+use crate::registrations::REGISTRATION_MODE;
+use crate::routes::ui::csrf::{CsrfForm, CsrfToken, Empty};
+use crate::routes::ui::templates::{
+    EmailFormTemplate, ResetPasswordTemplate, SigninTemplate, SignupTemplate, TwoFactorTemplate,
+};
+use crate::routes::ui::validation_error_description;
+use crate::util::Either;
+use crate::BASE_URL;
+use chrono::offset::Utc;
 use diesel::Connection;
 use failure::Error;
-use itertools::Itertools;
+use resopt::try_resopt;
+use rocket::http::uri::Uri;
 use rocket::http::{Cookie, Cookies};
-use rocket::request::{FlashMessage, Form};
+use rocket::request::FlashMessage;
 use rocket::response::{Flash, Redirect};
-use std::borrow::Cow;
+use rocket::State;
+use slog_scope::warn;
 use validator::Validate;
 
 #[get("/auth/sign_in")]
-pub fn signin_get<'b, 'c>(flash: Option<FlashMessage<'b, 'c>>) -> SigninTemplate<'static, 'b, 'c> {
-    HtmlTemplate!(SigninTemplate, flash)
+pub fn signin_get<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    csrf: CsrfToken,
+) -> SigninTemplate<'static, 'b, 'c> {
+    HtmlTemplate!(SigninTemplate, csrf, flash)
 }
 
 #[derive(Debug, FromForm)]
@@ -25,33 +44,226 @@ pub struct SigninForm {
 
 #[post("/auth/sign_in", data = "<form>")]
 pub fn signin_post(
-    form: Form<SigninForm>,
+    form: CsrfForm<SigninForm>,
+    client: ClientInfo,
     mut cookies: Cookies,
     db_conn: db::Connection,
 ) -> Result<Flash<Redirect>, Error> {
+    // throttle by the username as typed, whether or not it exists, so being blocked doesn't
+    // give away which usernames are real.
+    let mut keys = vec![username_key(&form.username)];
+    keys.extend(client.ip.as_ref().map(|ip| ip_key(ip)));
+
+    if SIGN_IN_LIMIT.blocked_until(&db_conn, &keys)?.is_some() {
+        return Ok(Flash::error(
+            Redirect::to("/auth/sign_in"),
+            "too many failed attempts to sign in; wait a while before trying again.",
+        ));
+    }
+
     let user = User::by_username(&db_conn, &form.username)?;
 
     if let Some(user) = user {
         if user.valid_password(&form.password) {
-            cookies.add_private(Cookie::new("uid", user.id.to_string()));
+            // with two factors, the limit only lets up once the code's right too; otherwise
+            // getting the password right again would reset the count of wrong codes.
+            if !user.otp_enabled {
+                SIGN_IN_LIMIT.clear(&db_conn, &keys[0])?;
+            }
+            user.upgrade_password_hash(&db_conn, &form.password)?;
+
+            if !user.approved {
+                return Ok(Flash::error(
+                    Redirect::to("/auth/sign_in"),
+                    "your account is still waiting for a moderator to approve it.",
+                ));
+            }
 
-            return Ok(Flash::success(Redirect::to("/"), "signed in!"));
+            if user.otp_enabled {
+                let pending = format!("{}:{}", user.id, Utc::now().timestamp());
+                cookies.add_private(Cookie::new(TWO_FACTOR_COOKIE, pending));
+
+                return Ok(Flash::success(
+                    Redirect::to("/auth/two_factor"),
+                    "enter the code from your authenticator app",
+                ));
+            }
+
+            start_session(&db_conn, &user, client, &mut cookies)?;
+            return Ok(Flash::success(after_sign_in(&mut cookies), "signed in!"));
         }
     }
 
+    for key in &keys {
+        SIGN_IN_LIMIT.hit(&db_conn, key)?;
+    }
+
     Ok(Flash::error(
         Redirect::to("/auth/sign_in"),
         "wrong password (or username!)",
     ))
 }
 
-#[post("/auth/sign_out")]
-pub fn signout(user: Option<User>, mut cookies: Cookies) -> Redirect {
-    if user.is_some() {
-        cookies.remove_private(Cookie::named("uid"));
+/// Name of the private cookie remembering where to send somebody once they've signed in.
+const RETURN_TO_COOKIE: &str = "return_to";
+
+/// Sends somebody to sign in, with `message`, and back to `path` (on this site) once they have.
+pub fn sign_in_and_return(cookies: &mut Cookies, path: String, message: &str) -> Flash<Redirect> {
+    cookies.add_private(Cookie::new(RETURN_TO_COOKIE, path));
+
+    Flash::error(Redirect::to("/auth/sign_in"), message)
+}
+
+/// Returns where to go now somebody has signed in: back where they came from, if they were
+/// sent to sign in part of the way through something, or the front page.
+fn after_sign_in(cookies: &mut Cookies) -> Redirect {
+    let path = cookies
+        .get_private(RETURN_TO_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        // only ever somewhere on this site.
+        .filter(|path| path.starts_with('/') && !path.starts_with("//"));
+    cookies.remove_private(Cookie::named(RETURN_TO_COOKIE));
+
+    Redirect::to(path.unwrap_or_else(|| "/".to_string()))
+}
+
+/// Signs `user` in, in a brand new session.
+fn start_session(
+    db_conn: &DbConnection,
+    user: &User,
+    client: ClientInfo,
+    cookies: &mut Cookies,
+) -> Result<(), Error> {
+    let (_, token) = Session::create(db_conn, user.id, client)?;
+    cookies.add_private(Cookie::new(SESSION_COOKIE, token));
+    CsrfToken::rotate(cookies);
+
+    Ok(())
+}
+
+/// Name of the private cookie remembering who got their password right, but still
+/// has to enter a TOTP code.
+const TWO_FACTOR_COOKIE: &str = "two_factor_user";
+
+/// How long somebody has to enter their TOTP code after their password, in seconds.
+const TWO_FACTOR_TIMEOUT: i64 = 5 * 60;
+
+/// Returns the user waiting to complete the second step of signing in, if there's one
+/// and they haven't taken too long.
+fn pending_two_factor_user(
+    db_conn: &DbConnection,
+    cookies: &mut Cookies,
+) -> Result<Option<User>, Error> {
+    let pending = match cookies.get_private(TWO_FACTOR_COOKIE) {
+        Some(cookie) => cookie.value().to_string(),
+        None => return Ok(None),
+    };
+
+    let mut parts = pending.splitn(2, ':').map(str::parse::<i64>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(uid)), Some(Ok(since))) if Utc::now().timestamp() - since < TWO_FACTOR_TIMEOUT => {
+            Ok(User::by_id(db_conn, uid)?)
+        },
+        _ => {
+            cookies.remove_private(Cookie::named(TWO_FACTOR_COOKIE));
+            Ok(None)
+        },
+    }
+}
+
+#[get("/auth/two_factor")]
+pub fn two_factor_get<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    csrf: CsrfToken,
+    mut cookies: Cookies,
+    db_conn: db::Connection,
+) -> Result<Either<TwoFactorTemplate<'static, 'b, 'c>, Flash<Redirect>>, Error> {
+    if pending_two_factor_user(&db_conn, &mut cookies)?.is_none() {
+        return Ok(Either::Right(two_factor_expired()));
     }
 
-    Redirect::to("/")
+    Ok(Either::Left(HtmlTemplate!(TwoFactorTemplate, csrf, flash)))
+}
+
+#[derive(Debug, FromForm)]
+pub struct TwoFactorForm {
+    code: String,
+}
+
+/// Finishes signing in, given either a TOTP code or one of the user's recovery codes.
+#[post("/auth/two_factor", data = "<form>")]
+pub fn two_factor_post(
+    form: CsrfForm<TwoFactorForm>,
+    client: ClientInfo,
+    mut cookies: Cookies,
+    db_conn: db::Connection,
+) -> Result<Flash<Redirect>, Error> {
+    let user = match pending_two_factor_user(&db_conn, &mut cookies)? {
+        Some(user) => user,
+        None => return Ok(two_factor_expired()),
+    };
+
+    // wrong codes count against the same limit as wrong passwords, so they can't be guessed.
+    let user_key =
+        Account::by_id(&db_conn, user.account_id)?.map(|account| username_key(&account.username));
+    let mut keys: Vec<String> = user_key.iter().cloned().collect();
+    keys.extend(client.ip.as_ref().map(|ip| ip_key(ip)));
+
+    if SIGN_IN_LIMIT.blocked_until(&db_conn, &keys)?.is_some() {
+        return Ok(Flash::error(
+            Redirect::to("/auth/two_factor"),
+            "too many failed attempts to sign in; wait a while before trying again.",
+        ));
+    }
+
+    let message = if user.redeem_otp_code(&db_conn, &form.code)? {
+        "signed in!".to_string()
+    } else if RecoveryCode::redeem(&db_conn, user.id, &form.code)? {
+        format!(
+            "signed in with a recovery code; you have {} left.",
+            RecoveryCode::count_for_user(&db_conn, user.id)?
+        )
+    } else {
+        for key in &keys {
+            SIGN_IN_LIMIT.hit(&db_conn, key)?;
+        }
+
+        return Ok(Flash::error(
+            Redirect::to("/auth/two_factor"),
+            "wrong code, try again",
+        ));
+    };
+
+    if let Some(key) = &user_key {
+        SIGN_IN_LIMIT.clear(&db_conn, key)?;
+    }
+    cookies.remove_private(Cookie::named(TWO_FACTOR_COOKIE));
+    start_session(&db_conn, &user, client, &mut cookies)?;
+
+    Ok(Flash::success(after_sign_in(&mut cookies), message))
+}
+
+fn two_factor_expired() -> Flash<Redirect> {
+    Flash::error(
+        Redirect::to("/auth/sign_in"),
+        "that took too long; sign in again.",
+    )
+}
+
+#[post("/auth/sign_out", data = "<_form>")]
+pub fn signout(
+    session: Option<Session>,
+    mut cookies: Cookies,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Result<Redirect, Error> {
+    if let Some(session) = session {
+        session.delete(&db_conn)?;
+        cookies.remove_private(Cookie::named(SESSION_COOKIE));
+        CsrfToken::rotate(&mut cookies);
+    }
+
+    Ok(Redirect::to("/"))
 }
 
 #[derive(FromForm, Validate, Debug)]
@@ -70,52 +282,100 @@ pub struct SignupForm {
     username: String,
     #[validate(email)]
     email: String,
-    #[validate(length(
-        min = 3,
-        max = 64,
-        message = "Password must be between 3 and 64 characters long."
-    ))]
+    #[validate(custom = "validators::validate_password")]
     password: String,
+    invite: Option<String>,
+}
+
+/// Returns where to send somebody back to on a problem signing up, keeping their invite code.
+fn signup_redirect(invite: Option<&str>) -> Redirect {
+    match invite {
+        Some(code) => Redirect::to(format!(
+            "/auth/sign_up?invite={}",
+            Uri::percent_encode(code)
+        )),
+        None => Redirect::to("/auth/sign_up"),
+    }
 }
 
-#[get("/auth/sign_up")]
-pub fn signup_get<'b, 'c>(flash: Option<FlashMessage<'b, 'c>>) -> SignupTemplate<'static, 'b, 'c> {
-    HtmlTemplate!(SignupTemplate, flash)
+#[get("/auth/sign_up?<invite>")]
+pub fn signup_get<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    invite: Option<String>,
+    csrf: CsrfToken,
+) -> Option<SignupTemplate<'static, 'b, 'c>> {
+    if !REGISTRATION_MODE.allows_signups() {
+        return None;
+    }
+
+    Some(HtmlTemplate!(SignupTemplate, csrf, flash, {
+        requires_invite: REGISTRATION_MODE.requires_invite(),
+        requires_approval: REGISTRATION_MODE.requires_approval(),
+        invite: invite.unwrap_or_default()
+    }))
 }
 
 #[post("/auth/sign_up", data = "<form>")]
 pub fn signup_post(
-    form: Form<SignupForm>,
+    form: CsrfForm<SignupForm>,
+    client: ClientInfo,
+    mailer: State<Box<dyn Mailer>>,
     db_conn: db::Connection,
-) -> Result<Flash<Redirect>, Error> {
+) -> Perhaps<Flash<Redirect>> {
+    if !REGISTRATION_MODE.allows_signups() {
+        return Ok(None);
+    }
+
+    let invite = form
+        .invite
+        .as_ref()
+        .map(|code| code.trim())
+        .filter(|code| !code.is_empty());
+
+    let keys: Vec<String> = client
+        .ip
+        .as_ref()
+        .map(|ip| ip_key(ip))
+        .into_iter()
+        .collect();
+    if SIGN_UP_LIMIT.blocked_until(&db_conn, &keys)?.is_some() {
+        return Ok(Some(Flash::error(
+            signup_redirect(invite),
+            "too many sign-ups from here; wait a while before trying again.",
+        )));
+    }
+
     if let Err(errs) = form.validate() {
-        let errs = errs.field_errors();
+        let error_desc = validation_error_description(&errs);
 
-        // concatenate the error descriptions, with commas between them.
-        // TODO: make this less ugly :(
-        let error_desc = errs
-            .iter()
-            .flat_map(|(_, &errs)| errs)
-            .map(|e| {
-                let msg = e.message.to_owned();
-                msg.unwrap_or(Cow::Borrowed("unknown error"))
-            })
-            .join(", ");
+        return Ok(Some(Flash::error(signup_redirect(invite), error_desc)));
+    }
 
-        return Ok(Flash::error(Redirect::to("/auth/sign_up"), error_desc));
+    // count every attempt that gets this far, so usernames can't be probed freely either.
+    for key in &keys {
+        SIGN_UP_LIMIT.hit(&db_conn, key)?;
     }
+
     if let Ok(Some(_)) =
         db::models::Account::fetch_local_by_username(&db_conn, form.username.as_str())
     {
-        return Ok(Flash::error(
-            Redirect::to("/auth/sign_up"),
+        return Ok(Some(Flash::error(
+            signup_redirect(invite),
             "Username taken",
-        ));
+        )));
     }
 
     let keypair = crypto::generate_keypair()?;
+    let confirmation_token = crypto::random_token()?;
+    let approved = !REGISTRATION_MODE.requires_approval();
+
+    let created = (*db_conn).transaction::<_, Error, _>(|| {
+        // redeeming the invite in the same transaction means it's only used up if
+        // the sign-up actually happens.
+        if REGISTRATION_MODE.requires_invite() && !Invite::redeem(&db_conn, invite.unwrap_or(""))? {
+            return Ok(false);
+        }
 
-    (*db_conn).transaction::<_, _, _>(|| {
         let mut id_gen = id_generator();
 
         let account = NewAccount {
@@ -130,6 +390,8 @@ pub fn signup_post(
 
             privkey: Some(keypair.private),
             pubkey:  keypair.public,
+
+            inbox_uri: None,
         }
         .insert(&db_conn)?;
 
@@ -138,9 +400,271 @@ pub fn signup_post(
             email: form.email.to_owned(),
             encrypted_password: User::encrypt_password(&form.password),
             account_id: account.id,
+
+            confirmation_token_hash: Some(crypto::hash_token(&confirmation_token)),
+            approved,
         }
-        .insert(&db_conn)
+        .insert(&db_conn)?;
+
+        Ok(true)
     })?;
 
-    Ok(Flash::success(Redirect::to("/"), "signed up!"))
+    if !created {
+        return Ok(Some(Flash::error(
+            signup_redirect(invite),
+            "that invite code isn't valid, or has been used up.",
+        )));
+    }
+
+    // the account exists now whatever happens, so don't fail the sign-up over the email;
+    // it can be sent again from the sign-in page.
+    if let Err(e) = send_confirmation(&mailer, &form.email, &form.username, &confirmation_token) {
+        warn!("Failed to send a confirmation email: {}", e);
+
+        return Ok(Some(Flash::error(
+            Redirect::to("/auth/confirmation/new"),
+            "signed up! but we couldn't send you an email to confirm your account; \
+             try sending it again.",
+        )));
+    }
+
+    let message = if approved {
+        "signed up! check your email to confirm your account."
+    } else {
+        "signed up! check your email to confirm your account; \
+         you can sign in once a moderator has approved it."
+    };
+    Ok(Some(Flash::success(Redirect::to("/"), message)))
+}
+
+/// Emails `address` a link to confirm it with `token`.
+fn send_confirmation(
+    mailer: &State<Box<dyn Mailer>>,
+    address: &str,
+    username: &str,
+    token: &str,
+) -> Result<(), Error> {
+    let link = format!("{}/auth/confirm/{}", *BASE_URL, token);
+
+    mailer.send(&Email::new(
+        address,
+        "Confirm your email address",
+        &ConfirmationEmail {
+            username,
+            link: &link,
+        },
+    )?)
+}
+
+#[get("/auth/confirm/<token>")]
+pub fn confirm(token: String, db_conn: db::Connection) -> Perhaps<Flash<Redirect>> {
+    let user = try_resopt!(User::by_confirmation_token(&db_conn, &token));
+    user.confirm(&db_conn)?;
+
+    Ok(Some(Flash::success(
+        Redirect::to("/"),
+        "thanks for confirming your email address!",
+    )))
+}
+
+#[derive(Debug, FromForm)]
+pub struct EmailForm {
+    email: String,
+}
+
+#[get("/auth/confirmation/new")]
+pub fn resend_confirmation_get<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    csrf: CsrfToken,
+) -> EmailFormTemplate<'static, 'b, 'c> {
+    HtmlTemplate!(EmailFormTemplate, csrf, flash, {
+        title: "Resend confirmation email",
+        action: "/auth/confirmation",
+        button: "Resend"
+    })
+}
+
+/// Counts a request to email `address`, returning whether it or the client asking has made
+/// too many lately, in which case nothing should be sent.
+///
+/// Like the sign-in limit, this goes by the address as typed, whether or not it has an
+/// account, so being blocked doesn't give away which addresses do.
+fn email_throttled(
+    db_conn: &DbConnection,
+    address: &str,
+    client: &ClientInfo,
+) -> Result<bool, Error> {
+    let mut keys = vec![email_key(address)];
+    keys.extend(client.ip.as_ref().map(|ip| ip_key(ip)));
+
+    if EMAIL_LIMIT.blocked_until(db_conn, &keys)?.is_some() {
+        return Ok(true);
+    }
+
+    for key in &keys {
+        EMAIL_LIMIT.hit(db_conn, key)?;
+    }
+
+    Ok(false)
+}
+
+#[post("/auth/confirmation", data = "<form>")]
+pub fn resend_confirmation_post(
+    form: CsrfForm<EmailForm>,
+    client: ClientInfo,
+    mailer: State<Box<dyn Mailer>>,
+    db_conn: db::Connection,
+) -> Result<Flash<Redirect>, Error> {
+    if email_throttled(&db_conn, &form.email, &client)? {
+        return Ok(Flash::error(
+            Redirect::to("/auth/confirmation/new"),
+            "too many emails asked for; wait a while before trying again.",
+        ));
+    }
+
+    if let Some(user) = User::by_email(&db_conn, &form.email)? {
+        if !user.is_confirmed() {
+            let token = crypto::random_token()?;
+            user.set_confirmation_token(&db_conn, &token)?;
+
+            // failing here would give away that the address has an account.
+            let account = user.get_account(&db_conn)?;
+            if let Err(e) = send_confirmation(&mailer, &form.email, &account.username, &token) {
+                warn!("Failed to send a confirmation email: {}", e);
+            }
+        }
+    }
+
+    // the same answer either way, so this doesn't reveal who has an account here.
+    Ok(Flash::success(
+        Redirect::to("/auth/sign_in"),
+        "if that address needs confirming, we've sent it a new link.",
+    ))
+}
+
+#[get("/auth/password/new")]
+pub fn reset_password_request_get<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    csrf: CsrfToken,
+) -> EmailFormTemplate<'static, 'b, 'c> {
+    HtmlTemplate!(EmailFormTemplate, csrf, flash, {
+        title: "Reset your password",
+        action: "/auth/password",
+        button: "Send reset link"
+    })
+}
+
+#[post("/auth/password", data = "<form>")]
+pub fn reset_password_request_post(
+    form: CsrfForm<EmailForm>,
+    client: ClientInfo,
+    mailer: State<Box<dyn Mailer>>,
+    db_conn: db::Connection,
+) -> Result<Flash<Redirect>, Error> {
+    if email_throttled(&db_conn, &form.email, &client)? {
+        return Ok(Flash::error(
+            Redirect::to("/auth/password/new"),
+            "too many emails asked for; wait a while before trying again.",
+        ));
+    }
+
+    if let Some(user) = User::by_email(&db_conn, &form.email)? {
+        let token = crypto::random_token()?;
+        user.set_reset_password_token(&db_conn, &token)?;
+
+        // failing here would give away that the address has an account.
+        let account = user.get_account(&db_conn)?;
+        if let Err(e) = send_password_reset(&mailer, &form.email, &account.username, &token) {
+            warn!("Failed to send a password reset email: {}", e);
+        }
+    }
+
+    // the same answer either way, so this doesn't reveal who has an account here.
+    Ok(Flash::success(
+        Redirect::to("/auth/sign_in"),
+        "if that address belongs to an account, we've sent it a link to reset the password.",
+    ))
+}
+
+/// Emails `address` a link to reset the password with `token`.
+fn send_password_reset(
+    mailer: &State<Box<dyn Mailer>>,
+    address: &str,
+    username: &str,
+    token: &str,
+) -> Result<(), Error> {
+    let link = format!("{}/auth/password/reset/{}", *BASE_URL, token);
+
+    mailer.send(&Email::new(
+        address,
+        "Reset your password",
+        &PasswordResetEmail {
+            username,
+            link: &link,
+            hours: RESET_PASSWORD_VALIDITY_HOURS,
+        },
+    )?)
+}
+
+#[get("/auth/password/reset/<token>")]
+pub fn reset_password_get<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    token: String,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<Either<ResetPasswordTemplate<'static, 'b, 'c>, Flash<Redirect>>, Error> {
+    if User::by_reset_password_token(&db_conn, &token)?.is_none() {
+        return Ok(Either::Right(expired_reset_link()));
+    }
+
+    Ok(Either::Left(HtmlTemplate!(
+        ResetPasswordTemplate,
+        csrf,
+        flash,
+        { token: token }
+    )))
+}
+
+#[derive(Debug, FromForm, Validate)]
+pub struct ResetPasswordForm {
+    #[validate(custom = "validators::validate_password")]
+    password: String,
+    password_confirmation: String,
+}
+
+#[post("/auth/password/reset/<token>", data = "<form>")]
+pub fn reset_password_post(
+    token: String,
+    form: CsrfForm<ResetPasswordForm>,
+    db_conn: db::Connection,
+) -> Result<Flash<Redirect>, Error> {
+    let user = match User::by_reset_password_token(&db_conn, &token)? {
+        Some(user) => user,
+        None => return Ok(expired_reset_link()),
+    };
+    let redirect = Redirect::to(format!("/auth/password/reset/{}", token));
+
+    if form.password != form.password_confirmation {
+        return Ok(Flash::error(redirect, "the passwords don't match"));
+    }
+
+    if let Err(errs) = form.validate() {
+        let error_desc = validation_error_description(&errs);
+
+        return Ok(Flash::error(redirect, error_desc));
+    }
+
+    user.reset_password(&db_conn, &form.password)?;
+
+    Ok(Flash::success(
+        Redirect::to("/auth/sign_in"),
+        "password changed; you can sign in with it now.",
+    ))
+}
+
+fn expired_reset_link() -> Flash<Redirect> {
+    Flash::error(
+        Redirect::to("/auth/password/new"),
+        "that link has expired (or was already used); try again.",
+    )
 }
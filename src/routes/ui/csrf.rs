@@ -0,0 +1,163 @@
+//! Protection against cross-site request forgery for the HTML UI.
+//!
+//! Every browser gets a random token, kept in a private cookie and rendered into each form as
+//! a hidden `csrf_token` field. Form POSTs go through `CsrfForm`, which refuses the request
+//! (with a 403, see `forbidden`) unless the field matches the cookie.
+
+use openssl::memcmp;
+use rocket::data::{self, Data, FromDataSimple};
+use rocket::http::{Cookie, Cookies, Status};
+use rocket::request::{self, FormItems, FromForm, FromRequest, Request};
+use rocket::Outcome;
+use std::io::Read;
+use std::ops::Deref;
+
+use crate::crypto;
+
+/// Name of the private cookie holding the CSRF token.
+pub const CSRF_COOKIE: &str = "csrf";
+
+/// Name of the form field the token is submitted in.
+pub const CSRF_FIELD: &str = "csrf_token";
+
+/// The CSRF token of the current browser, created on first use.
+#[derive(Clone, Debug)]
+pub struct CsrfToken(pub String);
+
+impl CsrfToken {
+    /// Forgets the token of the current browser, so that a fresh one is made next time.
+    ///
+    /// Called whenever somebody signs in or out, so a token can't outlive a session.
+    pub fn rotate(cookies: &mut Cookies) {
+        cookies.remove_private(Cookie::named(CSRF_COOKIE));
+    }
+
+    /// Checks (in constant time) whether `candidate` is this token.
+    pub fn matches(&self, candidate: &str) -> bool {
+        self.0.len() == candidate.len() && memcmp::eq(self.0.as_bytes(), candidate.as_bytes())
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for CsrfToken {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<CsrfToken, ()> {
+        let token = request.local_cache(|| {
+            let mut cookies = request.cookies();
+            if let Some(cookie) = cookies.get_private(CSRF_COOKIE) {
+                return Some(CsrfToken(cookie.value().to_string()));
+            }
+
+            let token = crypto::random_token().ok()?;
+            cookies.add_private(Cookie::new(CSRF_COOKIE, token.clone()));
+            Some(CsrfToken(token))
+        });
+
+        match token {
+            Some(token) => Outcome::Success(token.clone()),
+            None => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// Marks (in the request-local cache) that a request was refused for a bad CSRF token,
+/// so that `forbidden` can say so.
+struct CsrfFailed(bool);
+
+/// Whether `request` was refused because of a missing or bad CSRF token.
+pub fn csrf_failed(request: &Request) -> bool {
+    request.local_cache(|| CsrfFailed(false)).0
+}
+
+/// A form submitted with a valid CSRF token.
+///
+/// This works like `rocket::request::Form`, parsing `T` strictly from the fields other than
+/// `csrf_token`.
+#[derive(Debug)]
+pub struct CsrfForm<T>(pub T);
+
+impl<T> Deref for CsrfForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// A form with no fields we care about, for POSTs which are nothing but a button.
+#[derive(Debug)]
+pub struct Empty;
+
+impl<'f> FromForm<'f> for Empty {
+    type Error = ();
+
+    fn from_form(_items: &mut FormItems<'f>, _strict: bool) -> Result<Empty, ()> {
+        Ok(Empty)
+    }
+}
+
+impl<T> FromDataSimple for CsrfForm<T>
+where
+    T: for<'f> FromForm<'f>,
+{
+    type Error = String;
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Self, String> {
+        let refuse = |reason: &str| {
+            request.local_cache(|| CsrfFailed(true));
+            Outcome::Failure((Status::Forbidden, reason.to_string()))
+        };
+
+        // anything but a form can't carry the token, and so can't be trusted either.
+        if !request.content_type().map_or(false, |ct| ct.is_form()) {
+            return refuse("not a form submission");
+        }
+
+        let limit = request.limits().get("forms").unwrap_or(32 * 1024);
+        let mut body = String::new();
+        if let Err(e) = data.open().take(limit).read_to_string(&mut body) {
+            return Outcome::Failure((Status::BadRequest, e.to_string()));
+        }
+
+        let token = match request.guard::<CsrfToken>() {
+            Outcome::Success(token) => token,
+            _ => return Outcome::Failure((Status::InternalServerError, "no token".to_string())),
+        };
+
+        let mut submitted = None;
+        let mut fields = Vec::new();
+        for item in FormItems::from(body.as_str()) {
+            if item.key.as_str() == CSRF_FIELD {
+                submitted = item.value.url_decode().ok();
+            } else {
+                fields.push(item.raw.as_str());
+            }
+        }
+
+        match submitted {
+            Some(ref submitted) if token.matches(submitted) => {},
+            _ => return refuse("bad CSRF token"),
+        }
+
+        let fields = fields.join("&");
+        let form = T::from_form(&mut FormItems::from(fields.as_str()), true).ok();
+        match form {
+            Some(form) => Outcome::Success(CsrfForm(form)),
+            None => Outcome::Failure((Status::UnprocessableEntity, "invalid form".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_only_match_themselves() {
+        let token = CsrfToken("ABCDEF".to_string());
+        assert!(token.matches("ABCDEF"));
+        assert!(!token.matches("ABCDEG"));
+        assert!(!token.matches("ABCDE"));
+        assert!(!token.matches(""));
+    }
+}
@@ -0,0 +1,81 @@
+use crate::activitypub::{activities, delivery};
+use crate::db;
+use crate::db::models::{Account, FollowRequest, User};
+use crate::error::Perhaps;
+use crate::routes::ui::csrf::{CsrfForm, CsrfToken, Empty};
+use crate::routes::ui::templates::FollowRequestsTemplate;
+use failure::Error;
+use resopt::try_resopt;
+use rocket::http::RawStr;
+use rocket::request::{FlashMessage, FromParam};
+use rocket::response::{Flash, Redirect};
+
+/// What to do with a pending follow request.
+#[derive(Debug)]
+pub enum FollowRequestAction {
+    Authorize,
+    Reject,
+}
+
+impl<'r> FromParam<'r> for FollowRequestAction {
+    type Error = &'r RawStr;
+
+    fn from_param(param: &'r RawStr) -> Result<Self, Self::Error> {
+        match param.as_str() {
+            "authorize" => Ok(FollowRequestAction::Authorize),
+            "reject" => Ok(FollowRequestAction::Reject),
+            _ => Err(param),
+        }
+    }
+}
+
+#[get("/settings/follow_requests")]
+pub fn follow_requests<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    user: User,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<FollowRequestsTemplate<'static, 'b, 'c>, Error> {
+    let account = user.get_account(&db_conn)?;
+
+    Ok(HtmlTemplate!(FollowRequestsTemplate, csrf, flash, {
+        requesters: account.follow_requesters(&db_conn)?,
+        account: account
+    }))
+}
+
+/// Authorizes or rejects the request from the account `id` to follow the current user,
+/// letting the requester know if they're remote.
+#[post("/settings/follow_requests/<id>/<action>", data = "<_form>")]
+pub fn follow_request_action(
+    id: i64,
+    action: FollowRequestAction,
+    user: User,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Perhaps<Flash<Redirect>> {
+    let account = user.get_account(&db_conn)?;
+    let request = try_resopt!(FollowRequest::between(&db_conn, id, account.id));
+    let source = try_resopt!(Account::by_id(&db_conn, id));
+
+    let follow = activities::follow(&request, &source, &account);
+    let (response, message) = match action {
+        FollowRequestAction::Authorize => {
+            request.authorize(&db_conn)?;
+            (activities::accept_follow(follow, &account), "authorized")
+        },
+        FollowRequestAction::Reject => {
+            request.reject(&db_conn)?;
+            (activities::reject_follow(follow, &account), "rejected")
+        },
+    };
+
+    if source.uri.is_some() {
+        delivery::deliver(&response, &account, &source.get_inbox_endpoint())?;
+    }
+
+    Ok(Some(Flash::success(
+        Redirect::to("/settings/follow_requests"),
+        format!("{} {}", message, source.fully_qualified_username()),
+    )))
+}
@@ -1,24 +1,34 @@
-use crate::db::models::{Account, NewStatus, Status, User};
-use crate::db::{self, id_generator};
+use crate::db;
+use crate::db::models::{Account, Confirmed, Relationship, Status, User};
+use crate::db::validators;
 use crate::error::Perhaps;
+use crate::registrations::REGISTRATION_MODE;
 use crate::util::{Either, StatusID, Username};
-use chrono::offset::Utc;
+use crate::MEDIA_DIR;
 use failure::Error;
 use itertools::Itertools;
 use resopt::try_resopt;
 use rocket::http::RawStr;
-use rocket::request::{FlashMessage, Form, FromFormValue};
+use rocket::request::{FlashMessage, FromFormValue, Request};
 use rocket::response::{Flash, NamedFile, Redirect};
-use rocket::Route;
+use rocket::{Catcher, Outcome, Route};
 use std::borrow::Cow;
 use std::path::{Path, PathBuf};
-use validator::Validate;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 #[macro_use]
 mod templates;
+mod admin;
 mod auth;
+mod csrf;
+mod follow_requests;
+mod notifications;
+mod oauth;
+mod relationships;
+mod settings;
 pub mod view_helpers;
 
+use self::csrf::{CsrfForm, CsrfToken, Empty};
 use self::templates::*;
 
 pub fn routes() -> Vec<Route> {
@@ -34,13 +44,75 @@ pub fn routes() -> Vec<Route> {
         delete_status,
         auth::signin_get,
         auth::signin_post,
+        auth::two_factor_get,
+        auth::two_factor_post,
         auth::signout,
         auth::signup_get,
         auth::signup_post,
-        static_files
+        auth::confirm,
+        auth::resend_confirmation_get,
+        auth::resend_confirmation_post,
+        auth::reset_password_request_get,
+        auth::reset_password_request_post,
+        auth::reset_password_get,
+        auth::reset_password_post,
+        admin::accounts,
+        admin::account_action,
+        admin::registrations,
+        admin::registration_action,
+        admin::invites,
+        admin::create_invite,
+        admin::revoke_invite,
+        follow_requests::follow_requests,
+        follow_requests::follow_request_action,
+        notifications::notifications,
+        oauth::authorize_get,
+        oauth::authorize_signed_out,
+        oauth::authorize_post,
+        relationships::relationship_action,
+        settings::security,
+        settings::change_password,
+        settings::revoke_session,
+        settings::revoke_all_sessions,
+        settings::two_factor,
+        settings::two_factor_setup,
+        settings::two_factor_enable,
+        settings::two_factor_disable,
+        settings::two_factor_recovery_codes,
+        static_files,
+        media_files
     ]
 }
 
+pub fn catchers() -> Vec<Catcher> {
+    catchers![forbidden]
+}
+
+/// Explains why a request was refused; most often, a form was posted with a stale token.
+#[catch(403)]
+pub fn forbidden(request: &Request) -> ErrorTemplate<'static, 'static, 'static> {
+    let unconfirmed = match request.guard::<User>() {
+        Outcome::Success(user) => !user.is_confirmed(),
+        _ => false,
+    };
+
+    let message = if csrf::csrf_failed(request) {
+        "This form has expired, or was sent from another site. \
+         Go back, reload the page and try again."
+    } else if unconfirmed {
+        "You need to confirm your email address first; check your email for the link."
+    } else {
+        "You aren't allowed to do that."
+    };
+
+    ErrorTemplate {
+        title: "Forbidden",
+        message,
+        revision: crate::GIT_REV,
+        flash: None,
+    }
+}
+
 #[derive(Debug)]
 pub enum Timeline {
     Local,
@@ -59,32 +131,38 @@ impl<'v> FromFormValue<'v> for Timeline {
     }
 }
 
+/// Describes what's wrong with a form, with commas between the descriptions of each error.
+fn validation_error_description(errs: &ValidationErrors) -> String {
+    errs.field_errors()
+        .iter()
+        .flat_map(|(_, &errs)| errs)
+        .map(|e| {
+            let msg = e.message.to_owned();
+            msg.unwrap_or(Cow::Borrowed("unknown error"))
+        })
+        .join(", ")
+}
+
 #[derive(Debug, FromForm, Validate)]
+#[validate(schema(function = "validate_create_status_form"))]
 pub struct CreateStatusForm {
     #[validate(length(min = 1, message = "Content must not be empty"))]
     content: String,
     content_warning: String,
 }
 
+fn validate_create_status_form(form: &CreateStatusForm) -> Result<(), ValidationError> {
+    validators::validate_status_length(&form.content, &form.content_warning)
+}
+
 #[post("/statuses/create", data = "<form>")]
 pub fn create_status(
-    user: User,
+    user: Confirmed,
     db_conn: db::Connection,
-    form: Form<CreateStatusForm>,
+    form: CsrfForm<CreateStatusForm>,
 ) -> Result<Either<Flash<Redirect>, Redirect>, Error> {
     if let Err(errs) = form.validate() {
-        let errs = errs.field_errors();
-
-        // concatenate the error descriptions, with commas between them.
-        // TODO: make this less ugly :(
-        let error_desc = errs
-            .iter()
-            .flat_map(|(_, &errs)| errs)
-            .map(|e| {
-                let msg = e.message.to_owned();
-                msg.unwrap_or(Cow::Borrowed("unknown error"))
-            })
-            .join(", ");
+        let error_desc = validation_error_description(&errs);
 
         return Ok(Either::Left(Flash::error(Redirect::to("/"), error_desc)));
     }
@@ -96,24 +174,19 @@ pub fn create_status(
         None
     };
 
-    let _status = NewStatus {
-        id: id_generator().next(),
-        created_at: Utc::now(),
-        text: form.content.to_owned(),
-        content_warning,
-        account_id: user.account_id,
-    }
-    .insert(&db_conn)?;
+    let account = user.0.get_account(&db_conn)?;
+    Status::create_local(&db_conn, &account, form.content.to_owned(), content_warning)?;
 
     Ok(Either::Right(Redirect::to("/")))
 }
 
-#[post("/users/<username>/statuses/<status_id>/delete")]
+#[post("/users/<username>/statuses/<status_id>/delete", data = "<_form>")]
 pub fn delete_status(
     username: String,
     status_id: StatusID,
     user: User,
     db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
 ) -> Perhaps<Flash<Redirect>> {
     let status_user = try_resopt!(User::by_username(&db_conn, username));
 
@@ -130,8 +203,7 @@ pub fn delete_status(
         status_id.0 as i64
     ));
 
-    use diesel::prelude::*;
-    diesel::delete(&status).execute(&*db_conn)?;
+    status.delete(&db_conn)?;
 
     Ok(Some(Flash::success(Redirect::to("/"), "deleted status!")))
 }
@@ -141,6 +213,7 @@ pub fn status_page<'b, 'c>(
     username: String,
     status_id: StatusID,
     user: Option<User>,
+    csrf: CsrfToken,
     db_conn: db::Connection,
 ) -> Perhaps<StatusTemplate<'static, 'b, 'c>> {
     let account = try_resopt!(Account::fetch_local_by_username(&db_conn, username));
@@ -150,7 +223,7 @@ pub fn status_page<'b, 'c>(
         status_id.0 as i64
     ));
 
-    PerhapsHtmlTemplate!(StatusTemplate, {
+    PerhapsHtmlTemplate!(StatusTemplate, csrf, {
         status:  status,
         account: account,
         current_user: user,
@@ -170,12 +243,20 @@ pub fn status_page_simple<'b, 'c>(
 
 #[get("/users/<username>?<max_id>", format = "text/html")]
 pub fn user_page<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
     username: String,
     max_id: Option<i64>,
     db_conn: db::Connection,
     account: Option<Account>,
+    csrf: CsrfToken,
 ) -> Perhaps<UserTemplate<'static, 'b, 'c>> {
     let account_to_show = try_resopt!(Account::fetch_local_by_username(&db_conn, username));
+    let relationship = match &account {
+        Some(account) if account.id != account_to_show.id => {
+            Some(Relationship::between(&db_conn, account, &account_to_show)?)
+        },
+        _ => None,
+    };
     let statuses: Vec<Status> = account_to_show.statuses_before_id(&db_conn, max_id, 10)?;
     let prev_page_id = if let Some(prev_page_max_id) = statuses.iter().map(|s| s.id).min() {
         let bounds = account_to_show.status_id_bounds(&db_conn)?;
@@ -188,9 +269,10 @@ pub fn user_page<'b, 'c>(
     } else {
         None
     };
-    PerhapsHtmlTemplate!(UserTemplate, {
+    PerhapsHtmlTemplate!(UserTemplate, csrf, flash, {
         account_to_show: account_to_show,
         account: account,
+        relationship: relationship,
         statuses: statuses,
         prev_page_id: prev_page_id,
         connection: db_conn
@@ -215,8 +297,9 @@ pub fn user_page_simple<'b, 'c>(
 pub fn settings_profile<'b, 'c>(
     db_conn: db::Connection,
     user: User,
+    csrf: CsrfToken,
 ) -> Perhaps<EditProfileTemplate<'static, 'b, 'c>> {
-    PerhapsHtmlTemplate!(EditProfileTemplate, {
+    PerhapsHtmlTemplate!(EditProfileTemplate, csrf, {
         account: user.get_account(&db_conn)?
     })
 }
@@ -224,13 +307,14 @@ pub fn settings_profile<'b, 'c>(
 #[derive(Debug, FromForm)]
 pub struct UpdateProfileForm {
     summary: String,
+    locked:  bool,
 }
 
 #[post("/settings/profile", data = "<form>")]
 pub fn settings_profile_update(
     db_conn: db::Connection,
     user: User,
-    form: Form<UpdateProfileForm>,
+    form: CsrfForm<UpdateProfileForm>,
 ) -> Result<Redirect, Error> {
     let account = user.get_account(&db_conn)?;
 
@@ -239,6 +323,7 @@ pub fn settings_profile_update(
         x => Some(x.to_string()),
     };
     account.set_summary(&db_conn, new_summary)?;
+    account.set_locked(&db_conn, form.locked)?;
 
     Ok(Redirect::to(account.profile_path().to_string()))
 }
@@ -249,6 +334,7 @@ pub fn index<'b, 'c>(
     account: Option<Account>,
     max_id: Option<i64>,
     timeline: Option<Timeline>,
+    csrf: CsrfToken,
     db_conn: db::Connection,
 ) -> Result<IndexTemplate<'static, 'b, 'c>, Error> {
     let statuses: Vec<Status> = match timeline {
@@ -277,12 +363,13 @@ pub fn index<'b, 'c>(
         Some(Timeline::Federated) => "federated",
     };
 
-    Ok(HtmlTemplate!(IndexTemplate, flash, {
+    Ok(HtmlTemplate!(IndexTemplate, csrf, flash, {
         account: account,
         statuses: statuses,
         timeline: timeline_str,
         prev_page_id: prev_page_id,
-        connection: db_conn
+        connection: db_conn,
+        allows_signups: REGISTRATION_MODE.allows_signups()
     }))
 }
 
@@ -290,3 +377,8 @@ pub fn index<'b, 'c>(
 fn static_files(path: PathBuf) -> Option<NamedFile> {
     NamedFile::open(Path::new("static/").join(path)).ok()
 }
+
+#[get("/media/<path..>")]
+fn media_files(path: PathBuf) -> Option<NamedFile> {
+    NamedFile::open(MEDIA_DIR.join(path)).ok()
+}
@@ -0,0 +1,44 @@
+use crate::db;
+use crate::db::models::{Notification, NotificationKind, User};
+use crate::routes::ui::csrf::CsrfToken;
+use crate::routes::ui::templates::NotificationsTemplate;
+use failure::Error;
+use rocket::request::FlashMessage;
+
+/// Number of notifications shown per page.
+const PAGE_SIZE: usize = 20;
+
+/// Lists the current user's notifications (optionally only those of one `kind`),
+/// marking the shown ones as read.
+#[get("/notifications?<max_id>&<kind>")]
+pub fn notifications<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    user: User,
+    max_id: Option<i64>,
+    kind: Option<NotificationKind>,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<NotificationsTemplate<'static, 'b, 'c>, Error> {
+    let account = user.get_account(&db_conn)?;
+    let notifications = Notification::before_id(&db_conn, account.id, max_id, kind, PAGE_SIZE)?;
+    let prev_page_id = if notifications.len() == PAGE_SIZE {
+        notifications.iter().map(|n| n.id).min()
+    } else {
+        None
+    };
+
+    let unread: Vec<i64> = notifications
+        .iter()
+        .filter(|n| !n.read)
+        .map(|n| n.id)
+        .collect();
+    Notification::mark_read(&db_conn, account.id, &unread)?;
+
+    Ok(HtmlTemplate!(NotificationsTemplate, csrf, flash, {
+        account: account,
+        notifications: notifications,
+        kind: kind.map(NotificationKind::as_str).unwrap_or(""),
+        prev_page_id: prev_page_id,
+        connection: db_conn
+    }))
+}
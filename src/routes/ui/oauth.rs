@@ -0,0 +1,208 @@
+//! The consent page, where users let third-party clients act for them.
+//!
+//! Clients send users to `/oauth/authorize`; if they approve, they go back to the client with
+//! an authorization code, which it exchanges for a token at `/oauth/token` (see `api::oauth`).
+
+use failure::Error;
+use rocket::http::uri::{Origin, Uri};
+use rocket::http::{Cookies, Status};
+use rocket::request::{FlashMessage, LenientForm};
+use rocket::response::{status, Flash, Redirect};
+
+use crate::db;
+use crate::db::models::{Application, AuthorizationCode, Role, User, OUT_OF_BAND_URI};
+use crate::oauth::{Scope, Scopes};
+use crate::routes::ui::auth;
+use crate::routes::ui::csrf::{CsrfForm, CsrfToken};
+use crate::routes::ui::templates::{ErrorTemplate, OAuthAuthorizeTemplate, OAuthCodeTemplate};
+use crate::util::Either;
+
+/// The parameters of an authorization request, passed along by the consent form.
+#[derive(Debug, FromForm)]
+pub struct AuthorizeParams {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    scope: Option<String>,
+    state: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+    /// Set by the consent form's "Authorize" button, and left out by its "Deny" one.
+    approve: Option<String>,
+}
+
+/// Returns `value`, unless it's missing or blank (as it will be, coming back from the form).
+fn present(value: &Option<String>) -> Option<&str> {
+    value.as_ref().map(String::as_str).filter(|v| !v.is_empty())
+}
+
+/// Checks an authorization request by `user`, returning the application and the scopes it
+/// wants, or a message saying what's wrong.
+fn check_request(
+    db_conn: &db::Connection,
+    user: &User,
+    params: &AuthorizeParams,
+) -> Result<Result<(Application, Scopes), &'static str>, Error> {
+    let app = match Application::by_client_id(db_conn, &params.client_id)? {
+        Some(app) => app,
+        None => return Ok(Err("That application isn't registered here.")),
+    };
+
+    // until we know the redirect URI is the app's, we mustn't send the user there.
+    if !app.allows_redirect_uri(&params.redirect_uri) {
+        return Ok(Err("That application didn't register this redirect URI."));
+    }
+    if params.response_type != "code" {
+        return Ok(Err("Only the authorization code flow is supported."));
+    }
+
+    let scopes = present(&params.scope).map_or_else(Scopes::default_scopes, Scopes::from);
+    if !scopes.is_subset_of(&app.scopes()) {
+        return Ok(Err(
+            "That application asked for more than it registered for.",
+        ));
+    }
+    if scopes.contains(Scope::Admin) && user.role < Role::Moderator {
+        return Ok(Err(
+            "That application asked to moderate this instance, which you can't do.",
+        ));
+    }
+
+    match (
+        present(&params.code_challenge),
+        present(&params.code_challenge_method),
+    ) {
+        (None, None) | (Some(_), None) | (Some(_), Some("S256")) | (Some(_), Some("plain")) => {},
+        _ => return Ok(Err("That application sent an invalid PKCE challenge.")),
+    }
+
+    Ok(Ok((app, scopes)))
+}
+
+fn bad_request(message: &'static str) -> status::Custom<ErrorTemplate<'static, 'static, 'static>> {
+    status::Custom(
+        Status::BadRequest,
+        ErrorTemplate {
+            title: "Can't authorize this application",
+            message,
+            revision: crate::GIT_REV,
+            flash: None,
+        },
+    )
+}
+
+/// Sends the user back to the client at `redirect_uri`, with `query` (and the client's `state`).
+fn back_to_client(redirect_uri: &str, query: &str, state: Option<&str>) -> Redirect {
+    let separator = if redirect_uri.contains('?') { '&' } else { '?' };
+    let mut uri = format!("{}{}{}", redirect_uri, separator, query);
+    if let Some(state) = state {
+        uri.push_str("&state=");
+        uri.push_str(&Uri::percent_encode(state));
+    }
+
+    Redirect::to(uri)
+}
+
+#[get("/oauth/authorize?<params..>")]
+pub fn authorize_get<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    params: LenientForm<AuthorizeParams>,
+    user: User,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<
+    Either<
+        OAuthAuthorizeTemplate<'static, 'b, 'c>,
+        status::Custom<ErrorTemplate<'static, 'static, 'static>>,
+    >,
+    Error,
+> {
+    let (app, scopes) = match check_request(&db_conn, &user, &params)? {
+        Ok(checked) => checked,
+        Err(message) => return Ok(Either::Right(bad_request(message))),
+    };
+
+    let or_blank = |value: &Option<String>| present(value).unwrap_or("").to_string();
+    Ok(Either::Left(
+        HtmlTemplate!(OAuthAuthorizeTemplate, csrf, flash, {
+            account: user.get_account(&db_conn)?,
+            app_name: app.name,
+            app_website: app.website,
+            scope_descriptions: scopes.descriptions(),
+            client_id: params.client_id.clone(),
+            redirect_uri: params.redirect_uri.clone(),
+            scope: scopes.to_string(),
+            state: or_blank(&params.state),
+            code_challenge: or_blank(&params.code_challenge),
+            code_challenge_method: or_blank(&params.code_challenge_method)
+        }),
+    ))
+}
+
+/// Sends people who aren't signed in to do so first, bringing them back here afterwards.
+#[get("/oauth/authorize", rank = 1)]
+pub fn authorize_signed_out(uri: &Origin, mut cookies: Cookies) -> Flash<Redirect> {
+    auth::sign_in_and_return(
+        &mut cookies,
+        uri.to_string(),
+        "sign in first, to authorize the application.",
+    )
+}
+
+#[post("/oauth/authorize", data = "<form>")]
+pub fn authorize_post(
+    form: CsrfForm<AuthorizeParams>,
+    user: User,
+    db_conn: db::Connection,
+) -> Result<
+    Either<
+        Redirect,
+        Either<
+            OAuthCodeTemplate<'static, 'static, 'static>,
+            status::Custom<ErrorTemplate<'static, 'static, 'static>>,
+        >,
+    >,
+    Error,
+> {
+    let (app, scopes) = match check_request(&db_conn, &user, &form)? {
+        Ok(checked) => checked,
+        Err(message) => return Ok(Either::Right(Either::Right(bad_request(message)))),
+    };
+    let state = present(&form.state);
+
+    if form.approve.is_none() {
+        return Ok(Either::Left(back_to_client(
+            &form.redirect_uri,
+            "error=access_denied",
+            state,
+        )));
+    }
+
+    let code_challenge = present(&form.code_challenge).map(|challenge| {
+        let method = present(&form.code_challenge_method).unwrap_or("plain");
+        (challenge.to_string(), method.to_string())
+    });
+    let (_, code) = AuthorizationCode::create(
+        &db_conn,
+        &app,
+        user.id,
+        &form.redirect_uri,
+        &scopes,
+        code_challenge,
+    )?;
+
+    if form.redirect_uri == OUT_OF_BAND_URI {
+        return Ok(Either::Right(Either::Left(OAuthCodeTemplate {
+            app_name: app.name,
+            code,
+            revision: crate::GIT_REV,
+            flash: None,
+        })));
+    }
+
+    Ok(Either::Left(back_to_client(
+        &form.redirect_uri,
+        &format!("code={}", code),
+        state,
+    )))
+}
@@ -0,0 +1,158 @@
+use crate::db;
+use crate::db::models::{
+    Account, Block, Follow, FollowRequest, Mute, NewBlock, NewFollow, NewFollowRequest, NewMute,
+    Notification, NotificationKind, User,
+};
+use crate::db::{id_generator, DbConnection};
+use crate::error::Perhaps;
+use crate::routes::ui::csrf::{CsrfForm, Empty};
+use diesel::Connection;
+use failure::Error;
+use resopt::try_resopt;
+use rocket::http::RawStr;
+use rocket::request::FromParam;
+use rocket::response::{Flash, Redirect};
+
+/// Something the current user can do to their relationship with another account.
+#[derive(Debug)]
+pub enum RelationshipAction {
+    Follow,
+    Unfollow,
+    Block,
+    Unblock,
+    Mute,
+    Unmute,
+}
+
+impl<'r> FromParam<'r> for RelationshipAction {
+    type Error = &'r RawStr;
+
+    fn from_param(param: &'r RawStr) -> Result<Self, Self::Error> {
+        match param.as_str() {
+            "follow" => Ok(RelationshipAction::Follow),
+            "unfollow" => Ok(RelationshipAction::Unfollow),
+            "block" => Ok(RelationshipAction::Block),
+            "unblock" => Ok(RelationshipAction::Unblock),
+            "mute" => Ok(RelationshipAction::Mute),
+            "unmute" => Ok(RelationshipAction::Unmute),
+            _ => Err(param),
+        }
+    }
+}
+
+#[post("/users/<username>/<action>", data = "<_form>", rank = 2)]
+pub fn relationship_action(
+    username: String,
+    action: RelationshipAction,
+    user: User,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Perhaps<Flash<Redirect>> {
+    let account = user.get_account(&db_conn)?;
+    let target = try_resopt!(Account::fetch_local_by_username(&db_conn, username));
+    let redirect = Redirect::to(target.profile_path().to_string());
+
+    if account.id == target.id {
+        return Ok(Some(Flash::error(redirect, "that's you!")));
+    }
+
+    let message = match action {
+        RelationshipAction::Follow => follow(&db_conn, &account, &target)?,
+        RelationshipAction::Unfollow => {
+            Follow::delete_between(&db_conn, account.id, target.id)?;
+            FollowRequest::delete_between(&db_conn, account.id, target.id)?;
+            "unfollowed"
+        },
+        RelationshipAction::Block => {
+            block(&db_conn, &account, &target)?;
+            "blocked"
+        },
+        RelationshipAction::Unblock => {
+            Block::delete_between(&db_conn, account.id, target.id)?;
+            "unblocked"
+        },
+        RelationshipAction::Mute => {
+            NewMute {
+                id: id_generator().next(),
+                account_id: account.id,
+                target_id: target.id,
+            }
+            .insert(&db_conn)?;
+            "muted"
+        },
+        RelationshipAction::Unmute => {
+            Mute::delete_between(&db_conn, account.id, target.id)?;
+            "unmuted"
+        },
+    };
+
+    Ok(Some(Flash::success(
+        redirect,
+        format!("{} {}", message, target.fully_qualified_username()),
+    )))
+}
+
+/// Follows `target`, or asks to if it's locked.
+fn follow(
+    db_conn: &DbConnection,
+    account: &Account,
+    target: &Account,
+) -> Result<&'static str, Error> {
+    if Block::exists_between(db_conn, account.id, target.id)? {
+        return Ok("couldn't follow");
+    }
+
+    if Follow::exists(db_conn, account.id, target.id)?
+        || FollowRequest::between(db_conn, account.id, target.id)?.is_some()
+    {
+        return Ok("already following");
+    }
+
+    if target.locked {
+        NewFollowRequest {
+            id: id_generator().next(),
+            source_id: account.id,
+            target_id: target.id,
+            uri: None,
+        }
+        .insert(db_conn)?;
+        Notification::notify(
+            db_conn,
+            target,
+            account,
+            NotificationKind::FollowRequest,
+            None,
+        )?;
+
+        Ok("asked to follow")
+    } else {
+        NewFollow {
+            id: id_generator().next(),
+            source_id: account.id,
+            target_id: target.id,
+        }
+        .insert(db_conn)?;
+        Notification::notify(db_conn, target, account, NotificationKind::Follow, None)?;
+
+        Ok("followed")
+    }
+}
+
+/// Blocks `target`, severing any follows (or requests to follow) between the two accounts.
+fn block(db_conn: &DbConnection, account: &Account, target: &Account) -> Result<(), Error> {
+    db_conn.transaction::<_, Error, _>(|| {
+        NewBlock {
+            id: id_generator().next(),
+            account_id: account.id,
+            target_id: target.id,
+        }
+        .insert(db_conn)?;
+
+        Follow::delete_between(db_conn, account.id, target.id)?;
+        Follow::delete_between(db_conn, target.id, account.id)?;
+        FollowRequest::delete_between(db_conn, account.id, target.id)?;
+        FollowRequest::delete_between(db_conn, target.id, account.id)?;
+
+        Ok(())
+    })
+}
@@ -0,0 +1,260 @@
+use crate::db;
+use crate::db::models::{RecoveryCode, Session, User, SESSION_COOKIE};
+use crate::db::validators;
+use crate::error::Perhaps;
+use crate::routes::ui::csrf::{CsrfForm, CsrfToken, Empty};
+use crate::routes::ui::templates::{SecuritySettingsTemplate, TwoFactorSettingsTemplate};
+use crate::routes::ui::validation_error_description;
+use crate::util::Either;
+use crate::{totp, DOMAIN};
+use failure::Error;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use resopt::try_resopt;
+use rocket::http::{Cookie, Cookies};
+use rocket::request::FlashMessage;
+use rocket::response::{Flash, Redirect};
+use validator::Validate;
+
+#[get("/settings/security")]
+pub fn security<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    user: User,
+    session: Session,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<SecuritySettingsTemplate<'static, 'b, 'c>, Error> {
+    let sessions = Session::for_user(&db_conn, user.id)?;
+    let two_factor_enabled = user.otp_enabled;
+
+    Ok(HtmlTemplate!(SecuritySettingsTemplate, csrf, flash, {
+        account: user.get_account(&db_conn)?,
+        sessions: sessions,
+        current_session_id: session.id,
+        two_factor_enabled: two_factor_enabled
+    }))
+}
+
+#[derive(Debug, FromForm, Validate)]
+pub struct ChangePasswordForm {
+    current_password: String,
+    #[validate(custom = "validators::validate_password")]
+    new_password: String,
+    new_password_confirmation: String,
+}
+
+/// Changes the current user's password, signing out all of their other sessions.
+#[post("/settings/security/password", data = "<form>")]
+pub fn change_password(
+    user: User,
+    session: Session,
+    form: CsrfForm<ChangePasswordForm>,
+    db_conn: db::Connection,
+) -> Result<Flash<Redirect>, Error> {
+    let redirect = Redirect::to("/settings/security");
+
+    if !user.valid_password(&form.current_password) {
+        return Ok(Flash::error(redirect, "wrong password"));
+    }
+
+    if form.new_password != form.new_password_confirmation {
+        return Ok(Flash::error(redirect, "the new passwords don't match"));
+    }
+
+    if let Err(errs) = form.validate() {
+        let error_desc = validation_error_description(&errs);
+
+        return Ok(Flash::error(redirect, error_desc));
+    }
+
+    user.set_password(&db_conn, &form.new_password, Some(&session))?;
+
+    Ok(Flash::success(
+        redirect,
+        "password changed; your other sessions were signed out",
+    ))
+}
+
+#[post("/settings/security/sessions/<id>/revoke", data = "<_form>")]
+pub fn revoke_session(
+    id: i64,
+    user: User,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Perhaps<Flash<Redirect>> {
+    let session = try_resopt!(Session::by_user_and_id(&db_conn, user.id, id));
+    session.delete(&db_conn)?;
+
+    Ok(Some(Flash::success(
+        Redirect::to("/settings/security"),
+        "signed out session",
+    )))
+}
+
+/// Ends every session of the current user, including this one.
+#[post("/settings/security/sessions/revoke_all", data = "<_form>")]
+pub fn revoke_all_sessions(
+    user: User,
+    mut cookies: Cookies,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Result<Flash<Redirect>, Error> {
+    Session::delete_all_for_user(&db_conn, user.id, None)?;
+    cookies.remove_private(Cookie::named(SESSION_COOKIE));
+    CsrfToken::rotate(&mut cookies);
+
+    Ok(Flash::success(
+        Redirect::to("/auth/sign_in"),
+        "signed out everywhere",
+    ))
+}
+
+/// Builds the two-factor settings page; `recovery_codes` are only given right after
+/// they've been generated, as that's the only time they can be shown.
+fn two_factor_page<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    csrf: CsrfToken,
+    user: User,
+    recovery_codes: Vec<String>,
+    db_conn: &db::Connection,
+) -> Result<TwoFactorSettingsTemplate<'static, 'b, 'c>, Error> {
+    let enabled = user.otp_enabled;
+    let recovery_codes_left = RecoveryCode::count_for_user(db_conn, user.id)?;
+    // a secret that isn't enabled yet is in the middle of being set up; show how to add it.
+    let otp_secret = user.otp_secret.clone().filter(|_| !enabled);
+    let account = user.get_account(db_conn)?;
+
+    let (otp_uri, qr_svg) = match &otp_secret {
+        Some(secret) => {
+            let uri = totp::provisioning_uri(secret, &account.fully_qualified_username(), &DOMAIN);
+            let qr_svg = QrCode::new(uri.as_bytes())?
+                .render::<svg::Color>()
+                .min_dimensions(200, 200)
+                .build();
+            (Some(uri), Some(qr_svg))
+        },
+        None => (None, None),
+    };
+
+    Ok(HtmlTemplate!(TwoFactorSettingsTemplate, csrf, flash, {
+        account: account,
+        enabled: enabled,
+        otp_secret: otp_secret,
+        otp_uri: otp_uri,
+        qr_svg: qr_svg,
+        recovery_codes: recovery_codes,
+        recovery_codes_left: recovery_codes_left
+    }))
+}
+
+#[get("/settings/two_factor")]
+pub fn two_factor<'b, 'c>(
+    flash: Option<FlashMessage<'b, 'c>>,
+    user: User,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<TwoFactorSettingsTemplate<'static, 'b, 'c>, Error> {
+    two_factor_page(flash, csrf, user, Vec::new(), &db_conn)
+}
+
+/// Starts setting up two-factor authentication, by making the current user a new secret.
+#[post("/settings/two_factor/setup", data = "<_form>")]
+pub fn two_factor_setup(
+    user: User,
+    db_conn: db::Connection,
+    _form: CsrfForm<Empty>,
+) -> Result<Flash<Redirect>, Error> {
+    let redirect = Redirect::to("/settings/two_factor");
+
+    if user.otp_enabled {
+        return Ok(Flash::error(
+            redirect,
+            "two-factor authentication is already on",
+        ));
+    }
+
+    user.set_otp_secret(&db_conn, &totp::generate_secret()?)?;
+
+    Ok(Flash::success(
+        redirect,
+        "scan the code with your authenticator app, then enter the code it shows",
+    ))
+}
+
+#[derive(Debug, FromForm)]
+pub struct EnableTwoFactorForm {
+    code: String,
+}
+
+/// Finishes setting up two-factor authentication, once the user has shown their
+/// authenticator app works, and hands them their recovery codes.
+#[post("/settings/two_factor/enable", data = "<form>")]
+pub fn two_factor_enable(
+    user: User,
+    form: CsrfForm<EnableTwoFactorForm>,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<Either<TwoFactorSettingsTemplate<'static, 'static, 'static>, Flash<Redirect>>, Error> {
+    if user.otp_enabled || !user.redeem_otp_code(&db_conn, &form.code)? {
+        return Ok(Either::Right(Flash::error(
+            Redirect::to("/settings/two_factor"),
+            "that code didn't work; check your device's clock, and try again",
+        )));
+    }
+
+    user.enable_two_factor(&db_conn)?;
+    let codes = RecoveryCode::regenerate(&db_conn, user.id)?;
+    // the user was loaded before 2FA was turned on.
+    let user = User {
+        otp_enabled: true,
+        ..user
+    };
+
+    Ok(Either::Left(two_factor_page(
+        None, csrf, user, codes, &db_conn,
+    )?))
+}
+
+#[derive(Debug, FromForm)]
+pub struct ConfirmPasswordForm {
+    password: String,
+}
+
+#[post("/settings/two_factor/disable", data = "<form>")]
+pub fn two_factor_disable(
+    user: User,
+    form: CsrfForm<ConfirmPasswordForm>,
+    db_conn: db::Connection,
+) -> Result<Flash<Redirect>, Error> {
+    let redirect = Redirect::to("/settings/two_factor");
+
+    if !user.valid_password(&form.password) {
+        return Ok(Flash::error(redirect, "wrong password"));
+    }
+
+    user.disable_two_factor(&db_conn)?;
+
+    Ok(Flash::success(redirect, "two-factor authentication is off"))
+}
+
+/// Replaces the current user's recovery codes with new ones, and shows them.
+#[post("/settings/two_factor/recovery_codes", data = "<form>")]
+pub fn two_factor_recovery_codes(
+    user: User,
+    form: CsrfForm<ConfirmPasswordForm>,
+    csrf: CsrfToken,
+    db_conn: db::Connection,
+) -> Result<Either<TwoFactorSettingsTemplate<'static, 'static, 'static>, Flash<Redirect>>, Error> {
+    if !user.otp_enabled || !user.valid_password(&form.password) {
+        return Ok(Either::Right(Flash::error(
+            Redirect::to("/settings/two_factor"),
+            "wrong password",
+        )));
+    }
+
+    let codes = RecoveryCode::regenerate(&db_conn, user.id)?;
+
+    Ok(Either::Left(two_factor_page(
+        None, csrf, user, codes, &db_conn,
+    )?))
+}
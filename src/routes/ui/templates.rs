@@ -1,35 +1,39 @@
 use crate::db;
-use crate::db::models::{Account, Status, User};
+use crate::db::models::{Account, Invite, Notification, Relationship, Session, Status, User};
 use crate::routes::ui::view_helpers::*;
 use askama::Template;
 use rocket::request::FlashMessage;
 
 macro_rules! HtmlTemplate {
-    ($x:tt) => {{
+    ($x:tt, $csrf: ident) => {{
         $x {
             flash: None,
+            csrf_token: $csrf.0,
             revision: $crate::GIT_REV,
         }
     }};
 
-    ($x:tt, $flash: ident) => {{
+    ($x:tt, $csrf: ident, $flash: ident) => {{
         $x {
             flash: $flash,
+            csrf_token: $csrf.0,
             revision: $crate::GIT_REV,
         }
     }};
 
-    ($x:tt, { $( $y:ident : $z:expr ),* }) => {{
+    ($x:tt, $csrf: ident, { $( $y:ident : $z:expr ),* }) => {{
         $x {
             $( $y: $z ),*
             ,flash: None
+            ,csrf_token: $csrf.0
             ,revision: $crate::GIT_REV,
         }
     }};
-    ($x:tt, $flash: ident, { $( $y:ident : $z:expr ),* }) => {{
+    ($x:tt, $csrf: ident, $flash: ident, { $( $y:ident : $z:expr ),* }) => {{
         $x {
             $( $y: $z ),*
             ,flash: $flash
+            ,csrf_token: $csrf.0
             ,revision: $crate::GIT_REV,
         }
     }};
@@ -47,6 +51,7 @@ pub struct StatusTemplate<'a, 'b, 'c> {
     pub status: Status,
     pub account: Account,
     pub current_user: Option<User>,
+    pub csrf_token: String,
     pub revision: &'a str,
     pub flash: Option<FlashMessage<'b, 'c>>,
     pub connection: db::Connection,
@@ -57,9 +62,11 @@ pub struct StatusTemplate<'a, 'b, 'c> {
 pub struct UserTemplate<'a, 'b, 'c> {
     pub account_to_show: Account,
     pub account: Option<Account>,
+    pub relationship: Option<Relationship>,
     pub statuses: Vec<Status>,
     pub prev_page_id: Option<i64>,
     pub connection: db::Connection,
+    pub csrf_token: String,
     pub revision: &'a str,
     pub flash: Option<FlashMessage<'b, 'c>>,
 }
@@ -67,23 +74,49 @@ pub struct UserTemplate<'a, 'b, 'c> {
 #[derive(Template)]
 #[template(path = "edit_profile.html")]
 pub struct EditProfileTemplate<'a, 'b, 'c> {
-    pub account:  Account,
+    pub account: Account,
+    pub csrf_token: String,
     pub revision: &'a str,
-    pub flash:    Option<FlashMessage<'b, 'c>>,
+    pub flash: Option<FlashMessage<'b, 'c>>,
 }
 
 #[derive(Template)]
 #[template(path = "signin.html")]
 pub struct SigninTemplate<'a, 'b, 'c> {
+    pub csrf_token: String,
     pub revision: &'a str,
-    pub flash:    Option<FlashMessage<'b, 'c>>,
+    pub flash: Option<FlashMessage<'b, 'c>>,
 }
 
 #[derive(Template)]
 #[template(path = "signup.html")]
 pub struct SignupTemplate<'a, 'b, 'c> {
+    pub requires_invite: bool,
+    pub requires_approval: bool,
+    pub invite: String,
+    pub csrf_token: String,
     pub revision: &'a str,
-    pub flash:    Option<FlashMessage<'b, 'c>>,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "email_form.html")]
+pub struct EmailFormTemplate<'a, 'b, 'c> {
+    pub title: &'a str,
+    pub action: &'a str,
+    pub button: &'a str,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "reset_password.html")]
+pub struct ResetPasswordTemplate<'a, 'b, 'c> {
+    pub token: String,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
 }
 
 #[derive(Template)]
@@ -94,6 +127,140 @@ pub struct IndexTemplate<'a, 'b, 'c> {
     pub timeline: &'a str,
     pub prev_page_id: Option<i64>,
     pub connection: db::Connection,
+    pub allows_signups: bool,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_accounts.html")]
+pub struct AdminAccountsTemplate<'a, 'b, 'c> {
+    pub account: Account,
+    pub accounts: Vec<Account>,
+    pub prev_page_id: Option<i64>,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+/// A user waiting for approval, with their account, as listed in the review queue.
+pub struct PendingUser {
+    pub user:    User,
+    pub account: Account,
+}
+
+#[derive(Template)]
+#[template(path = "admin_registrations.html")]
+pub struct AdminRegistrationsTemplate<'a, 'b, 'c> {
+    pub account: Account,
+    pub pending: Vec<PendingUser>,
+    pub mode: &'a str,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_invites.html")]
+pub struct AdminInvitesTemplate<'a, 'b, 'c> {
+    pub account: Account,
+    pub invites: Vec<Invite>,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "oauth_authorize.html")]
+pub struct OAuthAuthorizeTemplate<'a, 'b, 'c> {
+    pub account: Account,
+    pub app_name: String,
+    pub app_website: Option<String>,
+    pub scope_descriptions: Vec<&'static str>,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: String,
+    pub code_challenge: String,
+    pub code_challenge_method: String,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "oauth_code.html")]
+pub struct OAuthCodeTemplate<'a, 'b, 'c> {
+    pub app_name: String,
+    pub code: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "follow_requests.html")]
+pub struct FollowRequestsTemplate<'a, 'b, 'c> {
+    pub account: Account,
+    pub requesters: Vec<Account>,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "notifications.html")]
+pub struct NotificationsTemplate<'a, 'b, 'c> {
+    pub account: Account,
+    pub notifications: Vec<Notification>,
+    pub kind: &'a str,
+    pub prev_page_id: Option<i64>,
+    pub connection: db::Connection,
+    pub csrf_token: String,
     pub revision: &'a str,
     pub flash: Option<FlashMessage<'b, 'c>>,
 }
+
+#[derive(Template)]
+#[template(path = "settings_security.html")]
+pub struct SecuritySettingsTemplate<'a, 'b, 'c> {
+    pub account: Account,
+    pub sessions: Vec<Session>,
+    pub current_session_id: i64,
+    pub two_factor_enabled: bool,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "two_factor.html")]
+pub struct TwoFactorTemplate<'a, 'b, 'c> {
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "settings_two_factor.html")]
+pub struct TwoFactorSettingsTemplate<'a, 'b, 'c> {
+    pub account: Account,
+    pub enabled: bool,
+    pub otp_secret: Option<String>,
+    pub otp_uri: Option<String>,
+    pub qr_svg: Option<String>,
+    pub recovery_codes: Vec<String>,
+    pub recovery_codes_left: i64,
+    pub csrf_token: String,
+    pub revision: &'a str,
+    pub flash: Option<FlashMessage<'b, 'c>>,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+pub struct ErrorTemplate<'a, 'b, 'c> {
+    pub title:    &'a str,
+    pub message:  &'a str,
+    pub revision: &'a str,
+    pub flash:    Option<FlashMessage<'b, 'c>>,
+}
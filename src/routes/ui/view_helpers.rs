@@ -1,21 +1,104 @@
 use crate::db;
-use crate::db::models::Account;
+use crate::db::models::{Account, CustomEmoji, Notification, Status};
 use crate::transform;
 use failure::Error;
+use posticle::render::PlainText;
 
 pub trait HasBio {
     fn transformed_bio(&self, connection: &db::DbConnection) -> Option<String>;
+
+    /// Returns the display name (or else the username) as HTML, with its custom emoji.
+    fn transformed_display_name(&self, connection: &db::DbConnection) -> String;
+
+    /// Returns the bio as plain text, for places HTML can't go.
+    fn plain_text_bio(&self, connection: &db::DbConnection) -> Option<String>;
 }
 
 impl HasBio for Account {
     fn transformed_bio(&self, connection: &db::DbConnection) -> Option<String> {
         if let Some(raw_bio) = self.summary.as_ref().map(String::as_str) {
-            transform::bio(raw_bio, |username, domain| {
-                Account::fetch_by_username_domain(connection, username, domain).map_err(Error::from)
-            })
+            transform::bio(
+                raw_bio,
+                |username, domain| {
+                    Account::fetch_by_username_domain(connection, username, domain)
+                        .map_err(Error::from)
+                },
+                |shortcode| {
+                    CustomEmoji::for_account(connection, shortcode, self).map_err(Error::from)
+                },
+            )
             .ok()
         } else {
             None
         }
     }
+
+    fn transformed_display_name(&self, connection: &db::DbConnection) -> String {
+        let name = self.display_name_or_username();
+
+        transform::display_name(name, |shortcode| {
+            CustomEmoji::for_account(connection, shortcode, self).map_err(Error::from)
+        })
+        .unwrap_or_else(|_| ammonia::clean_text(name))
+    }
+
+    fn plain_text_bio(&self, _connection: &db::DbConnection) -> Option<String> {
+        let raw_bio = self.summary.as_ref()?;
+
+        // mentions and emoji read the same as text whether they're found or not, so don't
+        // look them up.
+        transform::bio_with(raw_bio, |_, _| Ok(None), |_| Ok(None), PlainText).ok()
+    }
+}
+
+pub trait HasRenderedContent {
+    /// Returns the text of this status as HTML, rendering and caching it the first time.
+    fn rendered_content(&self, connection: &db::DbConnection) -> String;
+}
+
+impl HasRenderedContent for Status {
+    fn rendered_content(&self, connection: &db::DbConnection) -> String {
+        if let Some(html) = self.cached_content_html() {
+            return html.to_string();
+        }
+
+        let rendered = self
+            .account(connection)
+            .map_err(Error::from)
+            .and_then(|account| {
+                transform::status(
+                    &self.text,
+                    |username, domain| {
+                        Account::fetch_by_username_domain(connection, username, domain)
+                            .map_err(Error::from)
+                    },
+                    |shortcode| {
+                        CustomEmoji::for_account(connection, shortcode, &account)
+                            .map_err(Error::from)
+                    },
+                )
+            });
+
+        match rendered {
+            Ok(html) => {
+                // if caching fails, we'll just render it again next time.
+                let _ = self.cache_content_html(connection, &html);
+                html
+            },
+            Err(_) => format!("<p>{}</p>", ammonia::clean_text(&self.text)),
+        }
+    }
+}
+
+pub trait HasNotifications {
+    /// Returns the number of unread notifications, if there are any.
+    fn unread_notifications(&self, connection: &db::DbConnection) -> Option<i64>;
+}
+
+impl HasNotifications for Account {
+    fn unread_notifications(&self, connection: &db::DbConnection) -> Option<i64> {
+        Notification::count_unread(connection, self.id)
+            .ok()
+            .filter(|&count| count > 0)
+    }
 }
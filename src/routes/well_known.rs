@@ -10,6 +10,7 @@ use serde_json::json;
 use crate::db;
 use crate::db::models::{Account, Status, User};
 use crate::error::Perhaps;
+use crate::registrations::REGISTRATION_MODE;
 use crate::{BASE_URL, DOMAIN, GIT_REV};
 
 pub fn routes() -> Vec<Route> {
@@ -109,7 +110,7 @@ pub fn nodeinfo(db_conn: db::Connection) -> Result<Content<JsonValue>, Error> {
         },
         "protocols": ["activitypub"],
         "services": {"inbound": [], "outbound": []},
-        "openRegistrations": true, // TODO: hahaha
+        "openRegistrations": REGISTRATION_MODE.is_open(),
         "usage": { // TODO: cache?
             "users": {"total": User::count(&db_conn)?},
             "localPosts": Status::count_local(&db_conn)?,
@@ -0,0 +1,703 @@
+//! The streaming API, which pushes events from the event bus (see `events`) to clients over
+//! Server-Sent Events or a WebSocket, like Mastodon's.
+//!
+//! Rocket can neither hold a response open nor upgrade a connection, so this runs a little
+//! server of its own on another port, in the same process. The reverse proxy in front of
+//! Rustodon should send `/api/v1/streaming` there.
+
+use failure::{format_err, Error};
+use serde_json::json;
+use slog_scope::warn;
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::{Error as WsError, Message as WsMessage};
+use url::form_urlencoded;
+
+use crate::db::models::{AccessToken, Account, User};
+use crate::db::{self, DbConnection};
+use crate::events::{self, Event};
+use crate::oauth::Scope;
+use crate::routes::api::entities;
+use crate::transform;
+
+/// Where the streaming API listens if `STREAMING_BIND` isn't set.
+pub const DEFAULT_BIND: &str = "127.0.0.1:4000";
+
+/// How often to tell clients we're still here when nothing is happening.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The most we read of a request before giving up on it. Clients have nothing to send over
+/// a WebSocket, so its messages are held to this too.
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+
+/// How long a client may take to send its request, or to take something we send it.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a WebSocket waits for frames from its client before looking for events to send.
+const WEBSOCKET_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The most clients connected at once. More are turned away until some leave.
+const MAX_CONNECTIONS: usize = 1024;
+
+/// How many messages may wait for a client before it's disconnected for falling behind.
+const MAX_QUEUED_MESSAGES: usize = 256;
+
+/// What a client can listen to.
+#[derive(Debug)]
+pub enum Stream {
+    /// The home timeline and notifications of an account.
+    User(Account),
+    /// Every status we know of.
+    Public,
+    /// The statuses posted here.
+    PublicLocal,
+    /// The statuses which use a hashtag.
+    Hashtag(String),
+}
+
+/// An event, ready to send to a client.
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    pub event:   &'static str,
+    pub payload: String,
+}
+
+impl Stream {
+    /// Finds the stream called `name` (`tag` is the hashtag, for `hashtag`), as `account`,
+    /// which is who the client acts as, if anyone.
+    pub fn parse(name: &str, tag: Option<&str>, account: Option<Account>) -> Result<Stream, Error> {
+        match (name, tag, account) {
+            ("user", _, Some(account)) => Ok(Stream::User(account)),
+            ("user", _, None) => Err(format_err!("the user stream needs a user's token")),
+            ("public", ..) => Ok(Stream::Public),
+            ("public:local", ..) => Ok(Stream::PublicLocal),
+            ("hashtag", Some(tag), _) if !tag.is_empty() => Ok(Stream::Hashtag(tag.to_string())),
+            ("hashtag", ..) => Err(format_err!("the hashtag stream needs a tag")),
+            _ => Err(format_err!("unknown stream {}", name)),
+        }
+    }
+
+    /// Returns the name of this stream.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stream::User(_) => "user",
+            Stream::Public => "public",
+            Stream::PublicLocal => "public:local",
+            Stream::Hashtag(_) => "hashtag",
+        }
+    }
+
+    /// Returns the message to send for `event` to clients listening to this stream, if any.
+    pub fn message(&self, event: &Prepared) -> Option<Arc<Message>> {
+        let wanted = match (event, self) {
+            (Prepared::Status { home_of, .. }, Stream::User(account)) => {
+                home_of.contains(&account.id)
+            },
+            (Prepared::Status { hidden: true, .. }, _) => false,
+            (Prepared::Status { .. }, Stream::Public) => true,
+            (Prepared::Status { local, .. }, Stream::PublicLocal) => *local,
+            (Prepared::Status { hashtags, .. }, Stream::Hashtag(tag)) => {
+                hashtags.contains(&tag.to_lowercase())
+            },
+            (Prepared::Deleted(_), _) => true,
+            (Prepared::Notification { account_id, .. }, Stream::User(account)) => {
+                *account_id == account.id
+            },
+            (Prepared::Notification { .. }, _) => false,
+        };
+
+        if wanted {
+            Some(Arc::clone(event.message()))
+        } else {
+            None
+        }
+    }
+}
+
+/// An event, with what it takes to tell who should hear about it worked out once for every
+/// client listening.
+#[derive(Debug)]
+pub enum Prepared {
+    Status {
+        /// Whether its author is kept out of the public timelines.
+        hidden: bool,
+        /// Whether it was posted here.
+        local: bool,
+        /// The hashtags it uses, lowercased.
+        hashtags: Vec<String>,
+        /// The accounts listening to their user stream which have it on their home timelines.
+        home_of: Vec<i64>,
+        message: Arc<Message>,
+    },
+    Deleted(Arc<Message>),
+    Notification {
+        account_id: i64,
+        message:    Arc<Message>,
+    },
+}
+
+impl Prepared {
+    /// Prepares `event` for clients listening to streams, where `users` are the accounts
+    /// listening to their user stream.
+    pub fn new(db_conn: &DbConnection, event: &Event, users: &[i64]) -> Result<Prepared, Error> {
+        match event {
+            Event::StatusCreated(status) => {
+                let author = status.account(db_conn)?;
+                let home_of = if author.suspended {
+                    Vec::new()
+                } else {
+                    status.home_timeline_owners(db_conn, users)?
+                };
+
+                Ok(Prepared::Status {
+                    hidden: author.silenced || author.suspended,
+                    local: status.uri.is_none(),
+                    hashtags: transform::hashtags(&status.text)
+                        .iter()
+                        .map(|name| name.to_lowercase())
+                        .collect(),
+                    home_of,
+                    message: Arc::new(Message {
+                        event:   "update",
+                        payload: entities::status(db_conn, status, &author)?.to_string(),
+                    }),
+                })
+            },
+            Event::StatusDeleted { status_id, .. } => Ok(Prepared::Deleted(Arc::new(Message {
+                event:   "delete",
+                payload: status_id.to_string(),
+            }))),
+            Event::Notified(notification) => Ok(Prepared::Notification {
+                account_id: notification.account_id,
+                message:    Arc::new(Message {
+                    event:   "notification",
+                    payload: entities::notification(db_conn, notification)?.to_string(),
+                }),
+            }),
+        }
+    }
+
+    fn message(&self) -> &Arc<Message> {
+        match self {
+            Prepared::Status { message, .. }
+            | Prepared::Deleted(message)
+            | Prepared::Notification { message, .. } => message,
+        }
+    }
+}
+
+impl Message {
+    /// Formats this message as a Server-Sent Event.
+    pub fn to_sse(&self) -> String {
+        format!("event: {}\ndata: {}\n\n", self.event, self.payload)
+    }
+
+    /// Formats this message as the JSON sent over a WebSocket listening to `stream`.
+    pub fn to_json(&self, stream: &Stream) -> String {
+        json!({
+            "stream": [stream.name()],
+            "event": self.event,
+            "payload": self.payload,
+        })
+        .to_string()
+    }
+}
+
+/// A client listening to a stream, and where to send what it should hear.
+struct Listener {
+    stream:   Arc<Stream>,
+    messages: SyncSender<Arc<Message>>,
+}
+
+type Listeners = Arc<Mutex<Vec<Listener>>>;
+
+/// A client's place among the listeners, given up when it goes away.
+struct Listening {
+    listeners: Listeners,
+    stream:    Arc<Stream>,
+}
+
+impl Listening {
+    /// Starts sending the messages for `stream` to the returned receiver.
+    fn start(listeners: &Listeners, stream: &Arc<Stream>) -> (Listening, Receiver<Arc<Message>>) {
+        let (sender, receiver) = sync_channel(MAX_QUEUED_MESSAGES);
+        listeners.lock().unwrap().push(Listener {
+            stream:   Arc::clone(stream),
+            messages: sender,
+        });
+
+        let listening = Listening {
+            listeners: Arc::clone(listeners),
+            stream:    Arc::clone(stream),
+        };
+        (listening, receiver)
+    }
+}
+
+impl Drop for Listening {
+    fn drop(&mut self) {
+        let stream = &self.stream;
+        self.listeners
+            .lock()
+            .unwrap()
+            .retain(|listener| !Arc::ptr_eq(&listener.stream, stream));
+    }
+}
+
+/// Starts the streaming API on `bind`, handling each client on its own thread, up to
+/// `MAX_CONNECTIONS` of them.
+pub fn spawn(pool: db::Pool, bind: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(bind)?;
+    let listeners: Listeners = Arc::new(Mutex::new(Vec::new()));
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    {
+        let pool = pool.clone();
+        let listeners = Arc::clone(&listeners);
+        thread::spawn(move || dispatch(&pool, &listeners));
+    }
+
+    thread::spawn(move || {
+        for connection in listener.incoming() {
+            let mut connection = match connection {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!("couldn't accept a streaming connection: {}", e);
+                    continue;
+                },
+            };
+
+            if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                connections.fetch_sub(1, Ordering::SeqCst);
+                // the response is small enough not to wait for, and the client is turned
+                // away whether or not it arrives.
+                let _ = connection
+                    .set_nonblocking(true)
+                    .and_then(|_| refuse(&mut connection, 503, "Too many connections"));
+                continue;
+            }
+
+            let pool = pool.clone();
+            let listeners = Arc::clone(&listeners);
+            let connections = Arc::clone(&connections);
+            thread::spawn(move || {
+                if let Err(e) = handle(connection, &pool, &listeners) {
+                    warn!("streaming connection failed: {}", e);
+                }
+                connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    Ok(())
+}
+
+/// Hands each event on the bus to the listeners which should hear about it. Clients which
+/// have fallen too far behind are dropped, which disconnects them.
+fn dispatch(pool: &db::Pool, listeners: &Listeners) {
+    for event in events::subscribe() {
+        let users: Vec<i64> = listeners
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|listener| match *listener.stream {
+                Stream::User(ref account) => Some(account.id),
+                _ => None,
+            })
+            .collect();
+
+        let prepared = pool
+            .get()
+            .map_err(Error::from)
+            .and_then(|db_conn| Prepared::new(&db_conn, &event, &users));
+        let prepared = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                warn!("couldn't prepare an event for streaming: {}", e);
+                continue;
+            },
+        };
+
+        listeners
+            .lock()
+            .unwrap()
+            .retain(|listener| match listener.stream.message(&prepared) {
+                Some(message) => listener.messages.try_send(message).is_ok(),
+                None => true,
+            });
+    }
+}
+
+/// The parts of a request we need.
+struct RequestHead {
+    path: String,
+    params: Vec<(String, String)>,
+    authorization: Option<String>,
+    websocket_protocol: Option<String>,
+    is_upgrade: bool,
+}
+
+impl RequestHead {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the access token the client sent: as a bearer token, as the `access_token`
+    /// parameter (browsers can't set headers on these requests), or as the WebSocket protocol.
+    fn access_token(&self) -> Option<&str> {
+        self.authorization
+            .as_ref()
+            .filter(|header| header.starts_with("Bearer "))
+            .map(|header| header["Bearer ".len()..].trim())
+            .or_else(|| self.param("access_token"))
+            .or_else(|| self.websocket_protocol.as_ref().map(String::as_str))
+    }
+
+    /// Returns the name of the stream asked for, from the path (for SSE) or the `stream`
+    /// parameter (for WebSockets).
+    fn stream_name(&self) -> Option<&str> {
+        match self.path.trim_end_matches('/') {
+            "/api/v1/streaming" => self.param("stream"),
+            "/api/v1/streaming/user" => Some("user"),
+            "/api/v1/streaming/public" => Some("public"),
+            "/api/v1/streaming/public/local" => Some("public:local"),
+            "/api/v1/streaming/hashtag" => Some("hashtag"),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the head of a request, returning it along with everything read so far.
+fn read_head(connection: &mut TcpStream) -> Result<(RequestHead, Vec<u8>), Error> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 1024];
+    let deadline = Instant::now() + IO_TIMEOUT;
+
+    loop {
+        if Instant::now() > deadline {
+            return Err(format_err!("request took too long to arrive"));
+        }
+
+        let n = connection.read(&mut chunk)?;
+        if n == 0 {
+            return Err(format_err!("connection closed before the request was read"));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut request = httparse::Request::new(&mut headers);
+        if request.parse(&buffer)?.is_complete() {
+            let target = request.path.unwrap_or("/");
+            let (path, query) = match target.find('?') {
+                Some(i) => (&target[..i], &target[i + 1..]),
+                None => (target, ""),
+            };
+            let header = |name: &str| {
+                request
+                    .headers
+                    .iter()
+                    .find(|header| header.name.eq_ignore_ascii_case(name))
+                    .and_then(|header| std::str::from_utf8(header.value).ok())
+                    .map(String::from)
+            };
+
+            let head = RequestHead {
+                path: path.to_string(),
+                params: form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect(),
+                authorization: header("Authorization"),
+                websocket_protocol: header("Sec-WebSocket-Protocol"),
+                is_upgrade: header("Upgrade")
+                    .map_or(false, |upgrade| upgrade.eq_ignore_ascii_case("websocket")),
+            };
+            return Ok((head, buffer));
+        }
+
+        if buffer.len() > MAX_REQUEST_HEAD {
+            return Err(format_err!("request head too long"));
+        }
+    }
+}
+
+/// Finds the stream the client asked for, checking its token. Anyone with a `read` token
+/// may listen to the public streams; the user stream needs a token issued for a user.
+fn authorize(db_conn: &DbConnection, head: &RequestHead) -> Result<Stream, (u16, String)> {
+    let unauthorized = |msg: &str| (401, msg.to_string());
+    let internal = |_| (500, "Internal server error".to_string());
+
+    let token = match head.access_token() {
+        Some(candidate) => AccessToken::by_token(db_conn, candidate).map_err(internal)?,
+        None => None,
+    };
+    let token = token.ok_or_else(|| unauthorized("Missing access token"))?;
+    if !token.scopes().contains(Scope::Read) {
+        return Err((
+            403,
+            "This action is outside the authorized scopes".to_string(),
+        ));
+    }
+
+    let account = match token.user_id {
+        Some(uid) => {
+            let user = User::by_id(db_conn, uid)
+                .map_err(internal)?
+                .ok_or_else(|| unauthorized("Invalid access token"))?;
+            if user.is_suspended(db_conn).map_err(internal)? {
+                return Err((403, "Your account is suspended".to_string()));
+            }
+            Some(user.get_account(db_conn).map_err(internal)?)
+        },
+        None => None,
+    };
+
+    let name = head
+        .stream_name()
+        .ok_or_else(|| (404, "Unknown stream".to_string()))?;
+    Stream::parse(name, head.param("tag"), account).map_err(|e| (400, e.to_string()))
+}
+
+/// Answers a request we won't stream to with `status` and a JSON `error`.
+fn refuse(connection: &mut TcpStream, status: u16, error: &str) -> io::Result<()> {
+    let body = json!({ "error": error }).to_string();
+    write!(
+        connection,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        match status {
+            401 => "Unauthorized",
+            503 => "Service Unavailable",
+            _ => "Error",
+        },
+        body.len(),
+        body
+    )
+}
+
+fn handle(mut connection: TcpStream, pool: &db::Pool, listeners: &Listeners) -> Result<(), Error> {
+    connection.set_read_timeout(Some(IO_TIMEOUT))?;
+    connection.set_write_timeout(Some(IO_TIMEOUT))?;
+    let (head, read) = read_head(&mut connection)?;
+
+    let stream = {
+        let db_conn = pool.get()?;
+        authorize(&db_conn, &head)
+    };
+    let stream = match stream {
+        Ok(stream) => Arc::new(stream),
+        Err((status, error)) => {
+            refuse(&mut connection, status, &error)?;
+            return Ok(());
+        },
+    };
+
+    // listen before answering, so nothing that happens from here on is missed.
+    let (_listening, messages) = Listening::start(listeners, &stream);
+
+    if head.is_upgrade {
+        let protocol = head.websocket_protocol.clone();
+        let replayed = Replayed {
+            read:  Cursor::new(read),
+            inner: connection,
+        };
+        let config = WebSocketConfig {
+            max_send_queue:   None,
+            max_message_size: Some(MAX_REQUEST_HEAD),
+            max_frame_size:   Some(MAX_REQUEST_HEAD),
+        };
+        let mut websocket = tungstenite::server::accept_hdr_with_config(
+            replayed,
+            |_: &Request, mut response: Response| -> Result<Response, ErrorResponse> {
+                if let Some(protocol) = protocol.and_then(|p| p.parse().ok()) {
+                    response
+                        .headers_mut()
+                        .insert("Sec-WebSocket-Protocol", protocol);
+                }
+                Ok(response)
+            },
+            Some(config),
+        )
+        .map_err(|e| format_err!("WebSocket handshake failed: {}", e))?;
+
+        // reads only wait long enough for the events in between to go out promptly.
+        websocket
+            .get_mut()
+            .inner
+            .set_read_timeout(Some(WEBSOCKET_POLL_INTERVAL))?;
+        let mut last_sent = Instant::now();
+
+        loop {
+            // clients have nothing to tell us, but reading is what answers their pings and
+            // closes.
+            match websocket.read_message() {
+                Ok(WsMessage::Close(_)) => {
+                    let _ = websocket.write_pending();
+                    return Ok(());
+                },
+                Ok(_) => {},
+                Err(WsError::Io(ref e))
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {},
+                // the client went away.
+                Err(_) => return Ok(()),
+            }
+
+            loop {
+                let message = match messages.try_recv() {
+                    Ok(message) => WsMessage::Text(message.to_json(&stream)),
+                    Err(TryRecvError::Empty) if last_sent.elapsed() >= HEARTBEAT_INTERVAL => {
+                        WsMessage::Ping(Vec::new())
+                    },
+                    Err(TryRecvError::Empty) => break,
+                    // the client fell too far behind.
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                };
+
+                if websocket.write_message(message).is_err() {
+                    // the client went away.
+                    return Ok(());
+                }
+                last_sent = Instant::now();
+            }
+        }
+    } else {
+        connection.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+              Connection: keep-alive\r\n\r\n",
+        )?;
+
+        loop {
+            let message = match messages.recv_timeout(HEARTBEAT_INTERVAL) {
+                Ok(message) => message.to_sse(),
+                Err(RecvTimeoutError::Timeout) => ":thump\n\n".to_string(),
+                // the client fell too far behind.
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            };
+
+            if connection.write_all(message.as_bytes()).is_err() {
+                // the client went away.
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A connection which first gives back what was already read from it, so the WebSocket
+/// handshake can read the request we've looked at.
+struct Replayed {
+    read:  Cursor<Vec<u8>>,
+    inner: TcpStream,
+}
+
+impl Read for Replayed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.read.read(buf)? {
+            0 => self.inner.read(buf),
+            n => Ok(n),
+        }
+    }
+}
+
+impl Write for Replayed {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_stream_names() {
+        assert_eq!(
+            Stream::parse("public", None, None).unwrap().name(),
+            "public"
+        );
+        assert_eq!(
+            Stream::parse("public:local", None, None).unwrap().name(),
+            "public:local"
+        );
+        assert!(Stream::parse("user", None, None).is_err());
+        assert!(Stream::parse("hashtag", None, None).is_err());
+        assert!(Stream::parse("hashtag", Some("rust"), None).is_ok());
+        assert!(Stream::parse("direct", None, None).is_err());
+    }
+
+    fn prepared_status(hidden: bool, local: bool) -> Prepared {
+        Prepared::Status {
+            hidden,
+            local,
+            hashtags: vec!["rust".to_string()],
+            home_of: vec![1],
+            message: Arc::new(Message {
+                event:   "update",
+                payload: "{}".to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn it_picks_the_statuses_each_stream_wants() {
+        let rust = Stream::Hashtag("Rust".to_string());
+        let go = Stream::Hashtag("go".to_string());
+
+        let status = prepared_status(false, false);
+        assert!(Stream::Public.message(&status).is_some());
+        assert!(Stream::PublicLocal.message(&status).is_none());
+        assert!(rust.message(&status).is_some());
+        assert!(go.message(&status).is_none());
+
+        let local = prepared_status(false, true);
+        assert!(Stream::PublicLocal.message(&local).is_some());
+
+        let hidden = prepared_status(true, true);
+        assert!(Stream::Public.message(&hidden).is_none());
+        assert!(Stream::PublicLocal.message(&hidden).is_none());
+        assert!(rust.message(&hidden).is_none());
+    }
+
+    #[test]
+    fn it_keeps_notifications_to_user_streams() {
+        let notification = Prepared::Notification {
+            account_id: 1,
+            message:    Arc::new(Message {
+                event:   "notification",
+                payload: "{}".to_string(),
+            }),
+        };
+
+        assert!(Stream::Public.message(&notification).is_none());
+        assert!(Stream::Hashtag("rust".to_string())
+            .message(&notification)
+            .is_none());
+    }
+
+    #[test]
+    fn it_formats_messages() {
+        let message = Message {
+            event:   "delete",
+            payload: "123".to_string(),
+        };
+
+        assert_eq!(message.to_sse(), "event: delete\ndata: 123\n\n");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&message.to_json(&Stream::Public)).unwrap(),
+            json!({"stream": ["public"], "event": "delete", "payload": "123"})
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Time-based one-time passwords (RFC 6238), as used for two-factor authentication.
+//!
+//! We only do what authenticator apps expect by default: HMAC-SHA1, six digits, and a new
+//! code every thirty seconds.
+
+use failure::Error;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+
+/// How long each code is valid for, in seconds.
+const STEP: i64 = 30;
+
+/// How many digits a code has.
+const DIGITS: u32 = 6;
+
+/// How many steps either side of the current one we accept codes from, to allow for
+/// clocks being a bit off.
+const SKEW: i64 = 1;
+
+/// Number of random bytes in a secret; 160 bits, as recommended for HMAC-SHA1.
+const SECRET_BYTES: usize = 20;
+
+const SECRET_ALPHABET: base32::Alphabet = base32::Alphabet::RFC4648 { padding: false };
+
+/// Returns a fresh secret, base32-encoded as authenticator apps expect.
+pub fn generate_secret() -> Result<String, Error> {
+    let mut bytes = [0; SECRET_BYTES];
+    rand_bytes(&mut bytes)?;
+
+    Ok(base32::encode(SECRET_ALPHABET, &bytes))
+}
+
+/// Computes the HOTP (RFC 4226) value of the raw `key` for `counter`.
+fn hotp(key: &[u8], counter: u64, digits: u32) -> Result<u32, Error> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key)?;
+    signer.update(&counter.to_be_bytes())?;
+    let mac = signer.sign_to_vec()?;
+
+    // "dynamic truncation": the low nibble of the last byte picks where to read 31 bits from
+    let offset = (mac[mac.len() - 1] & 0xf) as usize;
+    let value = (u32::from(mac[offset]) & 0x7f) << 24
+        | u32::from(mac[offset + 1]) << 16
+        | u32::from(mac[offset + 2]) << 8
+        | u32::from(mac[offset + 3]);
+
+    Ok(value % 10u32.pow(digits))
+}
+
+/// Returns the code for `secret` at the Unix time `now`, zero-padded to six digits.
+pub fn code_at(secret: &str, now: i64) -> Result<String, Error> {
+    let key = base32::decode(SECRET_ALPHABET, secret)
+        .ok_or_else(|| failure::err_msg("malformed TOTP secret"))?;
+    let code = hotp(&key, (now / STEP) as u64, DIGITS)?;
+
+    Ok(format!("{:01$}", code, DIGITS as usize))
+}
+
+/// Checks `code` (ignoring any spaces in it) against `secret` at the Unix time `now`,
+/// returning the time step it's the code for if it's valid.
+///
+/// Steps only go up, so remembering the last one used lets the caller refuse to accept the
+/// same code (or an older one) twice.
+pub fn verify(secret: &str, code: &str, now: i64) -> Option<i64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+
+    (-SKEW..=SKEW)
+        .map(|skew| now / STEP + skew)
+        .find(|&step| match code_at(secret, step * STEP) {
+            Ok(expected) => openssl::memcmp::eq(expected.as_bytes(), code.as_bytes()),
+            Err(_) => false,
+        })
+}
+
+/// Returns the `otpauth://` URI authenticator apps use to enroll `secret`, labelled
+/// with `account` (eg a fully-qualified username) at `issuer` (eg the instance's domain).
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    let escape = |s: &str| {
+        s.replace('%', "%25")
+            .replace(' ', "%20")
+            .replace(':', "%3A")
+    };
+
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}",
+        issuer = escape(issuer),
+        account = escape(account),
+        secret = secret,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the secret from the test vectors in RFC 6238, appendix B ("12345678901234567890")
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn it_matches_the_rfc_test_vectors() {
+        for &(time, code) in &[
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_111_111_111, "050471"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+        ] {
+            assert_eq!(code_at(SECRET, time).unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn it_verifies_codes_with_some_skew() {
+        assert_eq!(verify(SECRET, "287082", 59), Some(1));
+        assert_eq!(verify(SECRET, "287 082", 59), Some(1));
+        assert_eq!(verify(SECRET, "287082", 59 + STEP), Some(1));
+        assert_eq!(verify(SECRET, "287082", 59 + 2 * STEP), None);
+        assert_eq!(verify(SECRET, "287083", 59), None);
+        assert_eq!(verify(SECRET, "", 59), None);
+    }
+
+    #[test]
+    fn secrets_round_trip() {
+        let secret = generate_secret().unwrap();
+        assert_eq!(
+            base32::decode(SECRET_ALPHABET, &secret).unwrap().len(),
+            SECRET_BYTES
+        );
+        assert!(code_at(&secret, 0).is_ok());
+    }
+
+    #[test]
+    fn it_builds_provisioning_uris() {
+        assert_eq!(
+            provisioning_uri("ABC", "alice@example.com", "example.com"),
+            "otpauth://totp/example.com:alice@example.com?secret=ABC&issuer=example.com"
+        );
+    }
+}
@@ -2,12 +2,19 @@ use ammonia::{Builder, Url};
 use failure::Error;
 use lazy_static::lazy_static;
 use maplit::hashset;
+use posticle::render::{Html, PlainText, Render};
 use posticle::tokens::*;
 use posticle::{ReaderBuilder, WriterBuilder};
 use regex::Regex;
 
-use crate::db::models::Account;
+use crate::db::models::{Account, CustomEmoji};
+use crate::db::validators::VALID_EMOJI_SHORTCODE_RE;
 use crate::error::Perhaps;
+use crate::DOMAIN;
+
+/// The version of `status`' output. Bump it whenever statuses render differently, so the HTML
+/// cached for them is rendered again.
+pub const STATUS_HTML_VERSION: i32 = 2;
 
 lazy_static! {
     /// Matches all valid characters in a hashtag name (after the first #).
@@ -17,11 +24,228 @@ lazy_static! {
     static ref VALID_MENTION_USERNAME_RE: Regex = Regex::new(r"^(?i)[a-z0-9_]+([a-z0-9_\.]+[a-z0-9_]+)?$").unwrap();
 }
 
-pub fn bio<L>(text: &str, account_lookup: L) -> Result<String, Error>
+/// Returns the (valid) mentions in a status' text.
+pub fn mentions(text: &str) -> Vec<Mention> {
+    ReaderBuilder::new()
+        .with_str(text)
+        .finish()
+        .filter_map(|token| match token {
+            Token::Mention(mention) if VALID_MENTION_USERNAME_RE.is_match(&mention.username) => {
+                Some(mention)
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the names of the (valid) hashtags in a status' text.
+pub fn hashtags(text: &str) -> Vec<String> {
+    ReaderBuilder::new()
+        .with_str(text)
+        .finish()
+        .filter_map(|token| match token {
+            Token::Hashtag(hashtag) if VALID_HASHTAG_NAME_RE.is_match(&hashtag.name) => {
+                Some(hashtag.name)
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the (valid) shortcodes of the custom emoji in a status' text, without colons.
+pub fn emoji_shortcodes(text: &str) -> Vec<String> {
+    ReaderBuilder::new()
+        .with_str(text)
+        .finish()
+        .filter_map(|token| match token {
+            Token::Emoticon(emoticon) if VALID_EMOJI_SHORTCODE_RE.is_match(&emoticon.name) => {
+                Some(emoticon.name)
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Flattens the HTML content of a remote status into the plain text we store for statuses.
+///
+/// Mentions keep the domain of the account they mention, so they can't be mistaken for
+/// mentions of local accounts when the text is read again, and links whose text isn't their
+/// address are followed by it, like `text (https://example.com)`.
+pub fn html_to_text(html: &str) -> String {
+    let transformer = |token| match token {
+        Token::Element(mut element) => {
+            let href = element
+                .attributes
+                .iter()
+                .find(|(name, _)| name == "href")
+                .map(|(_, href)| href.clone())
+                .filter(|href| {
+                    Url::parse(href).map_or(false, |url| {
+                        url.scheme() == "http" || url.scheme() == "https"
+                    })
+                });
+
+            if let (Some(href), "a") = (href, element.name.as_str()) {
+                element.children.push(Token::Text(Text {
+                    text: format!(" ({})", href),
+                }));
+            }
+            Token::Element(element)
+        },
+        _ => token,
+    };
+
+    let reader = ReaderBuilder::new()
+        .html()
+        .with_transformer(Box::new(transformer))
+        .with_str(html)
+        .finish();
+
+    WriterBuilder::new()
+        .with_renderer(PlainText)
+        .with_reader(reader)
+        .finish()
+        .to_string()
+}
+
+/// Renders an account's bio as HTML, turning links, mentions and hashtags into anchors and
+/// custom emoji into images.
+pub fn bio<L, E>(text: &str, account_lookup: L, emoji_lookup: E) -> Result<String, Error>
+where
+    L: Fn(&str, Option<&str>) -> Perhaps<Account>,
+    E: Fn(&str) -> Perhaps<CustomEmoji>,
+{
+    linkify(text, account_lookup, emoji_lookup)
+}
+
+/// Renders an account's display name as HTML, turning custom emoji into images. Nothing
+/// else in a name is linked.
+pub fn display_name<E>(name: &str, emoji_lookup: E) -> Result<String, Error>
+where
+    E: Fn(&str) -> Perhaps<CustomEmoji>,
+{
+    let mut html_sanitizer = Builder::default();
+
+    html_sanitizer
+        .tags(hashset!["img"])
+        .add_allowed_classes("img", &["custom-emoji"]);
+
+    render_linked(
+        name,
+        |_, _| Ok(None),
+        emoji_lookup,
+        Html::new(html_sanitizer),
+    )
+}
+
+/// Renders an account's bio with `renderer`, like `bio` but in any format: plain text drops
+/// the links, and Markdown writes them as Markdown links.
+pub fn bio_with<L, E, R>(
+    text: &str,
+    account_lookup: L,
+    emoji_lookup: E,
+    renderer: R,
+) -> Result<String, Error>
 where
     L: Fn(&str, Option<&str>) -> Perhaps<Account>,
+    E: Fn(&str) -> Perhaps<CustomEmoji>,
+    R: Render,
+{
+    render_linked(text, account_lookup, emoji_lookup, renderer)
+}
+
+/// Renders the text of a status as HTML, the same way as bios except that blank lines split
+/// it into paragraphs.
+pub fn status<L, E>(text: &str, account_lookup: L, emoji_lookup: E) -> Result<String, Error>
+where
+    L: Fn(&str, Option<&str>) -> Perhaps<Account>,
+    E: Fn(&str) -> Perhaps<CustomEmoji>,
+{
+    let text = text.replace("\r\n", "\n");
+    let paragraphs = text
+        .split("\n\n")
+        .map(|paragraph| paragraph.trim_matches('\n'))
+        .filter(|paragraph| !paragraph.is_empty());
+
+    let mut html = String::new();
+    for paragraph in paragraphs {
+        html.push_str(&linkify(paragraph, &account_lookup, &emoji_lookup)?);
+    }
+    Ok(html)
+}
+
+/// Turns links, mentions of known accounts and hashtags in `text` into anchors, and known
+/// custom emoji into images, escaping and sanitizing everything else.
+fn linkify<L, E>(text: &str, account_lookup: L, emoji_lookup: E) -> Result<String, Error>
+where
+    L: Fn(&str, Option<&str>) -> Perhaps<Account>,
+    E: Fn(&str) -> Perhaps<CustomEmoji>,
+{
+    let mut html_sanitizer = Builder::default();
+
+    // The emphasis and code tags only come from posticle's `markdown` feature.
+    html_sanitizer
+        .tags(hashset![
+            "br",
+            "a",
+            "img",
+            "strong",
+            "em",
+            "code",
+            "pre",
+            "blockquote"
+        ])
+        .add_allowed_classes("img", &["custom-emoji"])
+        .link_rel(Some("noopener nofollow"));
+
+    let html = render_linked(
+        text,
+        account_lookup,
+        emoji_lookup,
+        Html::new(html_sanitizer),
+    )?;
+
+    Ok(format!("<p>{}</p>", html))
+}
+
+/// Reads `text`, turning links, mentions of known accounts and hashtags into `<a>`
+/// elements and known custom emoji into `<img>` elements, and writes it out with `renderer`.
+fn render_linked<L, E, R>(
+    text: &str,
+    account_lookup: L,
+    emoji_lookup: E,
+    renderer: R,
+) -> Result<String, Error>
+where
+    L: Fn(&str, Option<&str>) -> Perhaps<Account>,
+    E: Fn(&str) -> Perhaps<CustomEmoji>,
+    R: Render,
 {
     let transformer = |token| match token {
+        Token::Emoticon(emoticon) => {
+            let lookup = if VALID_EMOJI_SHORTCODE_RE.is_match(&emoticon.name) {
+                emoji_lookup(&emoticon.name)
+            } else {
+                Ok(None)
+            };
+
+            if let Ok(Some(emoji)) = lookup {
+                let shortcode = format!(":{}:", emoji.shortcode);
+
+                Token::Element(Element {
+                    name: "img".to_string(),
+                    attributes: vec![
+                        ("src".to_string(), emoji.image_url()),
+                        ("alt".to_string(), shortcode.clone()),
+                        ("title".to_string(), shortcode),
+                        ("class".to_string(), "custom-emoji".to_string()),
+                    ],
+                    children: Vec::new(),
+                })
+            } else {
+                Token::Emoticon(emoticon)
+            }
+        },
         Token::Hashtag(hashtag) => {
             if VALID_HASHTAG_NAME_RE.is_match(&hashtag.name) {
                 Token::Element(Element {
@@ -42,8 +266,10 @@ where
                 match url.scheme() {
                     "http" | "https" => Token::Element(Element {
                         name: "a".to_string(),
-                        attributes: vec![("href".to_string(), link.url.clone())],
-                        children: vec![Token::Text(Text { text: link.url })],
+                        attributes: vec![("href".to_string(), link.ascii_url())],
+                        children: vec![Token::Text(Text {
+                            text: link.unicode_url(),
+                        })],
                     }),
                     _ => Token::Link(link),
                 }
@@ -53,15 +279,18 @@ where
         },
         Token::Mention(mention) => {
             if VALID_MENTION_USERNAME_RE.is_match(&mention.username) {
-                let lookup = account_lookup(
-                    &mention.username,
-                    mention.domain.as_ref().map(String::as_str),
-                );
+                // domains are looked up in ASCII, but shown in Unicode. mentions of our own
+                // domain (as remote statuses write them) are of local accounts, which are
+                // stored without one.
+                let domain = mention
+                    .ascii_domain()
+                    .filter(|domain| !domain.eq_ignore_ascii_case(&DOMAIN));
+                let lookup = account_lookup(&mention.username, domain.as_ref().map(String::as_str));
 
                 if let Ok(Some(account)) = lookup {
                     let mut name = format!("@{}", mention.username);
 
-                    if let Some(domain) = &mention.domain {
+                    if let Some(domain) = mention.unicode_domain() {
                         name.push_str(&format!("@{}", domain));
                     }
 
@@ -80,62 +309,165 @@ where
         _ => token,
     };
 
-    let mut html_sanitizer = Builder::default();
-
-    html_sanitizer
-        .tags(hashset!["br", "a"])
-        .link_rel(Some("noopener nofollow"));
-
     let reader = ReaderBuilder::new()
         .with_transformer(Box::new(transformer))
         .with_str(text)
         .finish();
-    let html = WriterBuilder::new()
-        .with_html_sanitizer(html_sanitizer)
+    let writer = WriterBuilder::new()
+        .with_renderer(renderer)
         .with_reader(reader)
         .finish();
 
-    Ok(format!("<p>{}</p>", html.to_string()))
+    Ok(writer.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn finds_hashtags() {
+        assert_eq!(
+            hashtags("#rust and #Rustodon, not # or #-"),
+            vec!["rust".to_string(), "Rustodon".to_string()]
+        );
+    }
+
+    #[test]
+    fn finds_mentions() {
+        let mentions = mentions("hi @foo and @bar@remote.example, not @-baz");
+        assert_eq!(
+            mentions,
+            vec![
+                Mention {
+                    username: "foo".to_string(),
+                    domain:   None,
+                },
+                Mention {
+                    username: "bar".to_string(),
+                    domain:   Some("remote.example".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn flattens_html_to_text() {
+        assert_eq!(
+            html_to_text(
+                "<p>hello <a href=\"https://x.example\">world</a></p><p>a &amp;&lt;b&gt;<br/>c</p>"
+            ),
+            "hello world (https://x.example)\na &<b>\nc"
+        );
+    }
+
+    #[test]
+    fn keeps_the_addresses_of_links() {
+        let text = html_to_text(
+            "<p><a href=\"https://x.example/a\">https://x.example/a</a> \
+             <a href=\"https://x.example/b\">b</a> <a href=\"javascript:alert(1)\">c</a></p>",
+        );
+        assert_eq!(text, "https://x.example/a b (https://x.example/b) c");
+
+        // the address is still a link when the text is rendered.
+        assert_eq!(
+            status(&text, |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p><a href=\"https://x.example/a\" rel=\"noopener nofollow\">https://x.example/a</a> \
+             b (<a href=\"https://x.example/b\" rel=\"noopener nofollow\">https://x.example/b</a>) c</p>"
+        );
+    }
+
+    #[test]
+    fn keeps_the_domains_of_remote_mentions() {
+        assert_eq!(
+            html_to_text(
+                "<p><span class=\"h-card\"><a href=\"https://remote.example/@foo\" class=\"u-url mention\">@<span>foo</span></a></span> <a href=\"https://remote.example/tags/bar\" class=\"mention hashtag\" rel=\"tag\">#<span>bar</span></a></p>"
+            ),
+            "@foo@remote.example #bar"
+        );
+    }
+
     #[test]
     fn passes_through_text() {
-        assert_eq!(bio("foo", |_, _| Ok(None)).unwrap(), "<p>foo</p>");
-        assert_eq!(bio("foo:bar", |_, _| Ok(None)).unwrap(), "<p>foo:bar</p>");
+        assert_eq!(
+            bio("foo", |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p>foo</p>"
+        );
+        assert_eq!(
+            bio("foo:bar", |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p>foo:bar</p>"
+        );
+    }
+
+    #[test]
+    fn renders_status_paragraphs() {
+        let text = "<b>hi</b> #rust\n\n\nhttps://example.com\r\nbye\n\n";
+        assert_eq!(
+            status(text, |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p>&lt;b&gt;hi&lt;/b&gt; <a href=\"#\" rel=\"noopener nofollow\">#rust</a></p>\
+             <p><a href=\"https://example.com\" rel=\"noopener nofollow\">https://example.com</a>\
+             \n<br>bye</p>"
+        );
+        assert_eq!(status("\n\n", |_, _| Ok(None), |_| Ok(None)).unwrap(), "");
+    }
+
+    #[test]
+    fn renders_bios_as_other_formats() {
+        use posticle::render::Markdown;
+
+        let text = "<b>hi</b> #rust\nhttps://example.com";
+        assert_eq!(
+            bio_with(text, |_, _| Ok(None), |_| Ok(None), PlainText).unwrap(),
+            "<b>hi</b> #rust\nhttps://example.com"
+        );
+        assert_eq!(
+            bio_with(text, |_, _| Ok(None), |_| Ok(None), Markdown).unwrap(),
+            "\\<b\\>hi\\</b\\> [\\#rust](<#>)  \n[https://example.com](<https://example.com>)"
+        );
     }
 
     #[test]
     fn escapes_html_characters() {
-        assert_eq!(bio("<>&", |_, _| Ok(None)).unwrap(), "<p>&lt;&gt;&amp;</p>");
         assert_eq!(
-            bio("<a></a>", |_, _| Ok(None)).unwrap(),
+            bio("<>&", |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p>&lt;&gt;&amp;</p>"
+        );
+        assert_eq!(
+            bio("<a></a>", |_, _| Ok(None), |_| Ok(None)).unwrap(),
             "<p>&lt;a&gt;&lt;/a&gt;</p>"
         );
     }
 
     #[test]
     fn converts_newlines_to_br() {
-        assert_eq!(bio("\n", |_, _| Ok(None)).unwrap(), "<p>\n<br></p>");
-        assert_eq!(bio("\r\n", |_, _| Ok(None)).unwrap(), "<p>\n<br></p>");
+        assert_eq!(
+            bio("\n", |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p>\n<br></p>"
+        );
+        assert_eq!(
+            bio("\r\n", |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p>\n<br></p>"
+        );
     }
 
     #[test]
     fn converts_links_to_a_tags() {
         assert_eq!(
-            bio("https://example.com", |_, _| Ok(None)).unwrap(),
+            bio("https://example.com", |_, _| Ok(None), |_| Ok(None)).unwrap(),
             "<p><a href=\"https://example.com\" rel=\"noopener nofollow\">https://example.com</a></p>"
         );
         assert_eq!(
-            bio("http://example.com", |_, _| Ok(None)).unwrap(),
+            bio("http://example.com", |_, _| Ok(None), |_| Ok(None)).unwrap(),
             "<p><a href=\"http://example.com\" rel=\"noopener nofollow\">http://example.com</a></p>"
         );
         assert_eq!(
-            bio("http://‽.com/∰/", |_, _| Ok(None)).unwrap(),
-            "<p><a href=\"http://‽.com/∰/\" rel=\"noopener nofollow\">http://‽.com/∰/</a></p>"
+            bio("http://‽.com/∰/", |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p><a href=\"http://xn--fwg.com/∰/\" rel=\"noopener nofollow\">http://‽.com/∰/</a></p>"
+        );
+        assert_eq!(
+            bio("https://xn--bcher-kva.example/ https://Bücher.example/", |_, _| Ok(None), |_| Ok(None)).unwrap(),
+            "<p><a href=\"https://xn--bcher-kva.example/\" rel=\"noopener nofollow\">https://bücher.example/</a> \
+             <a href=\"https://xn--bcher-kva.example/\" rel=\"noopener nofollow\">https://Bücher.example/</a></p>"
         );
     }
 
@@ -143,13 +475,14 @@ mod tests {
     fn converts_hashtags_to_links() {
         // TODO: we don't have hashtags atm, so we just fake-link them!
         assert_eq!(
-            bio("#hashtag", |_, _| Ok(None)).unwrap(),
+            bio("#hashtag", |_, _| Ok(None), |_| Ok(None)).unwrap(),
             "<p><a href=\"#\" rel=\"noopener nofollow\">#hashtag</a></p>"
         );
     }
 
     #[test]
     fn converts_mentions_to_links() {
+        use chrono::offset::Utc;
         use std::env;
         env::set_var("DOMAIN", "localhost"); // TODO: this is bad and should go away, _somehow_.
 
@@ -164,6 +497,12 @@ mod tests {
                     summary: None,
                     pubkey: Vec::new(),
                     privkey: None,
+                    silenced: false,
+                    suspended: false,
+                    force_sensitive: false,
+                    locked: false,
+                    inbox_uri: None,
+                    created_at: Utc::now(),
                 }),
                 ("remotefoo", Some("remote.example")) => Some(Account {
                     id: 1,
@@ -174,19 +513,116 @@ mod tests {
                     summary: None,
                     pubkey: Vec::new(),
                     privkey: None,
+                    silenced: false,
+                    suspended: false,
+                    force_sensitive: false,
+                    locked: false,
+                    inbox_uri: None,
+                    created_at: Utc::now(),
                 }),
                 _ => None,
             })
         }
 
         assert_eq!(
-            bio("@localfoo", acct_lookup).unwrap(),
+            bio("@localfoo", acct_lookup, |_| Ok(None)).unwrap(),
             "<p><a href=\"https://localhost/users/localfoo\" rel=\"noopener nofollow\">@localfoo</a></p>"
         );
         assert_eq!(
-            bio("@remotefoo@remote.example", acct_lookup).unwrap(),
+            bio("@remotefoo@remote.example", acct_lookup, |_| Ok(None)).unwrap(),
             "<p><a href=\"https://remote.example/remotefoo\" rel=\"noopener nofollow\">@remotefoo@remote.example</a></p>"
         );
-        assert_eq!(bio("@invalid", acct_lookup).unwrap(), "<p>@invalid</p>");
+        assert_eq!(
+            bio("@remotefoo@Remote.Example", acct_lookup, |_| Ok(None)).unwrap(),
+            "<p><a href=\"https://remote.example/remotefoo\" rel=\"noopener nofollow\">@remotefoo@Remote.Example</a></p>"
+        );
+        // other tests may have already fixed DOMAIN to something else.
+        let own_mention = format!("@localfoo@{}", *DOMAIN);
+        let own_mention_link = format!(
+            "<p><a href=\"https://localhost/users/localfoo\" rel=\"noopener nofollow\">{}</a></p>",
+            own_mention
+        );
+        assert_eq!(
+            bio(&own_mention, acct_lookup, |_| Ok(None)).unwrap(),
+            own_mention_link
+        );
+        assert_eq!(
+            status(
+                &html_to_text(&format!(
+                    "<p><a href=\"https://{}/users/localfoo\" class=\"u-url mention\">@<span>localfoo</span></a></p>",
+                    *DOMAIN
+                )),
+                acct_lookup,
+                |_| Ok(None)
+            )
+            .unwrap(),
+            own_mention_link
+        );
+        assert_eq!(
+            bio("@invalid", acct_lookup, |_| Ok(None)).unwrap(),
+            "<p>@invalid</p>"
+        );
+    }
+
+    #[test]
+    fn renders_display_names() {
+        use chrono::offset::Utc;
+        use std::env;
+        env::set_var("DOMAIN", "localhost");
+
+        let emoji_lookup = |shortcode: &str| {
+            Ok(Some(CustomEmoji {
+                id: 0,
+                shortcode: shortcode.to_string(),
+                domain: "remote.example".to_string(),
+                image: format!("emoji/remote/remote.example/{}.png", shortcode),
+                uri: None,
+                remote_image_url: None,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            }))
+        };
+
+        assert_eq!(
+            display_name("<b>cat</b> :blobcat: #cats https://example.com", emoji_lookup).unwrap(),
+            "&lt;b&gt;cat&lt;/b&gt; <img src=\"https://localhost/media/emoji/remote/remote.example/blobcat.png\" \
+             alt=\":blobcat:\" title=\":blobcat:\" class=\"custom-emoji\"> #cats https://example.com"
+        );
+    }
+
+    #[test]
+    fn converts_custom_emoji_to_images() {
+        use chrono::offset::Utc;
+        use std::env;
+        env::set_var("DOMAIN", "localhost");
+
+        fn emoji_lookup(shortcode: &str) -> Perhaps<CustomEmoji> {
+            Ok(match shortcode {
+                "blobcat" => Some(CustomEmoji {
+                    id: 0,
+                    shortcode: "blobcat".to_string(),
+                    domain: "".to_string(),
+                    image: "emoji/blobcat.png".to_string(),
+                    uri: None,
+                    remote_image_url: None,
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                }),
+                _ => None,
+            })
+        }
+
+        assert_eq!(
+            bio("hi :blobcat: :unknown:", |_, _| Ok(None), emoji_lookup).unwrap(),
+            "<p>hi <img src=\"https://localhost/media/emoji/blobcat.png\" alt=\":blobcat:\" title=\":blobcat:\" class=\"custom-emoji\"> :unknown:</p>"
+        );
+        assert_eq!(
+            bio_with(":blobcat:", |_, _| Ok(None), emoji_lookup, PlainText).unwrap(),
+            ":blobcat:"
+        );
+        assert_eq!(
+            emoji_shortcodes(":blobcat: :x: :a_b:"),
+            vec!["blobcat", "a_b"]
+        );
     }
 }